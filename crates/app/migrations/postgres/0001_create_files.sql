@@ -0,0 +1,10 @@
+-- "C" compares bytes like sqlite, which keeps sorting and the prefix ranges of the repositories the same
+CREATE TABLE IF NOT EXISTS files (
+    id bigserial NOT NULL PRIMARY KEY,
+    name text COLLATE "C" NOT NULL,
+    path text COLLATE "C" NOT NULL,
+    mime text COLLATE "C" NOT NULL,
+    size bigint NOT NULL,
+    group_id text COLLATE "C" NOT NULL,
+    group_member_name text COLLATE "C" NOT NULL
+);
@@ -0,0 +1,18 @@
+-- like the fts5 table of sqlite, names and paths are split at every character that is not alphanumeric
+CREATE TABLE IF NOT EXISTS files_search (
+    id bigint NOT NULL PRIMARY KEY REFERENCES files (id) ON DELETE CASCADE,
+    title text NOT NULL,
+    name text NOT NULL,
+    path text NOT NULL,
+    tags text NOT NULL,
+    plot text NOT NULL,
+    document tsvector GENERATED ALWAYS AS (
+        setweight(to_tsvector('simple', title), 'A') ||
+        setweight(to_tsvector('simple', regexp_replace(name, '[^[:alnum:]]+', ' ', 'g')), 'A') ||
+        setweight(to_tsvector('simple', regexp_replace(path, '[^[:alnum:]]+', ' ', 'g')), 'C') ||
+        setweight(to_tsvector('simple', tags), 'B') ||
+        setweight(to_tsvector('simple', plot), 'D')
+    ) STORED
+);
+
+CREATE INDEX IF NOT EXISTS files_search_document ON files_search USING GIN (document);
@@ -0,0 +1,9 @@
+CREATE TABLE users (
+    id bigserial NOT NULL PRIMARY KEY,
+    name text NOT NULL UNIQUE,
+    -- argon2 in the PHC string format
+    password_hash text NOT NULL,
+    created_at bigint NOT NULL,
+    -- incremented to end every session of the user, e.g. on logout
+    session_epoch bigint NOT NULL DEFAULT 0
+);
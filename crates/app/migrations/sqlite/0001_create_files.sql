@@ -0,0 +1,10 @@
+-- "IF NOT EXISTS" adopts databases that were created before migrations existed
+CREATE TABLE IF NOT EXISTS `files` (
+    `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+    `name` text NOT NULL,
+    `path` text NOT NULL,
+    `mime` text NOT NULL,
+    `size` integer NOT NULL,
+    `group_id` text NOT NULL,
+    `group_member_name` text NOT NULL
+);
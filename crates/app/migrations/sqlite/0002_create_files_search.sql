@@ -0,0 +1,12 @@
+CREATE VIRTUAL TABLE IF NOT EXISTS files_search USING fts5(
+    title,
+    name,
+    path,
+    tags,
+    plot,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+-- files of older databases are searchable by name and path until the next scan
+INSERT INTO files_search (rowid, title, name, path, tags, plot)
+SELECT id, name, name, path, '', '' FROM files WHERE id NOT IN (SELECT rowid FROM files_search);
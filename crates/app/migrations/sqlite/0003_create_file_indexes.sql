@@ -0,0 +1,3 @@
+CREATE INDEX IF NOT EXISTS files_group ON files (group_id, group_member_name);
+CREATE INDEX IF NOT EXISTS files_mime ON files (mime);
+CREATE INDEX IF NOT EXISTS files_path ON files (path);
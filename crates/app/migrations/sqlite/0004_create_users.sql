@@ -0,0 +1,9 @@
+CREATE TABLE `users` (
+    `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+    `name` text NOT NULL UNIQUE,
+    -- argon2 in the PHC string format
+    `password_hash` text NOT NULL,
+    `created_at` integer NOT NULL,
+    -- incremented to end every session of the user, e.g. on logout
+    `session_epoch` integer NOT NULL DEFAULT 0
+);
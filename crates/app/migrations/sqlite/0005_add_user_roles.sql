@@ -0,0 +1,4 @@
+-- one of "admin", "member" or "guest"
+ALTER TABLE `users` ADD COLUMN `role` text NOT NULL DEFAULT 'guest';
+-- only the admin of the first run could create accounts before roles existed
+UPDATE `users` SET `role` = 'admin';
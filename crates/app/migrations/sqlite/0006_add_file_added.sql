@@ -0,0 +1,4 @@
+-- seconds since the unix epoch when a scan found the file first, rescans keep it
+ALTER TABLE `files` ADD COLUMN `added` integer NOT NULL DEFAULT 0;
+-- the files that were found before are added by the migration
+UPDATE `files` SET `added` = CAST(strftime('%s', 'now') AS integer);
@@ -0,0 +1,578 @@
+//! copies of the catalog: snapshots of a sqlite database and a json export of the libraries,
+//! the accounts and the files, which can be imported on a machine whose media is stored below
+//! other paths
+
+#![allow(clippy::needless_return)]
+
+use crate::{
+    config::LibraryConfig,
+    entities::Role,
+    repositories::{FileRepository, InsertFile, SearchMetadata, UserRepository},
+    services::calc_group_id,
+};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// version of the export format, not of the database schema
+pub const EXPORT_VERSION: u32 = 3;
+
+/// paths of version 1 were saved as they were, later versions encode them like the database
+const FIRST_ENCODED_VERSION: u32 = 2;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Export {
+    pub version: u32,
+    /// seconds since the unix epoch
+    pub exported_at: u64,
+    /// the libraries of the config, exports before version 3 have none
+    #[serde(default)]
+    pub libraries: Vec<LibraryConfig>,
+    /// exports before version 3 have no accounts
+    #[serde(default)]
+    pub users: Vec<ExportedUser>,
+    pub files: Vec<ExportedFile>,
+}
+
+/// an account without its sessions, they are signed with the key of the exporting machine
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExportedUser {
+    pub name: String,
+    /// argon2 in the PHC string format
+    pub password_hash: String,
+    /// "admin", "member" or "guest"
+    pub role: String,
+    /// seconds since the unix epoch
+    pub created_at: i64,
+}
+
+/// what `import` added to the database and the libraries for the config
+#[derive(Debug, PartialEq)]
+pub struct Imported {
+    pub files: usize,
+    pub users: usize,
+    /// the libraries of the export with remapped paths
+    pub libraries: Vec<LibraryConfig>,
+}
+
+/// the group is not exported because it is derived from the path
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExportedFile {
+    pub name: String,
+    /// encoded by `fs::encode_path`
+    pub path: String,
+    pub mime: String,
+    pub size: u64,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub tags: String,
+    #[serde(default)]
+    pub plot: String,
+    /// seconds since the unix epoch, exports before version 3 have none and the import adds them
+    #[serde(default)]
+    pub added: Option<u64>,
+}
+
+/// replaces the directory `from` at the start of a path with `to`, written as "from=to"
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathMapping {
+    pub from: String,
+    pub to: String,
+}
+
+impl FromStr for PathMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (from, to) = s
+            .split_once('=')
+            .ok_or_else(|| format!("path mapping '{}' is not written as 'from=to'", s))?;
+        if from.is_empty() {
+            return Err(format!("path mapping '{}' has no path to replace", s));
+        }
+        Ok(Self {
+            from: from.to_string(),
+            to: to.to_string(),
+        })
+    }
+}
+
+impl PathMapping {
+    /// "/media=/mnt" maps "/media/a.mp4" but not "/media2/a.mp4"
+    fn apply(&self, path: &str) -> Option<String> {
+        let rest = path.strip_prefix(&self.from)?;
+        let at_boundary = rest.is_empty() || rest.starts_with('/') || self.from.ends_with('/');
+        if !at_boundary {
+            return None;
+        }
+        return Some(format!("{}{}", self.to, rest));
+    }
+
+    /// the mapping of encoded paths
+    fn encoded(&self) -> Self {
+        Self {
+            from: fs::encode_path(Path::new(&self.from)),
+            to: fs::encode_path(Path::new(&self.to)),
+        }
+    }
+}
+
+/// the longest matching mapping wins, paths without a matching mapping stay unchanged
+pub fn remap(path: &str, mappings: &[PathMapping]) -> String {
+    mappings
+        .iter()
+        .filter_map(|m| m.apply(path).map(|p| (m.from.len(), p)))
+        .max_by_key(|(length, _)| *length)
+        .map(|(_, path)| path)
+        .unwrap_or_else(|| path.to_string())
+}
+
+pub async fn export(
+    db: &DatabaseConnection,
+    libraries: &[LibraryConfig],
+) -> Result<Export, String> {
+    let users = UserRepository::new(db.clone())
+        .find_all_accounts()
+        .await?
+        .into_iter()
+        .map(|u| ExportedUser {
+            name: u.name,
+            password_hash: u.password_hash,
+            role: u.role,
+            created_at: u.created_at,
+        })
+        .collect();
+    let files = FileRepository::new(db.clone())
+        .find_all_with_metadata()
+        .await?;
+    let files = files
+        .into_iter()
+        .map(|f| ExportedFile {
+            name: f.name,
+            path: f.path,
+            mime: f.mime,
+            size: f.size,
+            title: f.metadata.title,
+            tags: f.metadata.tags,
+            plot: f.metadata.plot,
+            added: Some(f.added),
+        })
+        .collect();
+
+    Ok(Export {
+        version: EXPORT_VERSION,
+        exported_at: now(),
+        libraries: libraries.to_vec(),
+        users,
+        files,
+    })
+}
+
+/// adds the files of the export with remapped paths and its accounts, already saved files with
+/// the same path and accounts with the same name are replaced. The libraries are returned with
+/// remapped paths because they are part of the config, not of the database
+pub async fn import(
+    db: &DatabaseConnection,
+    export: Export,
+    mappings: &[PathMapping],
+) -> Result<Imported, String> {
+    if export.version > EXPORT_VERSION {
+        return Err(format!(
+            "the export has version {} but this program only knows version {}",
+            export.version, EXPORT_VERSION
+        ));
+    }
+
+    let libraries = export
+        .libraries
+        .into_iter()
+        .map(|l| LibraryConfig {
+            path: remap(&l.path, mappings),
+            ..l
+        })
+        .collect();
+
+    let users = UserRepository::new(db.clone());
+    for user in &export.users {
+        let role: Role = user.role.parse()?;
+        users
+            .replace(&user.name, &user.password_hash, role, user.created_at)
+            .await?;
+    }
+
+    let encoded = export.version >= FIRST_ENCODED_VERSION;
+    let mappings: Vec<PathMapping> = mappings.iter().map(PathMapping::encoded).collect();
+    let now = now();
+    let inserts: Vec<InsertFile> = export
+        .files
+        .into_iter()
+        .map(|f| {
+            let path = match encoded {
+                true => f.path,
+                false => fs::encode_path(Path::new(&f.path)),
+            };
+            let path = remap(&path, &mappings);
+            let group_member_name = path.rsplit('/').next().unwrap_or_default().to_string();
+            let dir = &path[..path.len() - group_member_name.len()];
+            InsertFile {
+                name: f.name,
+                group_id: calc_group_id(dir),
+                group_member_name,
+                path,
+                mime: f.mime,
+                size: f.size,
+                added: f.added.unwrap_or(now),
+                metadata: SearchMetadata {
+                    title: f.title,
+                    tags: f.tags,
+                    plot: f.plot,
+                },
+            }
+        })
+        .collect();
+    let count = inserts.len();
+
+    FileRepository::new(db.clone()).replace_all(inserts).await?;
+    return Ok(Imported {
+        files: count,
+        users: export.users.len(),
+        libraries,
+    });
+}
+
+/// consistent copy of a sqlite database written with the online backup api of sqlite,
+/// the server can keep using the database meanwhile
+pub async fn backup(database_url: &str, target: &Path) -> Result<(), String> {
+    if database_url.starts_with("postgres") {
+        return Err("postgres databases are backed up with pg_dump".to_string());
+    }
+    let source = sqlite_path(database_url)?;
+    if target.exists() {
+        return Err(format!("'{}' already exists", target.display()));
+    }
+
+    let target = target.to_owned();
+    tokio::task::spawn_blocking(move || sqlite_backup(&source, &target))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// "sqlite://db.sqlite?mode=rwc" => "db.sqlite"
+fn sqlite_path(database_url: &str) -> Result<PathBuf, String> {
+    let path = database_url
+        .strip_prefix("sqlite://")
+        .or_else(|| database_url.strip_prefix("sqlite:"))
+        .ok_or_else(|| format!("'{}' is not a sqlite database", database_url))?;
+    let path = path.split('?').next().unwrap_or_default();
+    if path.is_empty() || path == ":memory:" {
+        return Err("in-memory databases can not be backed up".to_string());
+    }
+    return Ok(PathBuf::from(path));
+}
+
+#[cfg(feature = "sqlite")]
+fn sqlite_backup(source: &Path, target: &Path) -> Result<(), String> {
+    use rusqlite::{backup::Backup, Connection, OpenFlags};
+    use std::time::Duration;
+
+    // copying a few pages at a time lets the server write in between
+    const PAGES_PER_STEP: std::os::raw::c_int = 256;
+    const PAUSE: Duration = Duration::from_millis(10);
+
+    let source = Connection::open_with_flags(source, OpenFlags::SQLITE_OPEN_READ_WRITE)
+        .map_err(|e| format!("could not open '{}': {}", source.display(), e))?;
+    let mut target = Connection::open(target).map_err(|e| e.to_string())?;
+    let backup = Backup::new(&source, &mut target).map_err(|e| e.to_string())?;
+    backup
+        .run_to_completion(PAGES_PER_STEP, PAUSE, None)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn sqlite_backup(_: &Path, _: &Path) -> Result<(), String> {
+    Err("support for sqlite is not enabled, build with the cargo feature \"sqlite\"".to_string())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::SymlinkPolicy;
+    #[cfg(feature = "sqlite")]
+    use crate::database;
+    use crate::database::{test_database, TestDatabase};
+    #[cfg(feature = "sqlite")]
+    use sea_orm::{ConnectionTrait, DbBackend};
+
+    fn mapping(s: &str) -> PathMapping {
+        s.parse().unwrap()
+    }
+
+    fn exported(path: &str) -> ExportedFile {
+        ExportedFile {
+            name: "name".to_string(),
+            path: path.to_string(),
+            mime: "video/mp4".to_string(),
+            size: 1,
+            title: "Toy Story".to_string(),
+            tags: String::new(),
+            plot: "toys come to life".to_string(),
+            added: Some(1),
+        }
+    }
+
+    async fn migrated() -> TestDatabase {
+        let db = test_database().await;
+        crate::migrations::migrate(&db).await.unwrap();
+        return db;
+    }
+
+    #[test]
+    fn parse_mapping() {
+        assert_eq!(
+            mapping("/media=/mnt/media"),
+            PathMapping {
+                from: "/media".to_string(),
+                to: "/mnt/media".to_string()
+            }
+        );
+        assert!("/media".parse::<PathMapping>().is_err());
+        assert!("=/mnt".parse::<PathMapping>().is_err());
+    }
+
+    #[test]
+    fn remap_whole_directories() {
+        let mappings = [mapping("/media=/mnt")];
+        assert_eq!(remap("/media/a.mp4", &mappings), "/mnt/a.mp4");
+        assert_eq!(remap("/media2/a.mp4", &mappings), "/media2/a.mp4");
+    }
+
+    #[test]
+    fn longest_mapping_wins() {
+        let mappings = [mapping("/media=/mnt"), mapping("/media/music=/music")];
+        assert_eq!(remap("/media/music/a.mp3", &mappings), "/music/a.mp3");
+        assert_eq!(remap("/media/movies/a.mp4", &mappings), "/mnt/movies/a.mp4");
+    }
+
+    #[tokio::test]
+    async fn export_and_import() {
+        let source = migrated().await;
+        let export = Export {
+            version: EXPORT_VERSION,
+            exported_at: 0,
+            libraries: Vec::new(),
+            users: Vec::new(),
+            files: vec![exported("/media/movies/a.mp4")],
+        };
+        import(&source, export, &[]).await.unwrap();
+        let export = super::export(&source, &[]).await.unwrap();
+        assert_eq!(export.files, [exported("/media/movies/a.mp4")]);
+
+        let json = serde_json::to_string(&export).unwrap();
+        let target = migrated().await;
+        let export = serde_json::from_str(&json).unwrap();
+        let imported = import(&target, export, &[mapping("/media=/mnt")])
+            .await
+            .unwrap();
+        assert_eq!(imported.files, 1);
+
+        let files = FileRepository::new(target.clone());
+        let file = files
+            .find_by_group(&calc_group_id("/mnt/movies/"), "a.mp4")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(file.path, "/mnt/movies/a.mp4");
+        let exported = super::export(&target, &[]).await.unwrap();
+        assert_eq!(exported.files[0].plot, "toys come to life");
+    }
+
+    #[tokio::test]
+    async fn import_replaces_same_paths() {
+        let db = migrated().await;
+        for _ in 0..2 {
+            let export = Export {
+                version: EXPORT_VERSION,
+                exported_at: 0,
+                libraries: Vec::new(),
+                users: Vec::new(),
+                files: vec![exported("/a.mp4"), exported("/b.mp4")],
+            };
+            import(&db, export, &[]).await.unwrap();
+        }
+        assert_eq!(super::export(&db, &[]).await.unwrap().files.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn paths_of_version_1_are_encoded() {
+        let db = migrated().await;
+        for (version, path) in [(1, "/100%25/a.mp4"), (EXPORT_VERSION, "/100%25/b.mp4")] {
+            let export = Export {
+                version,
+                exported_at: 0,
+                libraries: Vec::new(),
+                users: Vec::new(),
+                files: vec![exported(path)],
+            };
+            import(&db, export, &[]).await.unwrap();
+        }
+
+        let files = super::export(&db, &[]).await.unwrap().files;
+        let paths: Vec<PathBuf> = files.iter().map(|f| fs::decode_path(&f.path)).collect();
+        assert_eq!(
+            paths,
+            [Path::new("/100%25/a.mp4"), Path::new("/100%/b.mp4")]
+        );
+    }
+
+    #[test]
+    fn mappings_are_encoded_like_paths() {
+        let mappings = [mapping("/100%=/mnt").encoded()];
+        assert_eq!(remap("/100%25/a.mp4", &mappings), "/mnt/a.mp4");
+        assert_eq!(remap("/100%2525/a.mp4", &mappings), "/100%2525/a.mp4");
+    }
+
+    #[tokio::test]
+    async fn newer_export_is_refused() {
+        let export = Export {
+            version: EXPORT_VERSION + 1,
+            exported_at: 0,
+            libraries: Vec::new(),
+            users: Vec::new(),
+            files: Vec::new(),
+        };
+        assert!(import(&*migrated().await, export, &[]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn export_and_import_users_and_libraries() {
+        let source = migrated().await;
+        let users = UserRepository::new(source.clone());
+        users
+            .replace("admin", "$argon2id$admin", Role::Admin, 10)
+            .await
+            .unwrap();
+        users
+            .replace("guest", "$argon2id$guest", Role::Guest, 20)
+            .await
+            .unwrap();
+        let library = LibraryConfig {
+            path: "/media/movies".to_string(),
+            symlinks: SymlinkPolicy::Follow,
+            max_depth: Some(3),
+            guests: true,
+            ..Default::default()
+        };
+        let export = super::export(&source, std::slice::from_ref(&library))
+            .await
+            .unwrap();
+
+        let json = serde_json::to_string(&export).unwrap();
+        let target = migrated().await;
+        UserRepository::new(target.clone())
+            .replace("admin", "$argon2id$old", Role::Member, 0)
+            .await
+            .unwrap();
+        let export = serde_json::from_str(&json).unwrap();
+        let imported = import(&target, export, &[mapping("/media=/mnt")])
+            .await
+            .unwrap();
+
+        assert_eq!(imported.users, 2);
+        assert_eq!(
+            imported.libraries,
+            [LibraryConfig {
+                path: "/mnt/movies".to_string(),
+                ..library
+            }]
+        );
+        let exported = super::export(&target, &[]).await.unwrap();
+        assert_eq!(
+            exported.users,
+            [
+                ExportedUser {
+                    name: "admin".to_string(),
+                    password_hash: "$argon2id$admin".to_string(),
+                    role: "admin".to_string(),
+                    created_at: 10,
+                },
+                ExportedUser {
+                    name: "guest".to_string(),
+                    password_hash: "$argon2id$guest".to_string(),
+                    role: "guest".to_string(),
+                    created_at: 20,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_role_is_refused() {
+        let export = Export {
+            version: EXPORT_VERSION,
+            exported_at: 0,
+            libraries: Vec::new(),
+            users: vec![ExportedUser {
+                name: "root".to_string(),
+                password_hash: "$argon2id$root".to_string(),
+                role: "root".to_string(),
+                created_at: 0,
+            }],
+            files: Vec::new(),
+        };
+        assert!(import(&*migrated().await, export, &[]).await.is_err());
+    }
+
+    #[test]
+    fn sqlite_paths() {
+        assert_eq!(
+            sqlite_path("sqlite://db.sqlite?mode=rwc").unwrap(),
+            PathBuf::from("db.sqlite")
+        );
+        assert_eq!(
+            sqlite_path("sqlite:/data/db.sqlite").unwrap(),
+            PathBuf::from("/data/db.sqlite")
+        );
+        assert!(sqlite_path("sqlite::memory:").is_err());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn backup_while_connected() {
+        let dir = testing::TempDir::new("backup");
+        let source = dir.join("db.sqlite");
+        let target = dir.join("backup.sqlite");
+
+        let url = format!("sqlite://{}?mode=rwc", source.display());
+        let db = database::connect(&url).await.unwrap();
+        crate::migrations::migrate(&db).await.unwrap();
+        let export = Export {
+            version: EXPORT_VERSION,
+            exported_at: 0,
+            libraries: Vec::new(),
+            users: Vec::new(),
+            files: vec![exported("/a.mp4")],
+        };
+        import(&db, export, &[]).await.unwrap();
+
+        backup(&url, &target).await.unwrap();
+        assert!(backup(&url, &target).await.is_err());
+
+        let copy = database::connect(&format!("sqlite://{}", target.display()))
+            .await
+            .unwrap();
+        assert_eq!(copy.get_database_backend(), DbBackend::Sqlite);
+        assert_eq!(super::export(&copy, &[]).await.unwrap().files.len(), 1);
+    }
+}
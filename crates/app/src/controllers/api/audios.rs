@@ -0,0 +1,64 @@
+use super::{
+    super::auth::Visible,
+    error::{ApiError, ErrorBody},
+    extract::{ApiPath, ApiQuery},
+    model::{FileItem, ListQuery},
+};
+use crate::repositories::{Audios, Page};
+use axum::{extract::Extension, routing::get, Json, Router};
+use utoipa::OpenApi;
+
+pub fn setup() -> Router {
+    Router::new().route("/", get(list)).route("/:id", get(find))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(list, find), tags((name = "audios")))]
+pub struct Doc;
+
+/// files with a audio mime
+#[utoipa::path(
+    get,
+    path = "",
+    tag = "audios",
+    operation_id = "list_audios",
+    params(ListQuery),
+    responses(
+        (status = 200, body = Page<FileItem>, description = "a page of files"),
+        (status = 400, body = ErrorBody, description = "invalid parameters"),
+    )
+)]
+async fn list(
+    Extension(audios): Extension<Audios>,
+    ApiQuery(query): ApiQuery<ListQuery>,
+    visible: Visible,
+) -> Result<Json<Page<FileItem>>, ApiError> {
+    let page = audios
+        .find_page(&query.filter(visible), query.page()?)
+        .await?;
+    Ok(Json(page.map(FileItem::from)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}",
+    tag = "audios",
+    operation_id = "find_audio",
+    params(("id" = u64, Path)),
+    responses(
+        (status = 200, body = FileItem, description = "the file"),
+        (status = 404, body = ErrorBody, description = "no such file"),
+    )
+)]
+async fn find(
+    Extension(audios): Extension<Audios>,
+    ApiPath(id): ApiPath<u64>,
+    visible: Visible,
+) -> Result<Json<FileItem>, ApiError> {
+    let audio = audios
+        .find_by_id(id)
+        .await?
+        .filter(|f| visible.contains(&f.path))
+        .ok_or_else(|| ApiError::not_found("audio not found"))?;
+    Ok(Json(FileItem::from(audio)))
+}
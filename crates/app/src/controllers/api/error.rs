@@ -0,0 +1,83 @@
+#![allow(clippy::needless_return)]
+
+use axum::{
+    body::{Bytes, Full},
+    http::{Response, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::Serialize;
+use std::convert::Infallible;
+use utoipa::ToSchema;
+
+/// every error of the api is sent as `{"error": {"status": 404, "message": "..."}}`
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message)
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, message)
+    }
+
+    pub fn unavailable(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::SERVICE_UNAVAILABLE, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, message)
+    }
+}
+
+/// errors of the repositories and services are strings and always internal
+impl From<String> for ApiError {
+    fn from(message: String) -> Self {
+        Self::internal(message)
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = Error)]
+pub(super) struct ErrorBody<'a> {
+    error: ErrorDetails<'a>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ErrorDetails<'a> {
+    status: u16,
+    message: &'a str,
+}
+
+impl IntoResponse for ApiError {
+    type Body = Full<Bytes>;
+    type BodyError = Infallible;
+
+    fn into_response(self) -> Response<Self::Body> {
+        let body = ErrorBody {
+            error: ErrorDetails {
+                status: self.status.as_u16(),
+                message: &self.message,
+            },
+        };
+        let mut response = Json(body).into_response();
+        *response.status_mut() = self.status;
+        return response;
+    }
+}
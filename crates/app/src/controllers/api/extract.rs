@@ -0,0 +1,44 @@
+use super::error::ApiError;
+use axum::{
+    async_trait,
+    extract::{FromRequest, Path, Query, RequestParts},
+};
+use serde::de::DeserializeOwned;
+
+/// like `Query` but rejects invalid parameters with an `ApiError`
+pub struct ApiQuery<T>(pub T);
+
+#[async_trait]
+impl<T, B> FromRequest<B> for ApiQuery<T>
+where
+    T: DeserializeOwned,
+    B: Send, // required by `async_trait`
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::from_request(req)
+            .await
+            .map_err(|e| ApiError::bad_request(e.to_string()))?;
+        Ok(Self(value))
+    }
+}
+
+/// like `Path` but rejects invalid parameters with an `ApiError`
+pub struct ApiPath<T>(pub T);
+
+#[async_trait]
+impl<T, B> FromRequest<B> for ApiPath<T>
+where
+    T: DeserializeOwned + Send,
+    B: Send, // required by `async_trait`
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let Path(value) = Path::<T>::from_request(req)
+            .await
+            .map_err(|e| ApiError::bad_request(e.to_string()))?;
+        Ok(Self(value))
+    }
+}
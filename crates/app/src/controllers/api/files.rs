@@ -0,0 +1,92 @@
+use super::{
+    super::auth::Visible,
+    error::{ApiError, ErrorBody},
+    extract::{ApiPath, ApiQuery},
+    model::{FileItem, ListQuery, StreamUrls},
+};
+use crate::repositories::{FileRepository, Page};
+use axum::{extract::Extension, routing::get, Json, Router};
+use utoipa::OpenApi;
+
+pub fn setup() -> Router {
+    Router::new()
+        .route("/", get(list))
+        .route("/:id", get(find))
+        .route("/:id/stream", get(stream_urls))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(list, find, stream_urls), tags((name = "files")))]
+pub struct Doc;
+
+/// all files
+#[utoipa::path(
+    get,
+    path = "",
+    tag = "files",
+    operation_id = "list_files",
+    params(ListQuery),
+    responses(
+        (status = 200, body = Page<FileItem>, description = "a page of files"),
+        (status = 400, body = ErrorBody, description = "invalid parameters"),
+    )
+)]
+async fn list(
+    Extension(files): Extension<FileRepository>,
+    ApiQuery(query): ApiQuery<ListQuery>,
+    visible: Visible,
+) -> Result<Json<Page<FileItem>>, ApiError> {
+    let page = files
+        .find_page(&query.filter(visible), query.page()?)
+        .await?;
+    Ok(Json(page.map(FileItem::from)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}",
+    tag = "files",
+    operation_id = "find_file",
+    params(("id" = u64, Path)),
+    responses(
+        (status = 200, body = FileItem, description = "the file"),
+        (status = 404, body = ErrorBody, description = "no such file"),
+    )
+)]
+async fn find(
+    Extension(files): Extension<FileRepository>,
+    ApiPath(id): ApiPath<u64>,
+    visible: Visible,
+) -> Result<Json<FileItem>, ApiError> {
+    let file = files
+        .find_by_id(id)
+        .await?
+        .filter(|f| visible.contains(&f.path))
+        .ok_or_else(|| ApiError::not_found("file not found"))?;
+    Ok(Json(FileItem::from(file)))
+}
+
+/// where the content of the file can be fetched
+#[utoipa::path(
+    get,
+    path = "/{id}/stream",
+    tag = "files",
+    operation_id = "file_stream_urls",
+    params(("id" = u64, Path)),
+    responses(
+        (status = 200, body = StreamUrls, description = "urls of the content"),
+        (status = 404, body = ErrorBody, description = "no such file"),
+    )
+)]
+async fn stream_urls(
+    Extension(files): Extension<FileRepository>,
+    ApiPath(id): ApiPath<u64>,
+    visible: Visible,
+) -> Result<Json<StreamUrls>, ApiError> {
+    let file = files
+        .find_by_id(id)
+        .await?
+        .filter(|f| visible.contains(&f.path))
+        .ok_or_else(|| ApiError::not_found("file not found"))?;
+    Ok(Json(StreamUrls::from(&file)))
+}
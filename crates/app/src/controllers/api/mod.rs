@@ -0,0 +1,27 @@
+use super::auth::{require, Admin};
+use axum::{routing::get, Router};
+
+mod audios;
+mod error;
+mod extract;
+mod files;
+mod model;
+mod openapi;
+mod scan;
+mod videos;
+
+pub(super) use error::ApiError;
+
+pub fn setup() -> Router {
+    Router::new()
+        .nest("/v1", v1())
+        .route("/openapi.json", get(openapi::openapi))
+}
+
+fn v1() -> Router {
+    Router::new()
+        .nest("/files", files::setup())
+        .nest("/videos", videos::setup())
+        .nest("/audios", audios::setup())
+        .nest("/scan", scan::setup().layer(require::<Admin>()))
+}
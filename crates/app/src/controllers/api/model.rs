@@ -0,0 +1,87 @@
+use super::super::{auth::Visible, encode_path_segment};
+use super::error::ApiError;
+use crate::{
+    entities::File,
+    repositories::{FileFilter, PageRequest, Sort, SortOrder},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// query parameters of all list endpoints
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[serde(default)]
+#[into_params(parameter_in = Query)]
+pub struct ListQuery {
+    /// part of the name
+    pub q: Option<String>,
+    /// exact mime, e.g. "video/mp4"
+    pub mime: Option<String>,
+    pub group_id: Option<String>,
+    /// starts at 1
+    pub page: Option<usize>,
+    /// between 1 and 500, defaults to 50
+    pub per_page: Option<usize>,
+    /// defaults to "name"
+    #[param(inline)]
+    pub sort: Option<Sort>,
+    /// defaults to "asc"
+    #[param(inline)]
+    pub order: Option<SortOrder>,
+}
+
+impl ListQuery {
+    /// the files of the query that are visible to the user
+    pub fn filter(&self, Visible(roots): Visible) -> FileFilter {
+        FileFilter {
+            query: self.q.to_owned(),
+            mime: self.mime.to_owned(),
+            group_id: self.group_id.to_owned(),
+            roots,
+        }
+    }
+
+    pub fn page(&self) -> Result<PageRequest, ApiError> {
+        let request = PageRequest::new(self.page, self.per_page).map_err(ApiError::bad_request)?;
+        Ok(request.sorted(self.sort, self.order))
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FileItem {
+    #[serde(flatten)]
+    pub file: File,
+    pub browser_playable: bool,
+    pub urls: StreamUrls,
+}
+
+impl From<File> for FileItem {
+    fn from(file: File) -> Self {
+        Self {
+            browser_playable: file.is_browser_playable(),
+            urls: StreamUrls::from(&file),
+            file,
+        }
+    }
+}
+
+/// where the content of a file can be fetched
+#[derive(Serialize, ToSchema)]
+pub struct StreamUrls {
+    pub stream: String,
+    pub transcode: String,
+    pub download: String,
+    pub download_group: String,
+}
+
+impl From<&File> for StreamUrls {
+    fn from(file: &File) -> Self {
+        let group_id = encode_path_segment(&file.group_id);
+        let member = encode_path_segment(&file.group_member_name);
+        Self {
+            stream: format!("/stream/{}/{}", group_id, member),
+            transcode: format!("/stream/transcode/{}/{}", group_id, member),
+            download: format!("/download/{}", file.id),
+            download_group: format!("/download/group/{}.zip", group_id),
+        }
+    }
+}
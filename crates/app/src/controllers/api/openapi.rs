@@ -0,0 +1,28 @@
+use super::{audios, files, scan, videos};
+use axum::Json;
+use utoipa::OpenApi;
+
+/// the description of every route under "/api", it is generated from the `#[utoipa::path]`
+/// attributes of the handlers, so a route has to be added here and to its router
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "netflex", description = "browse, stream and scan the media library"),
+    paths(openapi),
+    nest(
+        (path = "/api/v1/files", api = files::Doc),
+        (path = "/api/v1/videos", api = videos::Doc),
+        (path = "/api/v1/audios", api = audios::Doc),
+        (path = "/api/v1/scan", api = scan::Doc),
+    )
+)]
+struct ApiDoc;
+
+/// this document
+#[utoipa::path(
+    get,
+    path = "/api/openapi.json",
+    responses((status = 200, description = "OpenAPI 3.1 document", content_type = "application/json"))
+)]
+pub(super) async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
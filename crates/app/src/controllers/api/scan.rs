@@ -0,0 +1,51 @@
+use super::error::{ApiError, ErrorBody};
+use crate::services::{ScanRefused, UpdateService, UpdateStatus};
+use axum::{extract::Extension, http::StatusCode, routing::get, Json, Router};
+use utoipa::OpenApi;
+
+pub fn setup() -> Router {
+    Router::new().route("/", get(status).post(start))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(status, start), tags((name = "scan")))]
+pub struct Doc;
+
+/// progress of the current or the last scan
+#[utoipa::path(
+    get,
+    path = "",
+    tag = "scan",
+    operation_id = "scan_status",
+    responses(
+        (status = 200, body = UpdateStatus, description = "status of the scan"),
+        (status = 403, body = ErrorBody, description = "only for admins"),
+    )
+)]
+async fn status(Extension(updater): Extension<UpdateService>) -> Json<UpdateStatus> {
+    Json(updater.status())
+}
+
+/// the scan runs in the background, its progress is returned by `status`
+#[utoipa::path(
+    post,
+    path = "",
+    tag = "scan",
+    operation_id = "start_scan",
+    responses(
+        (status = 202, body = UpdateStatus, description = "the scan was started"),
+        (status = 403, body = ErrorBody, description = "only for admins"),
+        (status = 409, body = ErrorBody, description = "a scan is already running"),
+        (status = 503, body = ErrorBody, description = "the server is shutting down"),
+    )
+)]
+async fn start(
+    Extension(updater): Extension<UpdateService>,
+) -> Result<(StatusCode, Json<UpdateStatus>), ApiError> {
+    let status = updater.clone();
+    updater.run().await.map_err(|e| match e {
+        ScanRefused::Running => ApiError::conflict(e.to_string()),
+        ScanRefused::ShuttingDown => ApiError::unavailable(e.to_string()),
+    })?;
+    Ok((StatusCode::ACCEPTED, Json(status.status())))
+}
@@ -1,8 +1,14 @@
-use super::render;
-use crate::repositories::Audios;
+use super::{
+    auth::Visible,
+    encode_path_segment,
+    error::AppError,
+    list::{ListQuery, ListView},
+    render,
+};
+use crate::{repositories::Audios, services::TranscodeService};
 use askama::Template;
 use axum::{
-    extract::{Extension, Path},
+    extract::{Extension, Path, Query},
     response::Html,
     routing::get,
     Router,
@@ -17,12 +23,19 @@ pub fn setup() -> Router {
 #[derive(Template)]
 #[template(path = "views/audios/list.html")]
 struct AudiosTemplate {
-    audios: Vec<crate::entities::File>,
+    list: ListView,
 }
 
-async fn list_all(Extension(audios): Extension<Audios>) -> Result<Html<String>, String> {
-    let audios = audios.find_all().await?;
-    let template = render(AudiosTemplate { audios })?;
+async fn list_all(
+    Extension(audios): Extension<Audios>,
+    Query(query): Query<ListQuery>,
+    visible: Visible,
+) -> Result<Html<String>, AppError> {
+    let request = query.page()?;
+    let page = audios.find_page(&query.filter(visible), request).await?;
+    let template = render(AudiosTemplate {
+        list: ListView::new(page, request, &query),
+    })?;
     Ok(Html::from(template))
 }
 
@@ -30,16 +43,26 @@ async fn list_all(Extension(audios): Extension<Audios>) -> Result<Html<String>,
 #[template(path = "views/audios/player.html")]
 struct PlayerTemplate {
     audio: crate::entities::File,
+    /// the name in the urls of the streams
+    member: String,
+    transcode_mime: String,
 }
 
 pub async fn player(
     Extension(audios): Extension<Audios>,
+    Extension(transcoder): Extension<TranscodeService>,
     Path(id): Path<u64>,
-) -> Result<Html<String>, String> {
+    visible: Visible,
+) -> Result<Html<String>, AppError> {
     let audio = audios
         .find_by_id(id)
         .await?
-        .ok_or_else(|| "id not found".to_string())?;
-    let template = render(PlayerTemplate { audio })?;
+        .filter(|f| visible.contains(&f.path))
+        .ok_or_else(|| AppError::NotFound(format!("{} {} does not exist", "audio", id)))?;
+    let template = render(PlayerTemplate {
+        member: encode_path_segment(&audio.group_member_name),
+        audio,
+        transcode_mime: transcoder.mime(),
+    })?;
     Ok(Html::from(template))
 }
@@ -0,0 +1,427 @@
+#![allow(clippy::needless_return)]
+
+use super::{api::ApiError, error::AppError, render};
+use crate::{
+    entities::{Role, User},
+    services::{AuthService, Libraries, SESSION_COOKIE, SESSION_LIFETIME},
+};
+use askama::Template;
+use axum::{
+    async_trait,
+    body::{boxed, BoxBody},
+    extract::{
+        extractor_middleware, extractor_middleware::ExtractorMiddlewareLayer, Extension, Form,
+        FromRequest, Query, RequestParts,
+    },
+    http::{header, HeaderMap, HeaderValue, Method, Request, Response, StatusCode, Uri},
+    response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
+    Router,
+};
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use std::{
+    convert::Infallible,
+    marker::PhantomData,
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
+
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+pub fn setup() -> Router {
+    Router::new()
+        .route("/login", get(login_page).post(login))
+        .route("/logout", post(logout))
+        .route("/setup", get(setup_page).post(create_admin))
+}
+
+/// reachable without a session
+fn is_public(path: &str) -> bool {
+    path == "/login"
+        || path == "/logout"
+        || path == "/setup"
+        || path == "/static"
+        || path.starts_with("/static/")
+}
+
+#[derive(Template)]
+#[template(path = "views/login.html")]
+struct LoginTemplate {
+    name: String,
+    next: String,
+    error: String,
+}
+
+#[derive(Deserialize)]
+struct LoginQuery {
+    next: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LoginForm {
+    name: String,
+    password: String,
+    next: Option<String>,
+}
+
+async fn login_page(
+    Extension(auth): Extension<AuthService>,
+    Query(query): Query<LoginQuery>,
+) -> Result<Response<BoxBody>, AppError> {
+    if auth.needs_setup() {
+        return Ok(redirect("/setup"));
+    }
+    let template = render(LoginTemplate {
+        name: String::new(),
+        next: safe_next(query.next),
+        error: String::new(),
+    })?;
+    Ok(Html(template).into_response().map(boxed))
+}
+
+async fn login(
+    Extension(auth): Extension<AuthService>,
+    Form(form): Form<LoginForm>,
+) -> Result<Response<BoxBody>, AppError> {
+    let next = safe_next(form.next);
+    let user = match auth.login(&form.name, &form.password).await? {
+        Some(user) => user,
+        None => {
+            let template = render(LoginTemplate {
+                name: form.name,
+                next,
+                error: "wrong name or password".to_string(),
+            })?;
+            return Ok((StatusCode::UNAUTHORIZED, Html(template))
+                .into_response()
+                .map(boxed));
+        }
+    };
+    let cookie = session_cookie(&auth.session(&user), SESSION_LIFETIME);
+    return Ok(with_cookie(redirect(&next), cookie));
+}
+
+/// the cookie is removed and every other session of the user ends too
+async fn logout(
+    Extension(auth): Extension<AuthService>,
+    headers: HeaderMap,
+) -> Result<Response<BoxBody>, AppError> {
+    if let Some(session) = cookie(&headers, SESSION_COOKIE) {
+        auth.logout(session).await?;
+    }
+    Ok(with_cookie(redirect("/login"), session_cookie("", 0)))
+}
+
+#[derive(Template)]
+#[template(path = "views/setup.html")]
+struct SetupTemplate {
+    name: String,
+    error: String,
+}
+
+#[derive(Deserialize)]
+struct SetupForm {
+    name: String,
+    password: String,
+    repeat: String,
+}
+
+impl SetupForm {
+    fn validate(&self) -> Result<(), String> {
+        validate_account(&self.name, &self.password)?;
+        if self.password != self.repeat {
+            return Err("the passwords do not match".to_string());
+        }
+        Ok(())
+    }
+}
+
+pub(super) fn validate_account(name: &str, password: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("the name must not be empty".to_string());
+    }
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(format!(
+            "the password needs at least {} characters",
+            MIN_PASSWORD_LENGTH
+        ));
+    }
+    Ok(())
+}
+
+/// only shown until the first account exists
+async fn setup_page(
+    Extension(auth): Extension<AuthService>,
+) -> Result<Response<BoxBody>, AppError> {
+    if !auth.needs_setup() {
+        return Ok(redirect("/login"));
+    }
+    let template = render(SetupTemplate {
+        name: String::new(),
+        error: String::new(),
+    })?;
+    Ok(Html(template).into_response().map(boxed))
+}
+
+async fn create_admin(
+    Extension(auth): Extension<AuthService>,
+    Form(form): Form<SetupForm>,
+) -> Result<Response<BoxBody>, AppError> {
+    if let Err(error) = form.validate() {
+        let template = render(SetupTemplate {
+            name: form.name,
+            error,
+        })?;
+        return Ok((StatusCode::BAD_REQUEST, Html(template))
+            .into_response()
+            .map(boxed));
+    }
+    let user = match auth
+        .create_first_user(form.name.trim(), &form.password)
+        .await?
+    {
+        Some(user) => user,
+        None => return Ok(redirect("/login")),
+    };
+    let cookie = session_cookie(&auth.session(&user), SESSION_LIFETIME);
+    return Ok(with_cookie(redirect("/"), cookie));
+}
+
+/// only paths of this server, "//host" would leave it
+fn safe_next(next: Option<String>) -> String {
+    match next {
+        Some(next) if next.starts_with('/') && !next.starts_with("//") && !next.contains('\\') => {
+            next
+        }
+        _ => "/".to_string(),
+    }
+}
+
+fn redirect(location: &str) -> Response<BoxBody> {
+    let uri = location.parse().unwrap_or_else(|_| Uri::from_static("/"));
+    Redirect::to(uri).into_response().map(boxed)
+}
+
+fn session_cookie(value: &str, max_age: u64) -> String {
+    format!(
+        "{}={}; HttpOnly; SameSite=Lax; Path=/; Max-Age={}",
+        SESSION_COOKIE, value, max_age
+    )
+}
+
+fn with_cookie(mut response: Response<BoxBody>, cookie: String) -> Response<BoxBody> {
+    if let Ok(cookie) = HeaderValue::from_str(&cookie) {
+        response.headers_mut().insert(header::SET_COOKIE, cookie);
+    }
+    return response;
+}
+
+pub(super) fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(';'))
+        .filter_map(|c| c.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// requires a session for everything except the public paths, adds the `User` to the request
+#[derive(Clone, Copy)]
+pub struct AuthLayer;
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = Auth<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Auth { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct Auth<S> {
+    inner: S,
+}
+
+impl<S, B> Service<Request<B>> for Auth<S>
+where
+    S: Service<Request<B>, Response = Response<BoxBody>, Error = Infallible>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        // the ready service handles this request, the clone the next one
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(async move {
+            if is_public(request.uri().path()) {
+                return inner.call(request).await;
+            }
+            let auth = match request.extensions().get::<AuthService>() {
+                Some(auth) => auth.clone(),
+                None => {
+                    let error = AppError::Internal("the auth service is missing".to_string());
+                    return Ok(error.into_response());
+                }
+            };
+            let session = cookie(request.headers(), SESSION_COOKIE).unwrap_or_default();
+            match auth.user_of_session(session).await {
+                Ok(Some(user)) => {
+                    request.extensions_mut().insert(user);
+                    inner.call(request).await
+                }
+                Ok(None) => Ok(unauthenticated(&auth, &request)),
+                Err(e) => Ok(AppError::Database(e).into_response()),
+            }
+        })
+    }
+}
+
+/// the least role a router requires, see `require`
+pub trait MinimumRole {
+    const ROLE: Role;
+}
+
+pub struct Admin;
+
+impl MinimumRole for Admin {
+    const ROLE: Role = Role::Admin;
+}
+
+/// rejects users whose role is lower than `R` with a 403, every logged in user is at least a guest
+pub struct RequireRole<R>(PhantomData<R>);
+
+#[async_trait]
+impl<R, B> FromRequest<B> for RequireRole<R>
+where
+    R: MinimumRole,
+    B: Send, // required by `async_trait`
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let user = req
+            .extensions()
+            .and_then(|e| e.get::<User>())
+            .ok_or_else(|| AppError::Unauthorized("login required".to_string()))?;
+        if user.role < R::ROLE {
+            return Err(AppError::Forbidden(format!(
+                "only for the role \"{}\" and above",
+                R::ROLE.as_str()
+            )));
+        }
+        Ok(Self(PhantomData))
+    }
+}
+
+/// the library roots whose files the user may see, `None` if every file, see
+/// `Libraries::visible_roots`
+pub struct Visible(pub Option<Vec<String>>);
+
+impl Visible {
+    /// `path` is encoded like in the database
+    pub fn contains(&self, path: &str) -> bool {
+        match &self.0 {
+            Some(roots) => roots.iter().any(|r| path.starts_with(r.as_str())),
+            None => true,
+        }
+    }
+}
+
+#[async_trait]
+impl<B> FromRequest<B> for Visible
+where
+    B: Send, // required by `async_trait`
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let Extension(libraries) = Extension::<Libraries>::from_request(req)
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        let user = req
+            .extensions()
+            .and_then(|e| e.get::<User>())
+            .ok_or_else(|| AppError::Unauthorized("login required".to_string()))?;
+        Ok(Self(libraries.visible_roots(user.role)))
+    }
+}
+
+/// guards every route of a router, e.g. `settings::setup().layer(require::<Admin>())`
+pub fn require<R: MinimumRole>() -> ExtractorMiddlewareLayer<RequireRole<R>> {
+    extractor_middleware()
+}
+
+/// pages are redirected to the login, everything else gets a 401
+fn unauthenticated<B>(auth: &AuthService, request: &Request<B>) -> Response<BoxBody> {
+    let message = match auth.needs_setup() {
+        true => "create the first account at /setup",
+        false => "login required",
+    };
+    if request.uri().path().starts_with("/api/") {
+        let error = ApiError::new(StatusCode::UNAUTHORIZED, message);
+        return error.into_response().map(boxed);
+    }
+    if request.method() != Method::GET && request.method() != Method::HEAD {
+        return AppError::Unauthorized(message.to_string()).into_response();
+    }
+    if auth.needs_setup() {
+        return redirect("/setup");
+    }
+    let next = request
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or("/");
+    let query = serde_urlencoded::to_string([("next", next)]).unwrap_or_default();
+    return redirect(&format!("/login?{}", query));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn next_stays_on_this_server() {
+        assert_eq!(
+            safe_next(Some("/videos?page=2".to_string())),
+            "/videos?page=2"
+        );
+        assert_eq!(safe_next(Some("//evil.com".to_string())), "/");
+        assert_eq!(safe_next(Some("/\\evil.com".to_string())), "/");
+        assert_eq!(safe_next(Some("https://evil.com".to_string())), "/");
+        assert_eq!(safe_next(None), "/");
+    }
+
+    #[test]
+    fn cookie_is_found() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_static("a=1; netflex_session=abc.def; b=2"),
+        );
+        assert_eq!(cookie(&headers, SESSION_COOKIE), Some("abc.def"));
+        assert_eq!(cookie(&headers, "c"), None);
+    }
+
+    #[test]
+    fn static_files_are_public() {
+        assert!(is_public("/static/custom/styles.css"));
+        assert!(is_public("/login"));
+        assert!(!is_public("/staticfiles"));
+        assert!(!is_public("/videos"));
+    }
+}
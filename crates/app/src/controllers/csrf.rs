@@ -0,0 +1,255 @@
+#![allow(clippy::needless_return)]
+
+use super::{auth::cookie, error::AppError};
+use crate::services::{AuthService, SESSION_COOKIE};
+use axum::{
+    body::{Body, BoxBody, HttpBody},
+    http::{header, HeaderMap, Method, Request, Response},
+    response::IntoResponse,
+};
+use futures::future::BoxFuture;
+use std::{
+    convert::Infallible,
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
+
+/// name of the hidden input of the forms
+pub const CSRF_FIELD: &str = "csrf_token";
+/// alternative to the form field for clients that do not send forms
+pub const CSRF_HEADER: &str = "x-csrf-token";
+/// bytes of a form that are read to find its token, longer forms are rejected
+const MAX_FORM_LENGTH: usize = 64 * 1024;
+
+/// the token of the current session, added to the requests of logged in users
+#[derive(Clone)]
+pub struct CsrfToken(pub String);
+
+/// rejects requests that change something if they could come from another site
+///
+/// with a session a request needs the token of the session in `CSRF_FIELD` or `CSRF_HEADER`,
+/// or an "Origin" header of this server. a foreign "Origin" is always rejected
+#[derive(Clone, Copy)]
+pub struct CsrfLayer;
+
+impl<S> Layer<S> for CsrfLayer {
+    type Service = Csrf<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Csrf { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct Csrf<S> {
+    inner: S,
+}
+
+impl<S> Service<Request<Body>> for Csrf<S>
+where
+    S: Service<Request<Body>, Response = Response<BoxBody>, Error = Infallible>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<Body>) -> Self::Future {
+        // the ready service handles this request, the clone the next one
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(async move {
+            let auth = request.extensions().get::<AuthService>().cloned();
+            let session = cookie(request.headers(), SESSION_COOKIE).map(str::to_owned);
+            if let (Some(auth), Some(session)) = (&auth, &session) {
+                let token = CsrfToken(auth.csrf_token(session));
+                request.extensions_mut().insert(token);
+            }
+
+            if is_safe(request.method()) {
+                return inner.call(request).await;
+            }
+            if !same_origin(request.headers()) {
+                return Ok(forbidden("requests from other sites are not allowed"));
+            }
+            let (auth, session) = match (auth, session) {
+                (Some(auth), Some(session)) => (auth, session),
+                // without a session there is nothing another site could use
+                _ => return inner.call(request).await,
+            };
+            match auth.user_of_session(&session).await {
+                Ok(Some(_)) => {}
+                Ok(None) => return inner.call(request).await,
+                Err(e) => return Ok(AppError::Database(e).into_response()),
+            }
+
+            let (request, token) = match token(request).await {
+                Ok(request_and_token) => request_and_token,
+                Err(e) => return Ok(e.into_response()),
+            };
+            let valid = match token {
+                Some(token) => auth.verify_csrf_token(&session, &token),
+                None => request.headers().contains_key(header::ORIGIN),
+            };
+            if !valid {
+                return Ok(forbidden("the csrf token is missing or invalid"));
+            }
+            inner.call(request).await
+        })
+    }
+}
+
+fn is_safe(method: &Method) -> bool {
+    method == Method::GET || method == Method::HEAD || method == Method::OPTIONS
+}
+
+fn forbidden(message: &str) -> Response<BoxBody> {
+    AppError::Forbidden(message.to_string()).into_response()
+}
+
+/// true without an "Origin" header, old browsers do not send it
+fn same_origin(headers: &HeaderMap) -> bool {
+    let origin = match headers.get(header::ORIGIN) {
+        Some(origin) => origin.to_str().unwrap_or_default(),
+        None => return true,
+    };
+    let host = match headers.get(header::HOST).and_then(|h| h.to_str().ok()) {
+        Some(host) => host,
+        None => return false,
+    };
+    let origin_host = origin
+        .strip_prefix("http://")
+        .or_else(|| origin.strip_prefix("https://"));
+    return origin_host.is_some_and(|o| o.eq_ignore_ascii_case(host));
+}
+
+/// from the header or the form, the body of a form is read and put back
+///
+/// a form longer than `MAX_FORM_LENGTH` is an error, it is not read further
+async fn token(request: Request<Body>) -> Result<(Request<Body>, Option<String>), AppError> {
+    let header = request
+        .headers()
+        .get(CSRF_HEADER)
+        .and_then(|h| h.to_str().ok());
+    if let Some(token) = header {
+        let token = token.to_string();
+        return Ok((request, Some(token)));
+    }
+
+    let is_form = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|c| c.starts_with("application/x-www-form-urlencoded"));
+    if !is_form {
+        return Ok((request, None));
+    }
+    let too_large = || {
+        let message = format!("a form may have at most {} bytes", MAX_FORM_LENGTH);
+        AppError::PayloadTooLarge(message)
+    };
+    let content_length = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|h| h.to_str().ok()?.parse::<u64>().ok());
+    if content_length.is_some_and(|l| l > MAX_FORM_LENGTH as u64) {
+        return Err(too_large());
+    }
+
+    let (parts, mut body) = request.into_parts();
+    let mut form = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(_) => return Ok((Request::from_parts(parts, Body::empty()), None)),
+        };
+        if form.len() + chunk.len() > MAX_FORM_LENGTH {
+            return Err(too_large());
+        }
+        form.extend_from_slice(&chunk);
+    }
+    let token = serde_urlencoded::from_bytes::<Vec<(String, String)>>(&form)
+        .ok()
+        .and_then(|fields| fields.into_iter().find(|(name, _)| name == CSRF_FIELD))
+        .map(|(_, value)| value);
+    return Ok((Request::from_parts(parts, Body::from(form)), token));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::http::{HeaderValue, StatusCode};
+
+    fn headers(origin: Option<&'static str>, host: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, HeaderValue::from_static(host));
+        if let Some(origin) = origin {
+            headers.insert(header::ORIGIN, HeaderValue::from_static(origin));
+        }
+        return headers;
+    }
+
+    #[test]
+    fn origin_of_this_server() {
+        assert!(same_origin(&headers(None, "localhost:8080")));
+        assert!(same_origin(&headers(
+            Some("http://localhost:8080"),
+            "localhost:8080"
+        )));
+        assert!(same_origin(&headers(
+            Some("https://Netflex.lan"),
+            "netflex.lan"
+        )));
+    }
+
+    #[test]
+    fn foreign_origin() {
+        assert!(!same_origin(&headers(
+            Some("http://evil.com"),
+            "localhost:8080"
+        )));
+        assert!(!same_origin(&headers(
+            Some("http://localhost"),
+            "localhost:8080"
+        )));
+        assert!(!same_origin(&headers(Some("null"), "localhost:8080")));
+    }
+
+    #[tokio::test]
+    async fn token_of_form_keeps_body() {
+        let request = Request::builder()
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from("name=a&csrf_token=abc"))
+            .unwrap();
+        let (request, token) = token(request).await.unwrap();
+        assert_eq!(token.as_deref(), Some("abc"));
+        let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"name=a&csrf_token=abc");
+    }
+
+    #[tokio::test]
+    async fn long_form_is_rejected() {
+        let form = "a".repeat(MAX_FORM_LENGTH + 1);
+        let declared = Request::builder()
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(header::CONTENT_LENGTH, form.len())
+            .body(Body::empty())
+            .unwrap();
+        let chunks = [form.clone(), form.clone()].map(Ok::<_, std::io::Error>);
+        let streamed = Request::builder()
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::wrap_stream(futures::stream::iter(chunks)))
+            .unwrap();
+        for request in [declared, streamed] {
+            let error = token(request).await.unwrap_err();
+            assert_eq!(error.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+#![allow(clippy::needless_return)]
+
+use super::auth::Visible;
+use super::error::AppError;
+use super::percent_encode;
+use super::stream::{confine, FileResponse, RangeHeader, Source, StreamSlot, Tracked};
+use crate::{repositories::FileRepository, services::Libraries};
+use axum::{
+    body::{Bytes, StreamBody},
+    extract::{Extension, Path},
+    http::{HeaderValue, Response, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use futures::stream::BoxStream;
+
+mod zip;
+
+const ARCHIVE_EXTENSION: &str = ".zip";
+
+pub fn setup() -> Router {
+    Router::new()
+        .route("/:file_id", get(download))
+        .route("/group/:archive", get(download_group))
+}
+
+async fn download(
+    Extension(files): Extension<FileRepository>,
+    Extension(libraries): Extension<Libraries>,
+    Path(file_id): Path<u64>,
+    RangeHeader(range): RangeHeader,
+    sendfile: Option<Extension<fs::Sendfile>>,
+    visible: Visible,
+    slot: StreamSlot,
+) -> Result<Tracked<Download>, AppError> {
+    let file = files
+        .find_by_id(file_id)
+        .await?
+        .filter(|f| visible.contains(&f.path))
+        .ok_or_else(|| AppError::NotFound(format!("file {} does not exist", file_id)))?;
+    let file = confine(&libraries, file).await?;
+
+    let source = Source::new(libraries.vfs().as_ref(), sendfile.map(|s| s.0));
+    let response = FileResponse::download(&source, &file, &range).await?;
+    let download = Download {
+        file_name: file.display_name(),
+        response,
+    };
+    return Ok(slot.track(&file.display_name(), download));
+}
+
+/// "archive" is the group_id followed by ".zip"
+async fn download_group(
+    Extension(files): Extension<FileRepository>,
+    Extension(libraries): Extension<Libraries>,
+    Path(archive): Path<String>,
+    visible: Visible,
+    slot: StreamSlot,
+) -> Result<Tracked<Archive>, AppError> {
+    let not_found = || AppError::NotFound(format!("{} does not exist", archive));
+    let group_id = archive
+        .strip_suffix(ARCHIVE_EXTENSION)
+        .ok_or_else(not_found)?;
+
+    let mut confined = Vec::new();
+    let group = files.find_all_by_group(group_id).await?;
+    for file in group.into_iter().filter(|f| visible.contains(&f.path)) {
+        confined.push(confine(&libraries, file).await?);
+    }
+    let files = confined;
+    let file_name = archive_name(&files).ok_or_else(not_found)?;
+
+    let entries: Vec<zip::Entry> = files.into_iter().map(zip::Entry::from).collect();
+    let archive = Archive {
+        file_name: file_name.to_owned(),
+        len: zip::archive_len(&entries),
+        content: Box::pin(zip::archive(libraries.vfs().clone(), entries)),
+    };
+    return Ok(slot.track(&file_name, archive));
+}
+
+/// name of the directory containing the files of the group
+fn archive_name(files: &[crate::entities::File]) -> Option<String> {
+    let file = files.first()?;
+    let directory = fs::decode_path(&file.path)
+        .parent()
+        .and_then(|p| p.file_name())
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|| file.group_id.to_owned());
+    // members of "album.zip!" are downloaded as "album.zip"
+    let archive = directory.strip_suffix('!').map(std::path::Path::new);
+    let is_archive = archive.and_then(|a| a.extension()).is_some_and(|e| {
+        fs::ARCHIVE_EXTENSIONS
+            .iter()
+            .any(|a| e.eq_ignore_ascii_case(a))
+    });
+    let directory = match (is_archive, archive.and_then(|a| a.file_stem())) {
+        (true, Some(stem)) => stem.to_string_lossy().to_string(),
+        _ => directory,
+    };
+    return Some(format!("{}{}", directory, ARCHIVE_EXTENSION));
+}
+
+/// a zip archive of all files of a group which is created while it is sent
+pub struct Archive {
+    file_name: String,
+    len: u64,
+    content: BoxStream<'static, std::io::Result<Bytes>>,
+}
+
+impl IntoResponse for Archive {
+    type Body = StreamBody<BoxStream<'static, std::io::Result<Bytes>>>;
+    type BodyError = <Self::Body as axum::body::HttpBody>::Error;
+
+    fn into_response(self) -> Response<Self::Body> {
+        Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/zip")
+            .header("Content-Length", self.len)
+            .header("Content-Disposition", content_disposition(&self.file_name))
+            .body(StreamBody::new(self.content))
+            .expect("valid streaming body")
+    }
+}
+
+/// a file response that browsers save instead of displaying it
+pub struct Download {
+    file_name: String,
+    response: FileResponse,
+}
+
+impl IntoResponse for Download {
+    type Body = StreamBody<fs::FileStream>;
+    type BodyError = <Self::Body as axum::body::HttpBody>::Error;
+
+    fn into_response(self) -> Response<Self::Body> {
+        let mut response = self.response.into_response();
+        let disposition = content_disposition(&self.file_name);
+        let disposition = HeaderValue::from_str(&disposition).expect("header is ascii");
+        response
+            .headers_mut()
+            .insert("Content-Disposition", disposition);
+        return response;
+    }
+}
+
+/// RFC 6266 header with an ascii fallback and the utf-8 name encoded as described in RFC 5987
+fn content_disposition(file_name: &str) -> String {
+    let fallback: String = file_name
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect();
+
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback,
+        percent_encode(file_name)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn file(path: &str) -> crate::entities::File {
+        crate::entities::File {
+            id: 1,
+            name: String::new(),
+            path: path.to_string(),
+            mime: String::new(),
+            size: 0,
+            group_id: "1".to_string(),
+            group_member_name: String::new(),
+            added: 0,
+        }
+    }
+
+    #[test]
+    fn archive_is_named_after_directory() {
+        let name = |path| archive_name(&[file(path)]).unwrap();
+        assert_eq!(name("/music/Album/01.flac"), "Album.zip");
+        assert_eq!(name("/music/Album.zip!/01.flac"), "Album.zip");
+        assert_eq!(name("/comics/Issue 1.cbz!/01.jpg"), "Issue 1.zip");
+        assert_eq!(name("/music/Wow!/01.flac"), "Wow!.zip");
+    }
+
+    #[test]
+    fn ascii_name() {
+        assert_eq!(
+            content_disposition("movie.mp4"),
+            "attachment; filename=\"movie.mp4\"; filename*=UTF-8''movie.mp4"
+        );
+    }
+
+    #[test]
+    fn utf8_name() {
+        assert_eq!(
+            content_disposition("Amélie 2.mkv"),
+            "attachment; filename=\"Am_lie 2.mkv\"; filename*=UTF-8''Am%C3%A9lie%202.mkv"
+        );
+    }
+
+    #[test]
+    fn quotes_are_not_passed_through() {
+        assert_eq!(
+            content_disposition("a\"b\\c.mp3"),
+            "attachment; filename=\"a_b_c.mp3\"; filename*=UTF-8''a%22b%5Cc.mp3"
+        );
+    }
+}
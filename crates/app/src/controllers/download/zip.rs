@@ -0,0 +1,358 @@
+//! streams an uncompressed (store-only) ZIP64 archive without writing it to disk
+//!
+//! the crc32 of a file is only known after it was read, so it is written to a data descriptor
+//! behind the content. all other header fields only depend on names and sizes, which allows
+//! calculating the length of the archive before it is created
+
+#![allow(clippy::needless_return)]
+
+use crate::entities::File;
+use axum::body::Bytes;
+use crc32fast::Hasher;
+use fs::Vfs;
+use futures::{stream, Stream, StreamExt};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const VERSION: u16 = 45; // 4.5: ZIP64
+const FLAGS: u16 = 0x0008 | 0x0800; // data descriptor + utf-8 names
+const METHOD_STORE: u16 = 0;
+const DOS_TIME: u16 = 0;
+const DOS_DATE: u16 = (1 << 5) | 1; // 1980-01-01
+const ZIP64_MARKER_32: u32 = u32::MAX;
+const ZIP64_MARKER_16: u16 = u16::MAX;
+
+const LOCAL_HEADER_LEN: u64 = 30;
+const LOCAL_EXTRA_LEN: u64 = 20;
+const DATA_DESCRIPTOR_LEN: u64 = 24;
+const CENTRAL_HEADER_LEN: u64 = 46;
+const CENTRAL_EXTRA_LEN: u64 = 28;
+const END_OF_ARCHIVE_LEN: u64 = 56 + 20 + 22;
+/// the length of a name is written as u16
+const MAX_NAME_LEN: usize = u16::MAX as usize;
+
+pub struct Entry {
+    /// at most `MAX_NAME_LEN` bytes
+    name: String,
+    path: PathBuf,
+    size: u64,
+}
+
+impl Entry {
+    /// longer names are cut at the last character that fits
+    fn new(mut name: String, path: PathBuf, size: u64) -> Self {
+        if name.len() > MAX_NAME_LEN {
+            let end = (0..=MAX_NAME_LEN)
+                .rev()
+                .find(|i| name.is_char_boundary(*i))
+                .unwrap_or(0);
+            name.truncate(end);
+        }
+        Self { name, path, size }
+    }
+}
+
+impl From<File> for Entry {
+    fn from(file: File) -> Self {
+        Self::new(file.display_name(), fs::decode_path(&file.path), file.size)
+    }
+}
+
+/// total size in bytes of the archive containing `entries`
+pub fn archive_len(entries: &[Entry]) -> u64 {
+    let files: u64 = entries
+        .iter()
+        .map(|e| local_len(e) + CENTRAL_HEADER_LEN + e.name.len() as u64 + CENTRAL_EXTRA_LEN)
+        .sum();
+    return files + END_OF_ARCHIVE_LEN;
+}
+
+fn local_len(entry: &Entry) -> u64 {
+    LOCAL_HEADER_LEN + entry.name.len() as u64 + LOCAL_EXTRA_LEN + entry.size + DATA_DESCRIPTOR_LEN
+}
+
+/// fails if a file is missing or its size differs from the one in `entries`
+pub fn archive(
+    vfs: Arc<dyn Vfs>,
+    entries: Vec<Entry>,
+) -> impl Stream<Item = io::Result<Bytes>> + Send {
+    let state = State {
+        vfs,
+        entries,
+        index: 0,
+        crcs: Vec::new(),
+        step: Step::Header,
+    };
+    stream::try_unfold(state, next_chunk)
+}
+
+struct State {
+    vfs: Arc<dyn Vfs>,
+    entries: Vec<Entry>,
+    /// entry that is currently written
+    index: usize,
+    crcs: Vec<u32>,
+    step: Step,
+}
+
+enum Step {
+    Header,
+    Content {
+        content: fs::FileStream,
+        hasher: Hasher,
+        read: u64,
+    },
+    Done,
+}
+
+async fn next_chunk(mut state: State) -> io::Result<Option<(Bytes, State)>> {
+    let step = std::mem::replace(&mut state.step, Step::Done);
+    match step {
+        Step::Header if state.index == state.entries.len() => {
+            let directory = central_directory(&state.entries, &state.crcs);
+            return Ok(Some((directory, state)));
+        }
+        Step::Header => {
+            let entry = &state.entries[state.index];
+            let range = fs::Range::new(0, entry.size);
+            let content = state.vfs.open_range(&entry.path, &range).await?;
+            let header = local_header(entry);
+            state.step = Step::Content {
+                content,
+                hasher: Hasher::new(),
+                read: 0,
+            };
+            return Ok(Some((header, state)));
+        }
+        Step::Content {
+            mut content,
+            mut hasher,
+            read,
+        } => match content.next().await {
+            Some(bytes) => {
+                let bytes = bytes?;
+                hasher.update(&bytes);
+                state.step = Step::Content {
+                    content,
+                    hasher,
+                    read: read + bytes.len() as u64,
+                };
+                return Ok(Some((bytes, state)));
+            }
+            None => {
+                let entry = &state.entries[state.index];
+                if read != entry.size {
+                    let message = format!("size of '{}' changed", entry.path.display());
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, message));
+                }
+                let crc = hasher.finalize();
+                let descriptor = data_descriptor(entry, crc);
+                state.crcs.push(crc);
+                state.index += 1;
+                state.step = Step::Header;
+                return Ok(Some((descriptor, state)));
+            }
+        },
+        Step::Done => return Ok(None),
+    }
+}
+
+fn local_header(entry: &Entry) -> Bytes {
+    let mut header = Vec::new();
+    put_u32(&mut header, 0x04034b50);
+    put_u16(&mut header, VERSION);
+    put_u16(&mut header, FLAGS);
+    put_u16(&mut header, METHOD_STORE);
+    put_u16(&mut header, DOS_TIME);
+    put_u16(&mut header, DOS_DATE);
+    put_u32(&mut header, 0); // crc32 is in the data descriptor
+    put_u32(&mut header, ZIP64_MARKER_32);
+    put_u32(&mut header, ZIP64_MARKER_32);
+    put_u16(&mut header, entry.name.len() as u16);
+    put_u16(&mut header, LOCAL_EXTRA_LEN as u16);
+    header.extend_from_slice(entry.name.as_bytes());
+
+    put_u16(&mut header, 0x0001); // zip64 extra field
+    put_u16(&mut header, 16);
+    put_u64(&mut header, entry.size);
+    put_u64(&mut header, entry.size);
+    return Bytes::from(header);
+}
+
+fn data_descriptor(entry: &Entry, crc: u32) -> Bytes {
+    let mut descriptor = Vec::new();
+    put_u32(&mut descriptor, 0x08074b50);
+    put_u32(&mut descriptor, crc);
+    put_u64(&mut descriptor, entry.size);
+    put_u64(&mut descriptor, entry.size);
+    return Bytes::from(descriptor);
+}
+
+fn central_directory(entries: &[Entry], crcs: &[u32]) -> Bytes {
+    let mut directory = Vec::new();
+    let mut offset = 0;
+
+    for (entry, crc) in entries.iter().zip(crcs) {
+        put_u32(&mut directory, 0x02014b50);
+        put_u16(&mut directory, VERSION);
+        put_u16(&mut directory, VERSION);
+        put_u16(&mut directory, FLAGS);
+        put_u16(&mut directory, METHOD_STORE);
+        put_u16(&mut directory, DOS_TIME);
+        put_u16(&mut directory, DOS_DATE);
+        put_u32(&mut directory, *crc);
+        put_u32(&mut directory, ZIP64_MARKER_32);
+        put_u32(&mut directory, ZIP64_MARKER_32);
+        put_u16(&mut directory, entry.name.len() as u16);
+        put_u16(&mut directory, CENTRAL_EXTRA_LEN as u16);
+        put_u16(&mut directory, 0); // comment
+        put_u16(&mut directory, 0); // disk
+        put_u16(&mut directory, 0); // internal attributes
+        put_u32(&mut directory, 0); // external attributes
+        put_u32(&mut directory, ZIP64_MARKER_32);
+        directory.extend_from_slice(entry.name.as_bytes());
+
+        put_u16(&mut directory, 0x0001); // zip64 extra field
+        put_u16(&mut directory, 24);
+        put_u64(&mut directory, entry.size);
+        put_u64(&mut directory, entry.size);
+        put_u64(&mut directory, offset);
+
+        offset += local_len(entry);
+    }
+
+    let directory_len = directory.len() as u64;
+    let entry_count = entries.len() as u64;
+
+    // zip64 end of central directory record
+    put_u32(&mut directory, 0x06064b50);
+    put_u64(&mut directory, 44);
+    put_u16(&mut directory, VERSION);
+    put_u16(&mut directory, VERSION);
+    put_u32(&mut directory, 0);
+    put_u32(&mut directory, 0);
+    put_u64(&mut directory, entry_count);
+    put_u64(&mut directory, entry_count);
+    put_u64(&mut directory, directory_len);
+    put_u64(&mut directory, offset);
+
+    // zip64 end of central directory locator
+    put_u32(&mut directory, 0x07064b50);
+    put_u32(&mut directory, 0);
+    put_u64(&mut directory, offset + directory_len);
+    put_u32(&mut directory, 1);
+
+    // end of central directory record
+    put_u32(&mut directory, 0x06054b50);
+    put_u16(&mut directory, 0);
+    put_u16(&mut directory, 0);
+    put_u16(&mut directory, ZIP64_MARKER_16);
+    put_u16(&mut directory, ZIP64_MARKER_16);
+    put_u32(&mut directory, ZIP64_MARKER_32);
+    put_u32(&mut directory, ZIP64_MARKER_32);
+    put_u16(&mut directory, 0);
+
+    return Bytes::from(directory);
+}
+
+fn put_u16(buffer: &mut Vec<u8>, value: u16) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buffer: &mut Vec<u8>, value: u64) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::TryStreamExt;
+    use std::io::{Cursor, Read};
+
+    fn entry(name: &str, path: &str) -> Entry {
+        let size = std::fs::metadata(path).unwrap().len();
+        Entry::new(name.to_string(), PathBuf::from(path), size)
+    }
+
+    async fn create(entries: Vec<Entry>) -> io::Result<Vec<u8>> {
+        let parts: Vec<Bytes> = archive(Arc::new(fs::Local), entries).try_collect().await?;
+        return Ok(parts.concat());
+    }
+
+    #[tokio::test]
+    async fn length_is_precomputed() {
+        let entries = vec![
+            entry("music.mp3", "./tests/data/music.mp3"),
+            entry("tëst.txt", "./tests/data/dir1/test1.txt"),
+        ];
+        let expected_len = archive_len(&entries);
+
+        let archive = create(entries).await.unwrap();
+        assert_eq!(archive.len() as u64, expected_len);
+    }
+
+    #[tokio::test]
+    async fn long_names_are_cut() {
+        // "é" has two bytes, so the limit is in the middle of a character
+        let name = "é".repeat(MAX_NAME_LEN);
+        let entries = vec![entry(&name, "./tests/data/music.mp3")];
+        assert_eq!(entries[0].name.len(), MAX_NAME_LEN - 1);
+        let expected_len = archive_len(&entries);
+
+        let archive = create(entries).await.unwrap();
+        assert_eq!(archive.len() as u64, expected_len);
+        let mut reader = zip::ZipArchive::new(Cursor::new(archive)).unwrap();
+        let file = reader.by_index(0).unwrap();
+        assert_eq!(file.name(), &name[..MAX_NAME_LEN - 1]);
+    }
+
+    #[tokio::test]
+    async fn readable_by_zip_reader() {
+        let entries = vec![
+            entry("music.mp3", "./tests/data/music.mp3"),
+            entry("tëst.txt", "./tests/data/dir1/test1.txt"),
+        ];
+        let archive = create(entries).await.unwrap();
+        let mut reader = zip::ZipArchive::new(Cursor::new(archive)).unwrap();
+
+        assert_eq!(reader.len(), 2);
+
+        let mut content = Vec::new();
+        reader
+            .by_name("music.mp3")
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, std::fs::read("./tests/data/music.mp3").unwrap());
+        assert!(reader.by_name("tëst.txt").is_ok());
+    }
+
+    #[tokio::test]
+    async fn empty_archive() {
+        let archive = create(Vec::new()).await.unwrap();
+        assert_eq!(archive.len() as u64, archive_len(&[]));
+        assert_eq!(zip::ZipArchive::new(Cursor::new(archive)).unwrap().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn changed_size_is_error() {
+        let mut changed = entry("music.mp3", "./tests/data/music.mp3");
+        changed.size += 1;
+        assert!(create(vec![changed]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn missing_file_is_error() {
+        let missing = Entry {
+            name: "missing".to_string(),
+            path: PathBuf::from("./tests/data/not_found"),
+            size: 1,
+        };
+        assert!(create(vec![missing]).await.is_err());
+    }
+}
@@ -0,0 +1,281 @@
+#![allow(clippy::needless_return)]
+
+use super::api::ApiError;
+use askama::Template;
+use axum::{
+    body::{boxed, BoxBody},
+    http::{header, HeaderMap, HeaderValue, Request, Response, StatusCode},
+    response::{Html, IntoResponse},
+};
+use futures::future::BoxFuture;
+use std::{
+    convert::Infallible,
+    fmt, io,
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
+
+/// error of the html pages and streams, the message of internal errors is only logged
+#[derive(Debug)]
+pub enum AppError {
+    NotFound(String),
+    BadRequest(String),
+    /// no valid session
+    Unauthorized(String),
+    /// the role of the user is too low
+    Forbidden(String),
+    /// e.g. a scan is already running
+    Conflict(String),
+    /// the requested range is outside of a file with this size
+    RangeNotSatisfiable(u64),
+    /// the body of the request is too long to be read
+    PayloadTooLarge(String),
+    /// e.g. the server is shutting down
+    Unavailable(String),
+    /// too much is running at the same time, the client should retry after the seconds
+    Busy(String, u64),
+    Io(io::Error),
+    /// errors of the repositories, they are strings
+    Database(String),
+    /// errors of templates and services
+    Internal(String),
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::RangeNotSatisfiable(_) => StatusCode::RANGE_NOT_SATISFIABLE,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::Unavailable(_) | AppError::Busy(..) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Io(_) | AppError::Database(_) | AppError::Internal(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    /// safe to send to the client, internal errors may contain paths
+    pub fn message(&self) -> String {
+        match self {
+            AppError::NotFound(m)
+            | AppError::BadRequest(m)
+            | AppError::Unauthorized(m)
+            | AppError::Forbidden(m)
+            | AppError::Conflict(m)
+            | AppError::PayloadTooLarge(m)
+            | AppError::Unavailable(m)
+            | AppError::Busy(m, _) => m.to_owned(),
+            AppError::RangeNotSatisfiable(size) => {
+                format!("the range is outside of the {} bytes of the file", size)
+            }
+            AppError::Io(_) | AppError::Database(_) | AppError::Internal(_) => {
+                "internal server error".to_string()
+            }
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::NotFound(m) => write!(f, "not found: {}", m),
+            AppError::BadRequest(m) => write!(f, "bad request: {}", m),
+            AppError::Unauthorized(m) => write!(f, "unauthorized: {}", m),
+            AppError::Forbidden(m) => write!(f, "forbidden: {}", m),
+            AppError::Conflict(m) => write!(f, "conflict: {}", m),
+            AppError::RangeNotSatisfiable(_) => write!(f, "{}", self.message()),
+            AppError::PayloadTooLarge(m) => write!(f, "payload too large: {}", m),
+            AppError::Unavailable(m) => write!(f, "unavailable: {}", m),
+            AppError::Busy(m, _) => write!(f, "busy: {}", m),
+            AppError::Io(e) => write!(f, "io error: {}", e),
+            AppError::Database(m) => write!(f, "database error: {}", m),
+            AppError::Internal(m) => write!(f, "internal error: {}", m),
+        }
+    }
+}
+
+impl From<io::Error> for AppError {
+    fn from(error: io::Error) -> Self {
+        AppError::Io(error)
+    }
+}
+
+/// errors of the repositories are strings
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        AppError::Database(message)
+    }
+}
+
+#[derive(Template)]
+#[template(path = "views/error.html")]
+struct ErrorTemplate {
+    status: u16,
+    reason: &'static str,
+    message: String,
+}
+
+/// added to the response of an `AppError`, so `ErrorFormatLayer` can send it as json instead
+#[derive(Clone)]
+struct ErrorMessage {
+    status: StatusCode,
+    message: String,
+}
+
+impl IntoResponse for AppError {
+    type Body = BoxBody;
+    type BodyError = <BoxBody as axum::body::HttpBody>::Error;
+
+    fn into_response(self) -> Response<Self::Body> {
+        if self.status().is_server_error() {
+            println!("{}", self);
+        }
+
+        let status = self.status();
+        let message = self.message();
+        let template = ErrorTemplate {
+            status: status.as_u16(),
+            reason: status.canonical_reason().unwrap_or_default(),
+            message: message.to_owned(),
+        };
+        let mut response = match template.render() {
+            Ok(html) => Html(html).into_response().map(boxed),
+            Err(_) => message.to_owned().into_response().map(boxed),
+        };
+        *response.status_mut() = status;
+        match self {
+            AppError::RangeNotSatisfiable(size) => {
+                let content_range = HeaderValue::from_str(&format!("bytes */{}", size));
+                response.headers_mut().insert(
+                    header::CONTENT_RANGE,
+                    content_range.expect("digits are valid"),
+                );
+            }
+            AppError::Busy(_, retry_after) => {
+                response
+                    .headers_mut()
+                    .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+            }
+            _ => {}
+        }
+        response
+            .extensions_mut()
+            .insert(ErrorMessage { status, message });
+        return response;
+    }
+}
+
+/// sends errors as json like the api for "/api" and if the client prefers json over html in its
+/// "Accept" header
+#[derive(Clone, Copy)]
+pub struct ErrorFormatLayer;
+
+impl<S> Layer<S> for ErrorFormatLayer {
+    type Service = ErrorFormat<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ErrorFormat { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct ErrorFormat<S> {
+    inner: S,
+}
+
+impl<S, B> Service<Request<B>> for ErrorFormat<S>
+where
+    S: Service<Request<B>, Response = Response<BoxBody>, Error = Infallible>,
+    S::Future: Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let json = request.uri().path().starts_with("/api/") || prefers_json(request.headers());
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let response = response.await?;
+            let error = match response.extensions().get::<ErrorMessage>() {
+                Some(error) if json => error.clone(),
+                _ => return Ok(response),
+            };
+            // headers like "Content-Range" or "Retry-After" describe the error in both formats
+            let mut headers = response.headers().clone();
+            headers.remove(header::CONTENT_TYPE);
+            headers.remove(header::CONTENT_LENGTH);
+            let mut response = ApiError::new(error.status, error.message).into_response();
+            response.headers_mut().extend(headers);
+            Ok(response.map(boxed))
+        })
+    }
+}
+
+/// compares the quality of "application/json" and "text/html", wildcards count for neither
+fn prefers_json(headers: &HeaderMap) -> bool {
+    let accept = match headers.get(header::ACCEPT).and_then(|h| h.to_str().ok()) {
+        Some(accept) => accept,
+        None => return false,
+    };
+
+    let mut json = 0.0;
+    let mut html = 0.0;
+    for media_range in accept.split(',') {
+        let mut parts = media_range.split(';').map(str::trim);
+        let media_type = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let quality = parts
+            .find_map(|p| p.strip_prefix("q="))
+            .and_then(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        match media_type.as_str() {
+            "application/json" => json = quality,
+            "text/html" => html = quality,
+            _ => {}
+        }
+    }
+    return json > html;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn accept(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_str(value).unwrap());
+        return headers;
+    }
+
+    #[test]
+    fn html_by_default() {
+        assert!(!prefers_json(&HeaderMap::new()));
+        assert!(!prefers_json(&accept("*/*")));
+        assert!(!prefers_json(&accept(
+            "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"
+        )));
+    }
+
+    #[test]
+    fn json_if_preferred() {
+        assert!(prefers_json(&accept("application/json")));
+        assert!(prefers_json(&accept("text/html;q=0.5, application/json")));
+        assert!(!prefers_json(&accept("text/html, application/json;q=0.9")));
+    }
+
+    #[test]
+    fn internal_details_are_hidden() {
+        let error = AppError::Io(io::Error::other("/secret/path"));
+        assert_eq!(error.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(!error.message().contains("/secret/path"));
+        assert!(error.to_string().contains("/secret/path"));
+    }
+}
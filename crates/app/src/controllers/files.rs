@@ -1,7 +1,17 @@
-use super::render;
+use super::{
+    auth::Visible,
+    error::AppError,
+    list::{ListQuery, ListView},
+    render,
+};
 use crate::repositories::FileRepository;
 use askama::Template;
-use axum::{extract::Extension, response::Html, routing::get, Router};
+use axum::{
+    extract::{Extension, Query},
+    response::Html,
+    routing::get,
+    Router,
+};
 
 pub fn setup() -> Router {
     Router::new().route("/", get(list_all))
@@ -10,11 +20,18 @@ pub fn setup() -> Router {
 #[derive(Template)]
 #[template(path = "views/files.html")]
 struct ListAllTemplate {
-    files: Vec<crate::entities::File>,
+    list: ListView,
 }
 
-async fn list_all(Extension(files): Extension<FileRepository>) -> Result<Html<String>, String> {
-    let files = files.find_all().await?;
-    let template = render(ListAllTemplate { files })?;
+async fn list_all(
+    Extension(files): Extension<FileRepository>,
+    Query(query): Query<ListQuery>,
+    visible: Visible,
+) -> Result<Html<String>, AppError> {
+    let request = query.page()?;
+    let page = files.find_page(&query.filter(visible), request).await?;
+    let template = render(ListAllTemplate {
+        list: ListView::new(page, request, &query),
+    })?;
     Ok(Html::from(template))
 }
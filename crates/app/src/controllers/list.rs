@@ -0,0 +1,102 @@
+#![allow(clippy::needless_return)]
+
+use super::{auth::Visible, error::AppError};
+use crate::{
+    entities::File,
+    repositories::{FileFilter, Page, PageRequest, Sort, SortOrder},
+};
+use serde::{Deserialize, Serialize};
+
+/// query parameters of the list pages
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ListQuery {
+    /// part of the name
+    pub q: Option<String>,
+    pub page: Option<usize>,
+    pub per_page: Option<usize>,
+    pub sort: Option<Sort>,
+    pub order: Option<SortOrder>,
+}
+
+impl ListQuery {
+    /// the files of the query that are visible to the user
+    pub fn filter(&self, Visible(roots): Visible) -> FileFilter {
+        FileFilter {
+            // the search form sends an empty value if nothing was entered
+            query: self.q.to_owned().filter(|q| !q.is_empty()),
+            roots,
+            ..Default::default()
+        }
+    }
+
+    pub fn page(&self) -> Result<PageRequest, AppError> {
+        let request = PageRequest::new(self.page, self.per_page).map_err(AppError::BadRequest)?;
+        Ok(request.sorted(self.sort, self.order))
+    }
+}
+
+/// a page of files and everything needed to render the search form and the page navigation
+pub struct ListView {
+    pub items: Vec<File>,
+    pub q: String,
+    pub sort: Sort,
+    pub order: SortOrder,
+    pub page: usize,
+    pub per_page: usize,
+    pub pages: usize,
+    pub total: usize,
+}
+
+#[derive(Serialize)]
+struct LinkQuery<'a> {
+    q: &'a str,
+    page: usize,
+    per_page: usize,
+    sort: Sort,
+    order: SortOrder,
+}
+
+impl ListView {
+    pub fn new(page: Page<File>, request: PageRequest, query: &ListQuery) -> Self {
+        Self {
+            pages: page.pages(),
+            items: page.items,
+            q: query.q.to_owned().unwrap_or_default(),
+            sort: request.sort,
+            order: request.order,
+            page: page.page,
+            per_page: page.per_page,
+            total: page.total,
+        }
+    }
+
+    /// query string of the same list on another page
+    pub fn link(&self, page: &usize) -> String {
+        let query = LinkQuery {
+            q: &self.q,
+            page: *page,
+            per_page: self.per_page,
+            sort: self.sort,
+            order: self.order,
+        };
+        let query = serde_urlencoded::to_string(query).unwrap_or_default();
+        return format!("?{}", query);
+    }
+
+    pub fn has_previous(&self) -> bool {
+        self.page > 1
+    }
+
+    pub fn has_next(&self) -> bool {
+        self.page < self.pages
+    }
+
+    pub fn previous(&self) -> usize {
+        self.page.saturating_sub(1).max(1)
+    }
+
+    pub fn next(&self) -> usize {
+        self.page + 1
+    }
+}
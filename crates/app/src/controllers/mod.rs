@@ -1,21 +1,33 @@
+#![allow(clippy::needless_return)]
+
 use askama::Template;
+use auth::{require, Admin, AuthLayer};
 use axum::{
     error_handling::HandleErrorExt,
+    handler::Handler,
     http::{StatusCode, Uri},
     response::Redirect,
     routing::{get, service_method_routing as service},
     Router,
 };
+use csrf::CsrfLayer;
+use error::{AppError, ErrorFormatLayer};
 use tower_http::services::ServeDir;
 
+mod api;
 mod audios;
+mod auth;
+mod csrf;
+mod download;
+mod error;
 mod files;
+mod list;
+mod search;
 mod settings;
 mod stream;
 mod videos;
 
-const UPDATE_PATH: &'static str = "./";
-const STATIC_FILE_DIR: &'static str = "./crates/app/static";
+const STATIC_FILE_DIR: &str = "./crates/app/static";
 
 pub fn setup(router: Router) -> Router {
     router
@@ -28,14 +40,70 @@ pub fn setup(router: Router) -> Router {
         .nest("/audios", audios::setup())
         .nest("/files", files::setup())
         .nest("/stream", stream::setup())
-        .nest("/settings", settings::setup())
+        .nest("/download", download::setup())
+        .nest("/search", search::setup())
+        .nest("/settings", settings::setup().layer(require::<Admin>()))
+        .nest("/api", api::setup())
+        .merge(auth::setup())
         .route("/", get(index))
+        .fallback(handler_404.into_service())
+        .layer(CsrfLayer)
+        .layer(AuthLayer)
+        .layer(ErrorFormatLayer)
 }
 
 async fn index() -> Redirect {
     return Redirect::to(Uri::from_static("/videos"));
 }
 
-fn render(template: impl Template) -> Result<String, String> {
-    template.render().map_err(|e| e.to_string())
+async fn handler_404() -> AppError {
+    AppError::NotFound("this page does not exist".to_string())
+}
+
+fn render(template: impl Template) -> Result<String, AppError> {
+    template
+        .render()
+        .map_err(|e| AppError::Internal(e.to_string()))
+}
+
+/// encodes everything except the "attr-char"s of RFC 5987, only for the "filename*" of a
+/// "Content-Disposition" header because "#" and "|" are left as they are
+fn percent_encode(value: &str) -> String {
+    let mut output = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'a'..=b'z'
+            | b'A'..=b'Z'
+            | b'0'..=b'9'
+            | b'!'
+            | b'#'
+            | b'$'
+            | b'&'
+            | b'+'
+            | b'-'
+            | b'.'
+            | b'^'
+            | b'_'
+            | b'`'
+            | b'|'
+            | b'~' => output.push(byte as char),
+            _ => output.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    return output;
+}
+
+/// encodes everything except the unreserved characters of RFC 3986, so the value stays a single
+/// segment of a url path even with "#", "?" or "/" in it
+fn encode_path_segment(value: &str) -> String {
+    let mut output = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                output.push(byte as char)
+            }
+            _ => output.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    return output;
 }
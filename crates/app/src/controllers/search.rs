@@ -0,0 +1,164 @@
+#![allow(clippy::needless_return)]
+
+use super::{auth::Visible, error::AppError, render};
+use crate::repositories::{SearchHit, SearchRepository, MATCH_END, MATCH_START};
+use askama::Template;
+use axum::{
+    extract::{Extension, Query},
+    response::Html,
+    routing::get,
+    Router,
+};
+use serde::Deserialize;
+
+const MAX_RESULTS: u64 = 200;
+
+pub fn setup() -> Router {
+    Router::new().route("/", get(search))
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SearchQuery {
+    q: String,
+}
+
+#[derive(Template)]
+#[template(path = "views/search.html")]
+struct SearchTemplate {
+    q: String,
+    total: usize,
+    groups: Vec<ResultGroup>,
+}
+
+struct ResultGroup {
+    name: &'static str,
+    results: Vec<SearchResult>,
+}
+
+/// the texts are escaped html with the matched terms in `<mark>`
+struct SearchResult {
+    href: String,
+    mime: String,
+    title: String,
+    path: String,
+    tags: String,
+    plot: String,
+}
+
+impl From<SearchHit> for SearchResult {
+    fn from(hit: SearchHit) -> Self {
+        let title = match hit.title.is_empty() {
+            true => escape(&hit.file.name),
+            false => highlight(&hit.title),
+        };
+        Self {
+            href: match media_type(&hit.file.mime) {
+                MediaType::Video => format!("/videos/{}", hit.file.id),
+                MediaType::Audio => format!("/audios/{}", hit.file.id),
+                MediaType::Other => format!("/download/{}", hit.file.id),
+            },
+            mime: hit.file.mime,
+            title,
+            path: highlight(&hit.path),
+            tags: highlight(&hit.tags),
+            plot: highlight(&hit.plot),
+        }
+    }
+}
+
+#[derive(PartialEq)]
+enum MediaType {
+    Video,
+    Audio,
+    Other,
+}
+
+/// same distinction as `repositories::Videos` and `repositories::Audios`
+fn media_type(mime: &str) -> MediaType {
+    match mime {
+        "video/MP2T" | "video/vnd.dlna.mpeg-tts" => MediaType::Other,
+        "application/x-mpegURL" | "vnd.apple.mpegURL" => MediaType::Video,
+        _ if mime.starts_with("video/") => MediaType::Video,
+        _ if mime.starts_with("audio/") => MediaType::Audio,
+        _ => MediaType::Other,
+    }
+}
+
+async fn search(
+    Extension(search): Extension<SearchRepository>,
+    Query(query): Query<SearchQuery>,
+    Visible(roots): Visible,
+) -> Result<Html<String>, AppError> {
+    let hits = search
+        .search(&query.q, roots.as_deref(), MAX_RESULTS)
+        .await?;
+    let total = hits.len();
+
+    let mut groups = vec![
+        (MediaType::Video, "Videos", Vec::new()),
+        (MediaType::Audio, "Audios", Vec::new()),
+        (MediaType::Other, "Files", Vec::new()),
+    ];
+    for hit in hits {
+        let media_type = media_type(&hit.file.mime);
+        if let Some((_, _, results)) = groups.iter_mut().find(|(t, _, _)| *t == media_type) {
+            results.push(SearchResult::from(hit));
+        }
+    }
+
+    let template = render(SearchTemplate {
+        q: query.q,
+        total,
+        groups: groups
+            .into_iter()
+            .filter(|(_, _, results)| !results.is_empty())
+            .map(|(_, name, results)| ResultGroup { name, results })
+            .collect(),
+    })?;
+    Ok(Html::from(template))
+}
+
+/// escapes the text and replaces the markers of the search index with `<mark>`
+fn highlight(text: &str) -> String {
+    escape(text)
+        .replace(MATCH_START, "<mark>")
+        .replace(MATCH_END, "</mark>")
+}
+
+fn escape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#x27;"),
+            c => output.push(c),
+        }
+    }
+    return output;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn highlight_escapes_text() {
+        let text = format!("<b>{}toy{}</b> & co", MATCH_START, MATCH_END);
+        assert_eq!(
+            highlight(&text),
+            "&lt;b&gt;<mark>toy</mark>&lt;/b&gt; &amp; co"
+        );
+    }
+
+    #[test]
+    fn media_types() {
+        assert!(media_type("video/mp4") == MediaType::Video);
+        assert!(media_type("video/MP2T") == MediaType::Other);
+        assert!(media_type("audio/mpeg") == MediaType::Audio);
+        assert!(media_type("text/plain") == MediaType::Other);
+    }
+}
@@ -1,38 +1,136 @@
-use crate::services::UpdateService;
+#![allow(clippy::needless_return)]
+
+use crate::{
+    entities::{Role, User},
+    repositories::UserRepository,
+    services::{
+        AuthService, Libraries, ScanRefused, Shutdown, StreamService, StreamStatus, UpdateService,
+        IGNORE_FILE,
+    },
+};
 use askama::Template;
 use axum::{
-    extract::Extension,
+    extract::{Extension, Form},
     http::Uri,
     response::{Html, Redirect},
     routing::{get, post},
     Router,
 };
 
-use super::UPDATE_PATH;
-const REFRESH_REDIRECT_PATH: &'static str = "/";
+use super::{auth::validate_account, csrf::CsrfToken, error::AppError, render};
+use serde::Deserialize;
+const REFRESH_REDIRECT_PATH: &str = "/";
 
 pub fn setup() -> Router {
     Router::new()
         .route("/refresh", post(refresh))
         .route("/shutdown", post(shutdown))
+        .route("/users", post(create_user))
         .route("/", get(settings))
 }
 
 #[derive(Template)]
 #[template(path = "views/settings.html")]
-struct SettingsTemplate {}
+struct SettingsTemplate {
+    libraries: Vec<LibraryView>,
+    streams: Vec<ActiveStreamView>,
+    users: Vec<User>,
+    roles: [Role; 3],
+    csrf_token: String,
+    ignore_file: &'static str,
+}
+
+struct LibraryView {
+    root: String,
+    symlinks: &'static str,
+    guests: bool,
+}
+
+struct ActiveStreamView {
+    client: String,
+    file: String,
+    sent: String,
+    throughput: String,
+}
 
-async fn settings() -> Result<Html<String>, String> {
-    let template = SettingsTemplate {}.render().map_err(|e| e.to_string())?;
+impl From<StreamStatus> for ActiveStreamView {
+    fn from(status: StreamStatus) -> Self {
+        Self {
+            throughput: format!("{}/s", format_bytes(status.throughput())),
+            sent: format_bytes(status.sent),
+            client: status.client,
+            file: status.file,
+        }
+    }
+}
+
+async fn settings(
+    Extension(streams): Extension<StreamService>,
+    Extension(users): Extension<UserRepository>,
+    Extension(libraries): Extension<Libraries>,
+    Extension(CsrfToken(csrf_token)): Extension<CsrfToken>,
+) -> Result<Html<String>, AppError> {
+    let streams = streams.active().into_iter().map(Into::into).collect();
+    let libraries = libraries
+        .all()
+        .iter()
+        .map(|l| LibraryView {
+            root: l.root().display().to_string(),
+            symlinks: l.symlinks().as_str(),
+            guests: l.guests(),
+        })
+        .collect();
+    let template = render(SettingsTemplate {
+        libraries,
+        streams,
+        users: users.find_all().await?,
+        roles: Role::ALL,
+        csrf_token,
+        ignore_file: IGNORE_FILE,
+    })?;
     Ok(Html::from(template))
 }
 
-async fn refresh(Extension(updater): Extension<UpdateService>) -> Result<Redirect, String> {
-    updater.run(UPDATE_PATH.to_string()).await?;
+async fn refresh(Extension(updater): Extension<UpdateService>) -> Result<Redirect, AppError> {
+    updater.run().await.map_err(|e| match e {
+        ScanRefused::Running => AppError::Conflict(e.to_string()),
+        ScanRefused::ShuttingDown => AppError::Unavailable(e.to_string()),
+    })?;
     let redirect = Redirect::to(Uri::from_static(REFRESH_REDIRECT_PATH));
     return Ok(redirect);
 }
 
-async fn shutdown() {
-    std::process::exit(0)
+#[derive(Deserialize)]
+struct UserForm {
+    name: String,
+    password: String,
+    role: Role,
+}
+
+async fn create_user(
+    Extension(auth): Extension<AuthService>,
+    Form(form): Form<UserForm>,
+) -> Result<Redirect, AppError> {
+    validate_account(&form.name, &form.password).map_err(AppError::BadRequest)?;
+    auth.create_user(form.name.trim(), &form.password, form.role)
+        .await?
+        .ok_or_else(|| AppError::Conflict(format!("the name {} is already taken", form.name)))?;
+    return Ok(Redirect::to(Uri::from_static("/settings")));
+}
+
+/// the server stops once the active responses finished
+async fn shutdown(Extension(shutdown): Extension<Shutdown>) -> &'static str {
+    shutdown.trigger();
+    "Netflex is shutting down"
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    return format!("{:.1} {}", value, UNITS[unit]);
 }
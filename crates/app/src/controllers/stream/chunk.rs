@@ -1,47 +1,35 @@
-use super::range::Range;
+use super::source::Source;
 use crate::entities::File;
 use axum::{
-    body::{Bytes, Full},
+    body::StreamBody,
     http::{Response, StatusCode},
     response::IntoResponse,
 };
-use std::convert::Infallible;
 
 pub struct Chunk {
     start: u64,
     end: u64,
     file_size: u64,
     mime: String,
-    content: Vec<u8>,
+    content: fs::FileStream,
 }
 
 impl Chunk {
-    pub async fn new(file: &File, range: &Option<Range>) -> Result<Self, String> {
-        let range = default_range(file, range);
-        let fs_file = fs::File::new(file.path.to_owned(), file.size);
-
+    /// `range` is inside of the file and not empty, see `Range::resolve`
+    pub async fn new(source: &Source<'_>, file: &File, range: fs::Range) -> std::io::Result<Self> {
         Ok(Self {
             start: range.start(),
-            end: range.end().unwrap_or(0),
+            end: range.start() + range.offset() - 1,
             file_size: file.size,
             mime: file.mime.to_string(),
-            content: fs_file
-                .chunk(&range.range())
-                .await
-                .map_err(|e| e.to_string())?,
+            content: source.open_range(file, &range).await?,
         })
     }
 }
 
-fn default_range(file: &File, range: &Option<Range>) -> Range {
-    let range = range.unwrap_or_default();
-    let range = range.apply_file_size(file.size);
-    return range;
-}
-
 impl IntoResponse for Chunk {
-    type Body = Full<Bytes>;
-    type BodyError = Infallible;
+    type Body = StreamBody<fs::FileStream>;
+    type BodyError = <Self::Body as axum::body::HttpBody>::Error;
 
     fn into_response(self) -> Response<Self::Body> {
         Response::builder()
@@ -52,7 +40,8 @@ impl IntoResponse for Chunk {
                 "Content-Range",
                 format!("bytes {}-{}/{}", self.start, self.end, self.file_size),
             )
-            .body(Full::from(self.content))
+            .header("Content-Length", self.end + 1 - self.start)
+            .body(StreamBody::new(self.content))
             .expect("valid streaming body")
     }
 }
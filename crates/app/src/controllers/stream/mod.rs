@@ -1,32 +1,113 @@
-use self::{range::Range, response::FileResponse};
-use crate::repositories::FileRepository;
+#![allow(clippy::needless_return)]
+
+use self::transcode::Transcode;
+pub(super) use self::{
+    range::RangeHeader, response::FileResponse, slot::StreamSlot, source::Source, tracked::Tracked,
+};
+use super::{auth::Visible, error::AppError};
+use crate::{
+    entities::File,
+    repositories::FileRepository,
+    services::{Libraries, TranscodeError, TranscodeService},
+};
 use axum::{
     extract::{Extension, Path},
     routing::get,
     Router,
 };
+use std::io::ErrorKind;
 
 mod chunk;
 mod range;
 mod response;
+mod slot;
+mod source;
+mod tracked;
+mod transcode;
 mod whole;
 
 const DEFAULT_RANGE: u64 = 1048576;
 
 pub fn setup() -> Router {
-    Router::new().route("/:group_id/:group_member_name", get(stream))
+    Router::new()
+        .route("/:group_id/:group_member_name", get(stream))
+        .route("/transcode/:group_id/:group_member_name", get(transcode))
 }
 
 async fn stream(
     Extension(files): Extension<FileRepository>,
+    Extension(libraries): Extension<Libraries>,
     Path((group_id, group_member_name)): Path<(String, String)>,
-    range: Option<Range>,
-) -> Result<FileResponse, String> {
+    RangeHeader(range): RangeHeader,
+    sendfile: Option<Extension<fs::Sendfile>>,
+    visible: Visible,
+    slot: StreamSlot,
+) -> Result<Tracked<FileResponse>, AppError> {
     let file = files
         .find_by_group(&group_id, &group_member_name)
         .await?
-        .ok_or_else(|| "not found".to_string())?;
+        .filter(|f| visible.contains(&f.path))
+        .ok_or_else(|| AppError::NotFound(format!("{} does not exist", group_member_name)))?;
+    let file = confine(&libraries, file).await?;
+
+    let source = Source::new(libraries.vfs().as_ref(), sendfile.map(|s| s.0));
+    let response = FileResponse::new(&source, &file, &range).await?;
+    return Ok(slot.track(&file.display_name(), response));
+}
+
+async fn transcode(
+    Extension(files): Extension<FileRepository>,
+    Extension(transcoder): Extension<TranscodeService>,
+    Extension(libraries): Extension<Libraries>,
+    Path((group_id, group_member_name)): Path<(String, String)>,
+    visible: Visible,
+    slot: StreamSlot,
+) -> Result<Tracked<Transcode>, AppError> {
+    let file = files
+        .find_by_group(&group_id, &group_member_name)
+        .await?
+        .filter(|f| visible.contains(&f.path))
+        .ok_or_else(|| AppError::NotFound(format!("{} does not exist", group_member_name)))?;
+    let file = confine(&libraries, file).await?;
+    // the transcoder reads from the disk, not from archives
+    if libraries
+        .vfs()
+        .local_path(&fs::decode_path(&file.path))
+        .is_none()
+    {
+        return Err(AppError::BadRequest(format!(
+            "{} can not be transcoded",
+            file.display_name()
+        )));
+    }
+
+    let response = Transcode::new(&transcoder, &file)
+        .await
+        .map_err(|e| match e {
+            TranscodeError::Busy(retry_after) => AppError::Busy(e.to_string(), retry_after),
+            TranscodeError::Failed(m) => AppError::Internal(m),
+        })?;
+    return Ok(slot.track(&file.display_name(), response));
+}
 
-    let response = FileResponse::new(&file, &range).await?;
-    return Ok(response);
+/// the file with its canonical path, checked right before it is opened because the file system
+/// may have changed since the scan
+pub(super) async fn confine(libraries: &Libraries, file: File) -> Result<File, AppError> {
+    let path = libraries
+        .resolve(&file.path)
+        .await
+        .map_err(|e| match e.kind() {
+            ErrorKind::NotFound => {
+                AppError::NotFound(format!("{} does not exist", file.display_name()))
+            }
+            ErrorKind::PermissionDenied => AppError::Forbidden(format!(
+                "{} is outside of the libraries",
+                file.display_name()
+            )),
+            _ => AppError::Io(e),
+        })?;
+    Ok(File {
+        path: fs::encode_path(&path),
+        ..file
+    })
 }
@@ -1,73 +1,161 @@
+#![allow(clippy::needless_return)]
+
+use super::AppError;
 use axum::{
     async_trait,
     extract::{FromRequest, RequestParts},
-    http::StatusCode,
+    http::header,
 };
-use once_cell::sync::Lazy;
-use regex::Regex;
-use std::cmp::max;
-
-use super::DEFAULT_RANGE;
 
-#[derive(Default, Clone, Copy)]
-pub struct Range(fs::Range);
+/// a single range of a "Range" header, the end is inclusive like in http
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Range {
+    /// "bytes=100-199", or "bytes=100-" without an end
+    From { start: u64, end: Option<u64> },
+    /// "bytes=-500" are the last 500 bytes of the file
+    Suffix(u64),
+}
 
 impl Range {
-    pub fn start(&self) -> u64 {
-        self.0.start()
-    }
-
-    /// returns start + offset or None if the number is too big
-    pub fn end(&self) -> Option<u64> {
-        let offset = self.0.offset().checked_sub(1).unwrap_or(0);
-        self.0.start().checked_add(offset)
-    }
+    /// "bytes=<start>-[<end>]" or "bytes=-<length>". Other units and lists of ranges are not
+    /// supported and ignored with `None` like rfc 9110 allows, the whole file is sent for them
+    pub fn parse(value: &str) -> Result<Option<Self>, String> {
+        let invalid = || format!("invalid range: {}", value);
+        let number = |n: &str| match !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()) {
+            true => n.parse::<u64>().map_err(|_| invalid()),
+            false => Err(invalid()),
+        };
 
-    pub fn range(&self) -> fs::Range {
-        self.0
+        let (unit, spec) = value.trim().split_once('=').ok_or_else(invalid)?;
+        if !unit.trim().eq_ignore_ascii_case("bytes") || spec.contains(',') {
+            return Ok(None);
+        }
+        let (start, end) = spec.split_once('-').ok_or_else(invalid)?;
+        let range = match (start.trim(), end.trim()) {
+            ("", length) => Range::Suffix(number(length)?),
+            (start, "") => Range::From {
+                start: number(start)?,
+                end: None,
+            },
+            (start, end) => {
+                let (start, end) = (number(start)?, number(end)?);
+                if end < start {
+                    return Err(invalid());
+                }
+                Range::From {
+                    start,
+                    end: Some(end),
+                }
+            }
+        };
+        return Ok(Some(range));
     }
 
-    pub fn apply_file_size(&self, file_size: u64) -> Range {
-        Range(self.0.apply_filesize(file_size))
+    /// the bytes of a file with `file_size` bytes to send, a range without an end reaches at most
+    /// `open_length` bytes. `None` if no byte of the file is in the range
+    pub fn resolve(&self, file_size: u64, open_length: u64) -> Option<fs::Range> {
+        let (start, length) = match *self {
+            Range::From {
+                start,
+                end: Some(end),
+            } => (start, (end - start).saturating_add(1)),
+            Range::From { start, end: None } => (start, open_length),
+            Range::Suffix(length) => (file_size.saturating_sub(length), length),
+        };
+        let range = fs::Range::new(start, length).apply_filesize(file_size)?;
+        return (range.offset() > 0).then_some(range);
     }
 }
 
+/// the range of the "Range" header, `None` without the header
+pub struct RangeHeader(pub Option<Range>);
+
 #[async_trait]
-impl<B> FromRequest<B> for Range
+impl<B> FromRequest<B> for RangeHeader
 where
     B: Send, // required by `async_trait`
 {
-    type Rejection = StatusCode;
+    type Rejection = AppError;
 
     async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
-        let headers = expect(req.headers())?;
-        let range_header = expect(headers.get("Range"))?;
-        let range_header = expect(range_header.to_str().ok())?;
-        let range = parse_range_with_defaults(range_header);
-        return Ok(Range(range));
+        let value = match req.headers().and_then(|h| h.get(header::RANGE)) {
+            Some(value) => value,
+            None => return Ok(Self(None)),
+        };
+        let value = value
+            .to_str()
+            .map_err(|_| AppError::BadRequest("invalid range".to_string()))?;
+        let range = Range::parse(value).map_err(AppError::BadRequest)?;
+        return Ok(Self(range));
     }
 }
 
-static REGEX_NUMBERS: Lazy<Regex> = Lazy::new(|| Regex::new(r"[0-9]+").unwrap());
-fn parse_range_with_defaults(header_value: &str) -> fs::Range {
-    let mut reversed_numbers: Vec<u64> = REGEX_NUMBERS
-        .find_iter(header_value)
-        .map(|e| e.as_str())
-        .map(|e| e.parse::<u64>().expect("invalid regex"))
-        .collect();
-    reversed_numbers.reverse();
-
-    let start = reversed_numbers.pop().unwrap_or(0);
-    let end = max(
-        start,
-        reversed_numbers.pop().unwrap_or(start + DEFAULT_RANGE),
-    );
-    return fs::Range::new(start, end - start);
-}
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn resolve(header: &str, file_size: u64) -> Option<(u64, u64)> {
+        let range = Range::parse(header).unwrap().unwrap();
+        let range = range.resolve(file_size, 1000)?;
+        return Some((range.start(), range.offset()));
+    }
+
+    #[test]
+    fn closed_range_is_inclusive() {
+        assert_eq!(resolve("bytes=0-99", 5000), Some((0, 100)));
+        assert_eq!(resolve("bytes=100-100", 5000), Some((100, 1)));
+    }
+
+    #[test]
+    fn end_is_cut_at_end_of_file() {
+        assert_eq!(resolve("bytes=4000-9999", 5000), Some((4000, 1000)));
+        let max = format!("bytes=0-{}", u64::MAX);
+        assert_eq!(resolve(&max, 5000), Some((0, 5000)));
+    }
+
+    #[test]
+    fn open_range_is_limited() {
+        assert_eq!(resolve("bytes=100-", 5000), Some((100, 1000)));
+        assert_eq!(resolve("bytes=4500-", 5000), Some((4500, 500)));
+        let range = Range::parse("bytes=100-").unwrap().unwrap();
+        let until_end = range.resolve(5000, u64::MAX).unwrap();
+        assert_eq!((until_end.start(), until_end.offset()), (100, 4900));
+    }
+
+    #[test]
+    fn suffix_is_the_end_of_the_file() {
+        assert_eq!(Range::parse("bytes=-500"), Ok(Some(Range::Suffix(500))));
+        assert_eq!(resolve("bytes=-500", 5000), Some((4500, 500)));
+        assert_eq!(resolve("bytes=-9999", 5000), Some((0, 5000)));
+    }
+
+    #[test]
+    fn ranges_outside_of_the_file_are_not_satisfiable() {
+        assert_eq!(resolve("bytes=5000-", 5000), None);
+        assert_eq!(resolve("bytes=6000-7000", 5000), None);
+        assert_eq!(resolve("bytes=-0", 5000), None);
+        assert_eq!(resolve("bytes=0-", 0), None);
+        assert_eq!(resolve("bytes=-10", 0), None);
+    }
+
+    #[test]
+    fn invalid_ranges_are_errors() {
+        for header in [
+            "bytes=100-50",
+            "bytes=-",
+            "bytes=a-10",
+            "bytes=0-99999999999999999999999",
+            "bytes=+1-2",
+            "bytes 0-1",
+        ] {
+            assert!(Range::parse(header).is_err(), "{}", header);
+        }
+    }
 
-fn expect<T>(value: Option<T>) -> Result<T, StatusCode> {
-    match value {
-        Some(v) => Ok(v),
-        None => Err(StatusCode::BAD_REQUEST),
+    #[test]
+    fn other_units_and_lists_are_ignored() {
+        for header in ["items=0-1", "Items=x", "bytes=0-1,5-6", "bytes=0-1, -5"] {
+            assert_eq!(Range::parse(header), Ok(None), "{}", header);
+        }
     }
 }
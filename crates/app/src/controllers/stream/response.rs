@@ -1,41 +1,74 @@
-use super::{chunk::Chunk, range::Range, whole::Whole};
+#![allow(clippy::needless_return)]
+
+use super::AppError;
+use super::{chunk::Chunk, range::Range, source::Source, whole::Whole};
 use crate::entities::File;
-use axum::{
-    body::{Bytes, Full},
-    http::Response,
-    response::IntoResponse,
-};
-use std::convert::Infallible;
+use axum::{body::StreamBody, http::Response, response::IntoResponse};
 
 use super::DEFAULT_RANGE;
 
+#[allow(clippy::upper_case_acronyms)]
 pub enum FileResponse {
     WHOLE(Whole),
     CHUNKED(Chunk),
 }
 
 impl FileResponse {
-    pub async fn new(file: &File, range: &Option<Range>) -> Result<Self, String> {
-        if file.size <= DEFAULT_RANGE {
-            return Self::whole(file).await;
-        }
-        return Self::chunked(file, range).await;
+    /// small files are sent whole without a range, ranges without an end reach `DEFAULT_RANGE`
+    /// bytes far
+    pub async fn new(
+        source: &Source<'_>,
+        file: &File,
+        range: &Option<Range>,
+    ) -> Result<Self, AppError> {
+        return match range {
+            None if file.size <= DEFAULT_RANGE => Self::whole(source, file).await,
+            None => {
+                let range = Range::From {
+                    start: 0,
+                    end: None,
+                };
+                Self::chunked(source, file, &range, DEFAULT_RANGE).await
+            }
+            Some(range) => Self::chunked(source, file, range, DEFAULT_RANGE).await,
+        };
+    }
+
+    /// serves the whole file unless a range is requested, open ranges reach until the end of the file
+    pub async fn download(
+        source: &Source<'_>,
+        file: &File,
+        range: &Option<Range>,
+    ) -> Result<Self, AppError> {
+        return match range {
+            Some(range) => Self::chunked(source, file, range, u64::MAX).await,
+            None => Self::whole(source, file).await,
+        };
     }
 
-    async fn whole(file: &File) -> Result<Self, String> {
-        let response = Self::WHOLE(Whole::new(file).await?);
+    async fn whole(source: &Source<'_>, file: &File) -> Result<Self, AppError> {
+        let response = Self::WHOLE(Whole::new(source, file).await?);
         return Ok(response);
     }
 
-    async fn chunked(file: &File, range: &Option<Range>) -> Result<Self, String> {
-        let response = Self::CHUNKED(Chunk::new(file, range).await?);
+    /// a 416 if the range is outside of the file
+    async fn chunked(
+        source: &Source<'_>,
+        file: &File,
+        range: &Range,
+        open_length: u64,
+    ) -> Result<Self, AppError> {
+        let range = range
+            .resolve(file.size, open_length)
+            .ok_or(AppError::RangeNotSatisfiable(file.size))?;
+        let response = Self::CHUNKED(Chunk::new(source, file, range).await?);
         return Ok(response);
     }
 }
 
 impl IntoResponse for FileResponse {
-    type Body = Full<Bytes>;
-    type BodyError = Infallible;
+    type Body = StreamBody<fs::FileStream>;
+    type BodyError = <Self::Body as axum::body::HttpBody>::Error;
 
     fn into_response(self) -> Response<Self::Body> {
         match self {
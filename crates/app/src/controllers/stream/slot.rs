@@ -0,0 +1,54 @@
+use super::{tracked::Tracked, AppError};
+use crate::services::{Slot, StreamService};
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, Extension, FromRequest, RequestParts},
+};
+use std::net::SocketAddr;
+
+/// a place in the limit of active streams, requests are rejected if none is left
+pub struct StreamSlot {
+    streams: StreamService,
+    slot: Slot,
+    client: String,
+}
+
+impl StreamSlot {
+    /// lists the response as active stream of `file_name` until its body is dropped
+    pub fn track<R>(self, file_name: &str, response: R) -> Tracked<R> {
+        let stream = self
+            .streams
+            .start(self.slot, self.client, file_name.to_owned());
+        Tracked::new(response, stream)
+    }
+}
+
+#[async_trait]
+impl<B> FromRequest<B> for StreamSlot
+where
+    B: Send, // required by `async_trait`
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let Extension(streams) = Extension::<StreamService>::from_request(req)
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+
+        let client = match req.extensions() {
+            Some(e) => e.get::<ConnectInfo<SocketAddr>>().map(|c| c.0.to_string()),
+            None => None,
+        };
+
+        let slot = streams.reserve().ok_or_else(|| {
+            let message = "too many active streams, try again later".to_string();
+            AppError::Busy(message, streams.retry_after())
+        })?;
+
+        Ok(Self {
+            slot,
+            client: client.unwrap_or_else(|| "unknown".to_string()),
+            streams,
+        })
+    }
+}
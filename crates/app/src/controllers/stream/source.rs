@@ -0,0 +1,29 @@
+use crate::entities::File;
+use fs::{FileStream, Sendfile, Vfs};
+
+/// where the content of files is read from
+pub struct Source<'a> {
+    vfs: &'a dyn Vfs,
+    /// only on connections that support it, see `server::serve`
+    sendfile: Option<Sendfile>,
+}
+
+impl<'a> Source<'a> {
+    pub fn new(vfs: &'a dyn Vfs, sendfile: Option<Sendfile>) -> Self {
+        Self { vfs, sendfile }
+    }
+
+    /// ranges of files on the disk are sent with sendfile if the connection supports it, the
+    /// others are streamed from the vfs
+    pub async fn open_range(&self, file: &File, range: &fs::Range) -> std::io::Result<FileStream> {
+        let path = fs::decode_path(&file.path);
+        match (&self.sendfile, self.vfs.local_path(&path)) {
+            (Some(sendfile), Some(local)) => {
+                fs::File::new(local, file.size)
+                    .sendfile(range, sendfile)
+                    .await
+            }
+            _ => self.vfs.open_range(&path, range).await,
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use crate::services::ActiveStream;
+use axum::{
+    body::{Bytes, HttpBody},
+    http::{HeaderMap, Response},
+    response::IntoResponse,
+};
+use futures::{ready, Future};
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time::{sleep_until, Instant, Sleep};
+
+/// a response whose body is listed as active stream and throttled to the rate limit
+pub struct Tracked<R> {
+    response: R,
+    stream: ActiveStream,
+}
+
+impl<R> Tracked<R> {
+    pub fn new(response: R, stream: ActiveStream) -> Self {
+        Self { response, stream }
+    }
+}
+
+impl<R> IntoResponse for Tracked<R>
+where
+    R: IntoResponse,
+    R::Body: Unpin,
+{
+    type Body = TrackedBody<R::Body>;
+    type BodyError = R::BodyError;
+
+    fn into_response(self) -> Response<Self::Body> {
+        let stream = self.stream;
+        self.response.into_response().map(|body| TrackedBody {
+            body,
+            stream,
+            started: Instant::now(),
+            sent: 0,
+            delay: None,
+        })
+    }
+}
+
+pub struct TrackedBody<B> {
+    body: B,
+    stream: ActiveStream,
+    started: Instant,
+    sent: u64,
+    /// waits before the next chunk is sent if the client is faster than the rate limit
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl<B> TrackedBody<B> {
+    fn throttle(&mut self) {
+        let rate_limit = match self.stream.rate_limit() {
+            Some(r) => r,
+            None => return,
+        };
+
+        let allowed_at =
+            self.started + Duration::from_secs_f64(self.sent as f64 / rate_limit as f64);
+        if allowed_at > Instant::now() {
+            self.delay = Some(Box::pin(sleep_until(allowed_at)));
+        }
+    }
+}
+
+impl<B> HttpBody for TrackedBody<B>
+where
+    B: HttpBody<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.get_mut();
+
+        if let Some(delay) = this.delay.as_mut() {
+            ready!(delay.as_mut().poll(cx));
+            this.delay = None;
+        }
+
+        let data = ready!(Pin::new(&mut this.body).poll_data(cx));
+        if let Some(Ok(bytes)) = &data {
+            this.sent += bytes.len() as u64;
+            this.stream.add_sent(bytes.len() as u64);
+            this.throttle();
+        }
+        Poll::Ready(data)
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.get_mut().body).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{config::StreamingConfig, services::StreamService};
+    use axum::body::StreamBody;
+    use futures::stream;
+
+    fn tracked(
+        rate_limit: u64,
+        chunks: usize,
+    ) -> (
+        StreamService,
+        TrackedBody<impl HttpBody<Data = Bytes, Error = axum::Error> + Unpin>,
+    ) {
+        let streams = StreamService::new(&StreamingConfig {
+            rate_limit,
+            ..Default::default()
+        });
+        let active = streams.start(streams.reserve().unwrap(), "client".into(), "file".into());
+        let chunks: Vec<Result<Bytes, std::io::Error>> =
+            (0..chunks).map(|_| Ok(Bytes::from(vec![0; 100]))).collect();
+        let response = StreamBody::new(stream::iter(chunks));
+
+        let body = Tracked::new(response, active).into_response().into_body();
+        (streams, body)
+    }
+
+    async fn read_all<B: HttpBody<Data = Bytes> + Unpin>(mut body: B) -> usize {
+        let mut len = 0;
+        while let Some(Ok(bytes)) = body.data().await {
+            len += bytes.len();
+        }
+        len
+    }
+
+    #[tokio::test]
+    async fn counts_sent_bytes() {
+        let (streams, mut body) = tracked(0, 2);
+        body.data().await;
+        assert_eq!(streams.active()[0].sent, 100);
+
+        read_all(body).await;
+        assert!(streams.active().is_empty());
+    }
+
+    #[tokio::test]
+    async fn throttles_to_rate_limit() {
+        let (_streams, body) = tracked(1000, 3);
+        let started = std::time::Instant::now();
+
+        assert_eq!(read_all(body).await, 300);
+        assert!(started.elapsed() >= Duration::from_millis(200));
+    }
+}
@@ -0,0 +1,36 @@
+use crate::services::{TranscodeError, TranscodeService, TranscodeStream};
+use axum::{
+    body::StreamBody,
+    http::{Response, StatusCode},
+    response::IntoResponse,
+};
+
+pub struct Transcode {
+    mime: String,
+    output: TranscodeStream,
+}
+
+impl Transcode {
+    pub async fn new(
+        transcoder: &TranscodeService,
+        file: &crate::entities::File,
+    ) -> Result<Self, TranscodeError> {
+        Ok(Self {
+            mime: transcoder.mime(),
+            output: transcoder.transcode(file).await?,
+        })
+    }
+}
+
+impl IntoResponse for Transcode {
+    type Body = StreamBody<TranscodeStream>;
+    type BodyError = <Self::Body as axum::body::HttpBody>::Error;
+
+    fn into_response(self) -> Response<Self::Body> {
+        Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", self.mime)
+            .body(StreamBody::new(self.output))
+            .expect("valid streaming body")
+    }
+}
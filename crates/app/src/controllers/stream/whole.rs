@@ -1,39 +1,40 @@
+use super::source::Source;
 use crate::entities::File;
 use axum::{
-    body::{Bytes, Full},
+    body::StreamBody,
     http::{Response, StatusCode},
     response::IntoResponse,
 };
-use std::convert::Infallible;
 
 pub struct Whole {
     mime: String,
-    content: Vec<u8>,
+    size: u64,
+    content: fs::FileStream,
 }
 
 impl Whole {
-    pub async fn new(file: &File) -> Result<Self, String> {
-        let fs_file = fs::File::new(file.path.to_owned(), file.size);
-
+    pub async fn new(source: &Source<'_>, file: &File) -> std::io::Result<Self> {
         Ok(Self {
             mime: file.mime.to_string(),
-            content: fs_file
-                .chunk(&fs::Range::new(0, file.size))
-                .await
-                .map_err(|e| e.to_string())?,
+            size: file.size,
+            content: source
+                .open_range(file, &fs::Range::new(0, file.size))
+                .await?,
         })
     }
 }
 
 impl IntoResponse for Whole {
-    type Body = Full<Bytes>;
-    type BodyError = Infallible;
+    type Body = StreamBody<fs::FileStream>;
+    type BodyError = <Self::Body as axum::body::HttpBody>::Error;
 
     fn into_response(self) -> Response<Self::Body> {
         Response::builder()
             .status(StatusCode::OK)
             .header("Content-Type", self.mime)
-            .body(Full::from(self.content))
+            .header("Accept-Ranges", "bytes")
+            .header("Content-Length", self.size)
+            .body(StreamBody::new(self.content))
             .expect("valid streaming body")
     }
 }
@@ -1,8 +1,14 @@
-use super::render;
-use crate::repositories::Videos;
+use super::{
+    auth::Visible,
+    encode_path_segment,
+    error::AppError,
+    list::{ListQuery, ListView},
+    render,
+};
+use crate::{repositories::Videos, services::TranscodeService};
 use askama::Template;
 use axum::{
-    extract::{Extension, Path},
+    extract::{Extension, Path, Query},
     response::Html,
     routing::get,
     Router,
@@ -17,12 +23,19 @@ pub fn setup() -> Router {
 #[derive(Template)]
 #[template(path = "views/videos/list.html")]
 struct VideosTemplate {
-    videos: Vec<crate::entities::File>,
+    list: ListView,
 }
 
-async fn list_all(Extension(videos): Extension<Videos>) -> Result<Html<String>, String> {
-    let videos = videos.find_all().await?;
-    let template = render(VideosTemplate { videos })?;
+async fn list_all(
+    Extension(videos): Extension<Videos>,
+    Query(query): Query<ListQuery>,
+    visible: Visible,
+) -> Result<Html<String>, AppError> {
+    let request = query.page()?;
+    let page = videos.find_page(&query.filter(visible), request).await?;
+    let template = render(VideosTemplate {
+        list: ListView::new(page, request, &query),
+    })?;
     Ok(Html::from(template))
 }
 
@@ -30,16 +43,26 @@ async fn list_all(Extension(videos): Extension<Videos>) -> Result<Html<String>,
 #[template(path = "views/videos/player.html")]
 struct PlayerTemplate {
     video: crate::entities::File,
+    /// the name in the urls of the streams
+    member: String,
+    transcode_mime: String,
 }
 
 pub async fn player(
     Extension(videos): Extension<Videos>,
+    Extension(transcoder): Extension<TranscodeService>,
     Path(id): Path<u64>,
-) -> Result<Html<String>, String> {
+    visible: Visible,
+) -> Result<Html<String>, AppError> {
     let video = videos
         .find_by_id(id)
         .await?
-        .ok_or_else(|| "id not found".to_string())?;
-    let template = render(PlayerTemplate { video })?;
+        .filter(|f| visible.contains(&f.path))
+        .ok_or_else(|| AppError::NotFound(format!("{} {} does not exist", "video", id)))?;
+    let template = render(PlayerTemplate {
+        member: encode_path_segment(&video.group_member_name),
+        video,
+        transcode_mime: transcoder.mime(),
+    })?;
     Ok(Html::from(template))
 }
@@ -0,0 +1,333 @@
+//! the catalog is stored in sqlite or postgres, the backend is chosen by the scheme of the url
+//! and has to be enabled by the cargo feature of the same name
+
+#![allow(clippy::needless_return)]
+
+use sea_orm::{DatabaseConnection, DbBackend, Statement, Value};
+use std::path::PathBuf;
+
+pub async fn connect(url: &str) -> Result<DatabaseConnection, String> {
+    open(url).await.map(|(db, _)| db)
+}
+
+/// like `connect`, the pool closes the connections of the returned `DatabaseConnection`
+pub async fn open(url: &str) -> Result<(DatabaseConnection, Pool), String> {
+    open_with(url, None).await
+}
+
+/// like `open` with at most `max_connections` connections instead of the default of the pool
+async fn open_with(
+    url: &str,
+    max_connections: Option<u32>,
+) -> Result<(DatabaseConnection, Pool), String> {
+    match check_backend(url)? {
+        #[cfg(feature = "sqlite")]
+        DbBackend::Sqlite => {
+            use sea_orm::SqlxSqliteConnector;
+            use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+
+            let options: SqliteConnectOptions = url.parse().map_err(|e| format!("{}", e))?;
+            let mut pool = SqlitePoolOptions::new();
+            // every connection to an in-memory database has its own database
+            if sqlite_path(url).is_none() {
+                pool = pool.max_connections(1);
+            } else if let Some(max) = max_connections {
+                pool = pool.max_connections(max);
+            }
+            let pool = pool
+                .connect_with(options)
+                .await
+                .map_err(|e| e.to_string())?;
+            let db = SqlxSqliteConnector::from_sqlx_sqlite_pool(pool.clone());
+            Ok((db, Pool::Sqlite(pool)))
+        }
+        #[cfg(feature = "postgres")]
+        DbBackend::Postgres => {
+            use sea_orm::SqlxPostgresConnector;
+            use sqlx::postgres::PgPoolOptions;
+
+            let mut pool = PgPoolOptions::new();
+            if let Some(max) = max_connections {
+                pool = pool.max_connections(max);
+            }
+            let pool = pool.connect(url).await.map_err(|e| e.to_string())?;
+            let db = SqlxPostgresConnector::from_sqlx_postgres_pool(pool.clone());
+            Ok((db, Pool::Postgres(pool)))
+        }
+        backend => Err(format!("unsupported database: {:?}", backend)),
+    }
+}
+
+/// the connections of a `DatabaseConnection`, sea-orm can not close them itself
+#[derive(Clone)]
+pub enum Pool {
+    #[cfg(feature = "sqlite")]
+    Sqlite(sqlx::SqlitePool),
+    #[cfg(feature = "postgres")]
+    Postgres(sqlx::PgPool),
+}
+
+impl Pool {
+    /// waits until the connections in use are returned, then closes all of them
+    pub async fn close(&self) {
+        match self {
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(pool) => pool.close().await,
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(pool) => pool.close().await,
+        }
+    }
+}
+
+fn check_backend(url: &str) -> Result<DbBackend, String> {
+    let (backend, feature, enabled) = match url.split(':').next().unwrap_or_default() {
+        "sqlite" => (DbBackend::Sqlite, "sqlite", cfg!(feature = "sqlite")),
+        "postgres" | "postgresql" => (DbBackend::Postgres, "postgres", cfg!(feature = "postgres")),
+        scheme => return Err(format!("unsupported database: '{}'", scheme)),
+    };
+    if !enabled {
+        return Err(format!(
+            "support for {} is not enabled, build with the cargo feature \"{}\"",
+            feature, feature
+        ));
+    }
+    Ok(backend)
+}
+
+/// the file of a sqlite database and its journals, none for in-memory databases and other backends
+pub fn sqlite_files(url: &str) -> Vec<PathBuf> {
+    let path = match sqlite_path(url) {
+        Some(path) => path,
+        None => return Vec::new(),
+    };
+    return ["", "-journal", "-wal", "-shm"]
+        .iter()
+        .map(|suffix| PathBuf::from(format!("{}{}", path, suffix)))
+        .collect();
+}
+
+/// the path of the file of a sqlite url, `None` for in-memory databases
+fn sqlite_path(url: &str) -> Option<&str> {
+    let database = url.strip_prefix("sqlite:")?;
+    let database = database.strip_prefix("//").unwrap_or(database);
+    let (path, options) = database.split_once('?').unwrap_or((database, ""));
+    let in_memory = options.split('&').any(|o| o == "mode=memory");
+    return (!path.is_empty() && path != ":memory:" && !in_memory).then_some(path);
+}
+
+/// raw sql with "?" as placeholders, which are numbered for postgres ("$1", "$2", ...)
+///
+/// a "?" in a string literal or a quoted identifier is not a placeholder
+pub fn statement(backend: DbBackend, sql: &str, values: Vec<Value>) -> Statement {
+    let sql = match backend {
+        DbBackend::Postgres => {
+            let mut numbered = String::with_capacity(sql.len());
+            let mut quote = None;
+            let mut index = 0;
+            for c in sql.chars() {
+                match (quote, c) {
+                    // a doubled quote inside of quotes ends and starts them again
+                    (None, '\'' | '"') => quote = Some(c),
+                    (Some(q), _) if q == c => quote = None,
+                    (None, '?') => {
+                        index += 1;
+                        numbered.push_str(&format!("${}", index));
+                        continue;
+                    }
+                    _ => {}
+                }
+                numbered.push(c);
+            }
+            numbered
+        }
+        _ => sql.to_string(),
+    };
+    Statement::from_sql_and_values(backend, &sql, values)
+}
+
+/// an empty database for tests, "NETFLEX_TEST_DATABASE_URL" selects a postgres server
+///
+/// every call creates a new database on the server, so tests can run in parallel. It is
+/// dropped with the returned `TestDatabase`
+#[cfg(test)]
+pub async fn test_database() -> TestDatabase {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    let url = match std::env::var("NETFLEX_TEST_DATABASE_URL") {
+        Ok(url) if url.starts_with("postgres") => url,
+        _ => {
+            let (db, _) = open("sqlite::memory:").await.unwrap();
+            return TestDatabase { db, server: None };
+        }
+    };
+
+    let name = format!(
+        "netflex_test_{}_{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    );
+    execute_on_server(
+        &url,
+        &[
+            format!("DROP DATABASE IF EXISTS {}", name),
+            format!("CREATE DATABASE {}", name),
+        ],
+    )
+    .await;
+
+    let (server_url, _) = url.rsplit_once('/').unwrap();
+    // the server allows 100 connections, tests run in parallel
+    let (db, _) = open_with(&format!("{}/{}", server_url, name), Some(1))
+        .await
+        .unwrap();
+    return TestDatabase {
+        db,
+        server: Some((url, name)),
+    };
+}
+
+/// runs the statements with a connection of its own that is closed afterwards
+#[cfg(test)]
+async fn execute_on_server(url: &str, statements: &[String]) {
+    use sea_orm::ConnectionTrait;
+
+    let (server, pool) = open_with(url, Some(1)).await.unwrap();
+    for sql in statements {
+        server
+            .execute(Statement::from_string(DbBackend::Postgres, sql.to_owned()))
+            .await
+            .unwrap();
+    }
+    pool.close().await;
+}
+
+/// a database of `test_database`, the ones on a postgres server are dropped with it
+#[cfg(test)]
+pub struct TestDatabase {
+    db: DatabaseConnection,
+    /// the url of the server and the name of the database
+    server: Option<(String, String)>,
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TestDatabase {
+    type Target = DatabaseConnection;
+
+    fn deref(&self) -> &DatabaseConnection {
+        return &self.db;
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestDatabase {
+    fn drop(&mut self) {
+        let (url, name) = match self.server.take() {
+            Some(server) => server,
+            None => return,
+        };
+        // drop can not wait for a future on the runtime of the test, "FORCE" closes the
+        // connections that are still open
+        let drop_database = move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            let sql = format!("DROP DATABASE IF EXISTS {} WITH (FORCE)", name);
+            runtime.block_on(execute_on_server(&url, &[sql]));
+        };
+        if std::thread::spawn(drop_database).join().is_err() && !std::thread::panicking() {
+            panic!("the test database could not be dropped");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn files_of_sqlite_databases() {
+        assert_eq!(
+            sqlite_files("sqlite://data/db.sqlite?mode=rwc"),
+            [
+                PathBuf::from("data/db.sqlite"),
+                PathBuf::from("data/db.sqlite-journal"),
+                PathBuf::from("data/db.sqlite-wal"),
+                PathBuf::from("data/db.sqlite-shm"),
+            ]
+        );
+        assert_eq!(
+            sqlite_files("sqlite:///db.sqlite")[0],
+            PathBuf::from("/db.sqlite")
+        );
+        for url in [
+            "sqlite::memory:",
+            "sqlite://db?mode=memory",
+            "postgres://localhost/db",
+        ] {
+            assert!(sqlite_files(url).is_empty(), "{}", url);
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn file_databases_have_several_connections() {
+        let dir = testing::TempDir::new("pool");
+        let url = format!("sqlite://{}?mode=rwc", dir.join("db.sqlite").display());
+        // the match is needed if postgres is enabled as well
+        #[allow(clippy::infallible_destructuring_match)]
+        let pool = match open(&url).await.unwrap().1 {
+            Pool::Sqlite(pool) => pool,
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(_) => unreachable!(),
+        };
+        // a single connection would wait forever for the second
+        let first = pool.acquire().await.unwrap();
+        let second = pool.acquire().await.unwrap();
+        drop((first, second));
+        pool.close().await;
+    }
+
+    #[test]
+    fn placeholders_are_numbered_for_postgres() {
+        let values = vec![1.into(), 2.into()];
+        let sql = statement(DbBackend::Postgres, "a = ? AND b = ?", values.clone()).sql;
+        assert_eq!(sql, "a = $1 AND b = $2");
+
+        let sql = statement(DbBackend::Sqlite, "a = ? AND b = ?", values).sql;
+        assert_eq!(sql, "a = ? AND b = ?");
+    }
+
+    #[test]
+    fn quoted_question_marks_are_not_numbered() {
+        let sql = "a = '?' AND \"b?\" = ? AND c = 'it''s ?' AND d = ?";
+        assert_eq!(
+            statement(DbBackend::Postgres, sql, Vec::new()).sql,
+            "a = '?' AND \"b?\" = $1 AND c = 'it''s ?' AND d = $2"
+        );
+    }
+
+    #[test]
+    fn unknown_scheme_is_error() {
+        assert!(check_backend("mysql://localhost/netflex").is_err());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_is_enabled() {
+        assert_eq!(check_backend("sqlite://db.sqlite"), Ok(DbBackend::Sqlite));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn closed_pool_refuses_queries() {
+        use sea_orm::ConnectionTrait;
+
+        let (db, pool) = open("sqlite::memory:").await.unwrap();
+        let select = || Statement::from_string(DbBackend::Sqlite, "SELECT 1".to_string());
+        assert!(db.execute(select()).await.is_ok());
+        pool.close().await;
+        assert!(db.execute(select()).await.is_err());
+    }
+}
@@ -1,5 +1,6 @@
-use sea_orm::{entity::prelude::*, ConnectionTrait, DbConn, DbErr, ExecResult, Schema};
+use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "files")]
@@ -13,6 +14,8 @@ pub struct Model {
     pub size: i64,
     pub group_id: String,
     pub group_member_name: String,
+    /// seconds since the unix epoch when a scan found the file first
+    pub added: i64,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter)]
@@ -26,16 +29,8 @@ impl RelationTrait for Relation {
 
 impl ActiveModelBehavior for ActiveModel {}
 
-pub(super) async fn setup(db: &DbConn) -> Result<ExecResult, DbErr> {
-    let statement = Schema::create_table_from_entity(Entity)
-        .if_not_exists()
-        .to_owned();
-    let builder = db.get_database_backend();
-    db.execute(builder.build(&statement)).await
-}
-
 // helper because u64 is not supported by sqlite
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct File {
     pub id: u64,
     pub name: String,
@@ -44,6 +39,36 @@ pub struct File {
     pub size: u64,
     pub group_id: String,
     pub group_member_name: String,
+    pub added: u64,
+}
+
+/// mimes that browsers can play without transcoding
+const BROWSER_PLAYABLE_MIMES: &[&str] = &[
+    "video/mp4",
+    "video/webm",
+    "video/ogg",
+    "application/x-mpegURL",
+    "vnd.apple.mpegURL",
+    "audio/mpeg",
+    "audio/mp4",
+    "audio/aac",
+    "audio/ogg",
+    "audio/webm",
+    "audio/wav",
+    "audio/flac",
+];
+
+impl File {
+    /// the name as it is shown to people, bytes that are not utf-8 are replaced
+    pub fn display_name(&self) -> String {
+        fs::decode_path(&self.group_member_name)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    pub fn is_browser_playable(&self) -> bool {
+        BROWSER_PLAYABLE_MIMES.contains(&self.mime.as_str())
+    }
 }
 
 impl TryFrom<File> for Model {
@@ -64,6 +89,10 @@ impl TryFrom<File> for Model {
                 .map_err(|_| "file.size should not be negative or too big".to_string())?,
             group_id: value.group_id,
             group_member_name: value.group_member_name,
+            added: value
+                .added
+                .try_into()
+                .map_err(|_| "file.added should not be too big".to_string())?,
         })
     }
 }
@@ -76,8 +105,9 @@ impl From<Model> for File {
             path: value.path,
             mime: value.mime,
             size: value.size.try_into().expect("should never be negative"),
-            group_id: value.group_id.try_into().expect("should never be negative"),
+            group_id: value.group_id,
             group_member_name: value.group_member_name,
+            added: value.added.try_into().unwrap_or_default(),
         }
     }
 }
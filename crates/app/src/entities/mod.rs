@@ -1,10 +1,5 @@
-use axum::Router;
-use sea_orm::{DbConn, DbErr};
-
 pub mod file;
 pub use file::File;
-
-pub async fn setup(router: Router, db: &DbConn) -> Result<Router, DbErr> {
-    file::setup(db).await?;
-    Ok(router)
-}
+pub mod search;
+pub mod user;
+pub use user::{Role, User};
@@ -0,0 +1,2 @@
+/// fts5 index of the files, the rowid of an entry is the id of its file
+pub const TABLE: &str = "files_search";
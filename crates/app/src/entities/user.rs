@@ -0,0 +1,84 @@
+use sea_orm::entity::prelude::*;
+use serde::Deserialize;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "users")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    pub name: String,
+    pub password_hash: String,
+    pub role: String,
+    /// seconds since the unix epoch
+    pub created_at: i64,
+    /// sessions of an older epoch are not valid anymore
+    pub session_epoch: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// an account without its password hash, added to the requests of logged in users
+#[derive(Clone, Debug, PartialEq)]
+pub struct User {
+    pub id: u64,
+    pub name: String,
+    pub role: Role,
+    pub session_epoch: u64,
+}
+
+/// what an account may do, every role may do everything the roles before it may do
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// browses, streams and downloads the files of the libraries for guests
+    Guest,
+    /// browses, streams and downloads the files of every library
+    Member,
+    /// changes the settings, scans the files and manages the accounts
+    Admin,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Admin, Role::Member, Role::Guest];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Guest => "guest",
+            Role::Member => "member",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Role::ALL
+            .into_iter()
+            .find(|r| r.as_str() == value)
+            .ok_or_else(|| format!("unknown role: {}", value))
+    }
+}
+
+impl From<Model> for User {
+    fn from(value: Model) -> Self {
+        User {
+            id: value.id.try_into().expect("should never be negative"),
+            name: value.name,
+            // an unknown role gets the least rights
+            role: value.role.parse().unwrap_or(Role::Guest),
+            session_epoch: value.session_epoch.try_into().unwrap_or_default(),
+        }
+    }
+}
@@ -1,19 +1,50 @@
+#[cfg(not(any(feature = "sqlite", feature = "postgres")))]
+compile_error!("at least one database backend has to be enabled: \"sqlite\" or \"postgres\"");
+
 use axum::Router;
-use sea_orm::Database;
 
+pub mod backup;
+pub mod config;
 mod controllers;
+pub mod database;
 mod entities;
+pub mod migrations;
 mod repositories;
+pub mod server;
 mod services;
 
-pub async fn app(database_url: &str) -> anyhow::Result<Router> {
-    let database = Database::connect(database_url).await?;
+pub use config::Config;
+pub use services::Shutdown;
+
+pub async fn app_with_config(database_url: &str, config: &Config) -> anyhow::Result<Router> {
+    let (app, _) = app_with_shutdown(database_url, config).await?;
+    Ok(app)
+}
+
+/// like `app_with_config`, the `Shutdown` is triggered by "/settings/shutdown"
+pub async fn app_with_shutdown(
+    database_url: &str,
+    config: &Config,
+) -> anyhow::Result<(Router, Shutdown)> {
+    let (database, pool) = database::open(database_url)
+        .await
+        .map_err(anyhow::Error::msg)?;
+    let shutdown = Shutdown::new(pool);
+    migrations::migrate(&database)
+        .await
+        .map_err(anyhow::Error::msg)?;
 
     let mut app = Router::new();
     app = controllers::setup(app);
     app = repositories::setup(app, &database);
-    app = services::setup(app, &database);
-    app = entities::setup(app, &database).await?;
+    // the database, the config and the session key are never part of a library, even if they
+    // are inside of one
+    let mut protected = database::sqlite_files(database_url);
+    protected.extend(config.path.clone());
+    protected.push(config.auth.key_file.clone().into());
+    app = services::setup(app, &database, config, protected, &shutdown)
+        .await
+        .map_err(anyhow::Error::msg)?;
 
-    Ok(app)
+    Ok((app, shutdown))
 }
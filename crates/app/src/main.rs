@@ -1,25 +1,225 @@
-use axum::Server;
+use anyhow::bail;
+use app::{backup, migrations};
+use sea_orm::ConnectionTrait;
 use std::net::SocketAddr;
+use std::path::Path;
 use std::str::FromStr;
+use std::time::Duration;
+use tokio::net::TcpListener;
 
 const ADDRESS: &str = "127.0.0.1:8080";
-const DATABASE_URL: &str = "sqlite://db.sqlite";
+const CONFIG_PATH: &str = "netflex.toml";
+const MIGRATE_USAGE: &str = "usage: app migrate [status | --dry-run]";
+const BACKUP_USAGE: &str = "usage: app backup <target.sqlite>";
+const EXPORT_USAGE: &str = "usage: app export <file.json>";
+const IMPORT_USAGE: &str = "usage: app import <file.json> [--map <from>=<to>]...";
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let config = app::Config::load(CONFIG_PATH)?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("migrate") => return migrate(&config.database.url, &args[1..]).await,
+        Some("backup") => return backup(&config.database.url, &args[1..]).await,
+        Some("export") => return export(&config, &args[1..]).await,
+        Some("import") => return import(&config, &args[1..]).await,
+        _ => {}
+    }
+
     let address = SocketAddr::from_str(ADDRESS)?;
-    let app = app::app(DATABASE_URL).await?;
+    let (app, shutdown) = app::app_with_shutdown(&config.database.url, &config).await?;
+    tokio::spawn(shutdown_on_signal(shutdown.clone()));
 
     print_info();
 
-    Server::bind(&address)
-        .serve(app.into_make_service())
-        .await?;
+    // no new connections are accepted after the shutdown was triggered
+    let listener = TcpListener::bind(address).await?;
+    let server = app::server::serve(listener, app, shutdown.triggered());
+    let drain_timeout = Duration::from_secs(config.shutdown.drain_timeout);
+    let drain = async {
+        shutdown.triggered().await;
+        tokio::time::sleep(drain_timeout).await;
+    };
+    tokio::select! {
+        result = server => result?,
+        _ = drain => println!("aborting the responses that are still active"),
+    }
 
+    shutdown.finish().await;
     Ok(())
 }
 
+async fn shutdown_on_signal(shutdown: app::Shutdown) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate()).expect("signal handler");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {},
+            _ = terminate.recv() => {},
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+
+    println!("shutting down");
+    shutdown.trigger();
+}
+
 fn print_info() {
-    println!("");
+    println!();
     println!("Server: http://{}", ADDRESS);
 }
+
+/// "migrate" applies the pending migrations, "status" lists all and "--dry-run" prints the pending ones
+async fn migrate(database_url: &str, args: &[String]) -> anyhow::Result<()> {
+    let db = app::database::connect(database_url)
+        .await
+        .map_err(anyhow::Error::msg)?;
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        [] => {
+            let applied = migrations::migrate(&db).await.map_err(anyhow::Error::msg)?;
+            for migration in &applied {
+                println!("applied {:04} {}", migration.version, migration.name);
+            }
+            println!("{} migrations applied", applied.len());
+        }
+        ["status"] => {
+            let status = migrations::status(&db).await.map_err(anyhow::Error::msg)?;
+            println!(
+                "schema version {} (latest known: {})",
+                status.version(),
+                migrations::latest_version()
+            );
+            for migration in &status.applied {
+                println!(
+                    "applied {:04} {} at {}",
+                    migration.version, migration.name, migration.applied_at
+                );
+            }
+            for migration in &status.pending {
+                println!("pending {:04} {}", migration.version, migration.name);
+            }
+            if status.is_newer_than_binary() {
+                println!("the database is newer than this program");
+            }
+        }
+        ["--dry-run"] => {
+            let pending = migrations::pending(&db).await.map_err(anyhow::Error::msg)?;
+            for migration in &pending {
+                println!("-- {:04} {}", migration.version, migration.name);
+                let sql = migration.sql(db.get_database_backend());
+                for statement in migrations::statements(sql) {
+                    println!("{};", statement);
+                }
+                println!();
+            }
+            println!("{} migrations pending", pending.len());
+        }
+        _ => bail!(MIGRATE_USAGE),
+    }
+    Ok(())
+}
+
+/// snapshot of the sqlite database, works while the server is running
+async fn backup(database_url: &str, args: &[String]) -> anyhow::Result<()> {
+    let target = match args {
+        [target] => Path::new(target),
+        _ => bail!(BACKUP_USAGE),
+    };
+    backup::backup(database_url, target)
+        .await
+        .map_err(anyhow::Error::msg)?;
+    println!("database written to '{}'", target.display());
+    Ok(())
+}
+
+/// writes the libraries, the accounts and the catalog as json
+async fn export(config: &app::Config, args: &[String]) -> anyhow::Result<()> {
+    let target = match args {
+        [target] => Path::new(target),
+        _ => bail!(EXPORT_USAGE),
+    };
+    let db = connect_migrated(&config.database.url).await?;
+    let export = backup::export(&db, &config.libraries)
+        .await
+        .map_err(anyhow::Error::msg)?;
+    std::fs::write(target, serde_json::to_vec_pretty(&export)?)?;
+    println!(
+        "{} libraries, {} users and {} files exported to '{}'",
+        export.libraries.len(),
+        export.users.len(),
+        export.files.len(),
+        target.display()
+    );
+    Ok(())
+}
+
+/// reads a catalog written by "export", "--map" replaces the start of the paths
+/// libraries that are not configured yet are appended to the config file
+async fn import(config: &app::Config, args: &[String]) -> anyhow::Result<()> {
+    let (source, options) = match args.split_first() {
+        Some((source, options)) if !source.starts_with("--") => (source, options),
+        _ => bail!(IMPORT_USAGE),
+    };
+    let mut mappings = Vec::new();
+    for option in options.chunks(2) {
+        match option {
+            [flag, mapping] if flag == "--map" => {
+                mappings.push(mapping.parse().map_err(anyhow::Error::msg)?)
+            }
+            _ => bail!(IMPORT_USAGE),
+        }
+    }
+
+    let export = serde_json::from_slice(&std::fs::read(source)?)?;
+    let db = connect_migrated(&config.database.url).await?;
+    let imported = backup::import(&db, export, &mappings)
+        .await
+        .map_err(anyhow::Error::msg)?;
+    let libraries: Vec<_> = imported
+        .libraries
+        .into_iter()
+        .filter(|l| !config.libraries.iter().any(|c| c.path == l.path))
+        .collect();
+    add_libraries(CONFIG_PATH, &libraries)?;
+    println!(
+        "{} libraries, {} users and {} files imported",
+        libraries.len(),
+        imported.users,
+        imported.files
+    );
+    Ok(())
+}
+
+/// appends the libraries as `[[libraries]]` tables to the config file
+fn add_libraries(path: &str, libraries: &[app::config::LibraryConfig]) -> anyhow::Result<()> {
+    #[derive(serde::Serialize)]
+    struct Libraries<'a> {
+        libraries: &'a [app::config::LibraryConfig],
+    }
+
+    if libraries.is_empty() {
+        return Ok(());
+    }
+    let mut content = std::fs::read_to_string(path).unwrap_or_default();
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push('\n');
+    content.push_str(&toml::to_string(&Libraries { libraries })?);
+    // the file has to stay loadable, e.g. a `libraries = []` line can not be extended
+    toml::from_str::<app::Config>(&content)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+async fn connect_migrated(database_url: &str) -> anyhow::Result<sea_orm::DatabaseConnection> {
+    let db = app::database::connect(database_url)
+        .await
+        .map_err(anyhow::Error::msg)?;
+    migrations::migrate(&db).await.map_err(anyhow::Error::msg)?;
+    Ok(db)
+}
@@ -0,0 +1,309 @@
+//! numbered changes of the database schema, applied versions are stored in `schema_migrations`
+//!
+//! a migration must never change after it was released, add a new one instead. every migration
+//! exists for each backend in "migrations/sqlite" and "migrations/postgres"
+
+#![allow(clippy::needless_return)]
+
+use crate::database;
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, FromQueryResult, Statement};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    sqlite: &'static str,
+    postgres: &'static str,
+}
+
+impl Migration {
+    pub fn sql(&self, backend: DbBackend) -> &'static str {
+        match backend {
+            DbBackend::Postgres => self.postgres,
+            _ => self.sqlite,
+        }
+    }
+}
+
+macro_rules! migration {
+    ($version:expr, $name:expr, $file:expr) => {
+        Migration {
+            version: $version,
+            name: $name,
+            sqlite: include_str!(concat!("../../migrations/sqlite/", $file)),
+            postgres: include_str!(concat!("../../migrations/postgres/", $file)),
+        }
+    };
+}
+
+/// ordered by version
+pub const MIGRATIONS: &[Migration] = &[
+    migration!(1, "create files", "0001_create_files.sql"),
+    migration!(2, "create files_search", "0002_create_files_search.sql"),
+    migration!(3, "create file indexes", "0003_create_file_indexes.sql"),
+    migration!(4, "create users", "0004_create_users.sql"),
+    migration!(5, "add user roles", "0005_add_user_roles.sql"),
+    migration!(6, "add file added", "0006_add_file_added.sql"),
+];
+
+const CREATE_MIGRATIONS_TABLE: &str = "CREATE TABLE IF NOT EXISTS schema_migrations (\
+    version bigint NOT NULL PRIMARY KEY, \
+    name text NOT NULL, \
+    applied_at bigint NOT NULL)";
+
+#[derive(Debug, FromQueryResult)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub name: String,
+    /// seconds since the unix epoch
+    pub applied_at: i64,
+}
+
+pub struct MigrationStatus {
+    pub applied: Vec<AppliedMigration>,
+    pub pending: Vec<&'static Migration>,
+}
+
+impl MigrationStatus {
+    /// 0 if no migration was applied
+    pub fn version(&self) -> i64 {
+        self.applied.iter().map(|m| m.version).max().unwrap_or(0)
+    }
+
+    /// the database was migrated by a newer version of this program
+    pub fn is_newer_than_binary(&self) -> bool {
+        self.version() > latest_version()
+    }
+}
+
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version as i64).unwrap_or(0)
+}
+
+/// does not change the database
+pub async fn status(db: &DatabaseConnection) -> Result<MigrationStatus, String> {
+    // the table does not exist before the first migration
+    let applied = match table_exists(db, "schema_migrations").await? {
+        true => {
+            let statement = Statement::from_string(
+                db.get_database_backend(),
+                "SELECT version, name, applied_at FROM schema_migrations ORDER BY version"
+                    .to_string(),
+            );
+            AppliedMigration::find_by_statement(statement)
+                .all(db)
+                .await
+                .map_err(|e| e.to_string())?
+        }
+        false => Vec::new(),
+    };
+
+    let pending = MIGRATIONS
+        .iter()
+        .filter(|m| !applied.iter().any(|a| a.version == m.version as i64))
+        .collect();
+    return Ok(MigrationStatus { applied, pending });
+}
+
+async fn table_exists(db: &DatabaseConnection, table: &str) -> Result<bool, String> {
+    #[derive(FromQueryResult)]
+    struct Count {
+        count: i64,
+    }
+    let backend = db.get_database_backend();
+    let sql = match backend {
+        DbBackend::Postgres => {
+            "SELECT COUNT(*) AS count FROM information_schema.tables \
+             WHERE table_schema = current_schema() AND table_name = ?"
+        }
+        _ => "SELECT COUNT(*) AS count FROM sqlite_master WHERE type = 'table' AND name = ?",
+    };
+    let statement = database::statement(backend, sql, vec![table.into()]);
+    let count = Count::find_by_statement(statement)
+        .one(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    return Ok(count.is_some_and(|c| c.count > 0));
+}
+
+/// the migrations `migrate` would apply, fails like `migrate` if the database is newer
+pub async fn pending(db: &DatabaseConnection) -> Result<Vec<&'static Migration>, String> {
+    let status = status(db).await?;
+    check_version(&status)?;
+    return Ok(status.pending);
+}
+
+/// applies all pending migrations, each one in its own transaction
+pub async fn migrate(db: &DatabaseConnection) -> Result<Vec<&'static Migration>, String> {
+    execute(db, CREATE_MIGRATIONS_TABLE).await?;
+    let pending = pending(db).await?;
+    for migration in &pending {
+        apply(db, migration)
+            .await
+            .map_err(|e| format!("migration {} failed: {}", migration.version, e))?;
+    }
+    return Ok(pending);
+}
+
+fn check_version(status: &MigrationStatus) -> Result<(), String> {
+    if status.is_newer_than_binary() {
+        return Err(format!(
+            "the database has schema version {} but this program only knows version {}, use a newer version of the program",
+            status.version(),
+            latest_version()
+        ));
+    }
+    Ok(())
+}
+
+async fn apply(db: &DatabaseConnection, migration: &Migration) -> Result<(), String> {
+    let transaction = db.begin().await.map_err(|e| e.to_string())?;
+    for sql in statements(migration.sql(db.get_database_backend())) {
+        execute(&transaction, &sql).await?;
+    }
+
+    let record = database::statement(
+        db.get_database_backend(),
+        "INSERT INTO schema_migrations (version, name, applied_at) VALUES (?, ?, ?)",
+        vec![
+            (migration.version as i64).into(),
+            migration.name.into(),
+            now().into(),
+        ],
+    );
+    transaction
+        .execute(record)
+        .await
+        .map_err(|e| e.to_string())?;
+    transaction.commit().await.map_err(|e| e.to_string())
+}
+
+/// splits a migration at each ";" at the end of a line and drops comments
+pub fn statements(sql: &str) -> Vec<String> {
+    let without_comments: Vec<&str> = sql
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("--"))
+        .collect();
+    return without_comments
+        .join("\n")
+        .split(";\n")
+        .map(|s| s.trim().trim_end_matches(';').trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+}
+
+async fn execute<'a, C: ConnectionTrait<'a>>(db: &C, sql: &str) -> Result<(), String> {
+    let statement = Statement::from_string(db.get_database_backend(), sql.to_string());
+    db.execute(statement)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::database::test_database as database;
+
+    async fn count(db: &DatabaseConnection, table: &str) -> i64 {
+        #[derive(FromQueryResult)]
+        struct Count {
+            count: i64,
+        }
+        let sql = format!("SELECT COUNT(*) AS count FROM {}", table);
+        let statement = Statement::from_string(db.get_database_backend(), sql);
+        Count::find_by_statement(statement)
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap()
+            .count
+    }
+
+    #[test]
+    fn versions_are_ordered_and_unique() {
+        for pair in MIGRATIONS.windows(2) {
+            assert!(pair[0].version < pair[1].version);
+        }
+    }
+
+    #[test]
+    fn statements_are_split() {
+        let sql = "-- comment;\nCREATE TABLE a (x);\n\nINSERT INTO a\nVALUES (';');\n";
+        assert_eq!(
+            statements(sql),
+            ["CREATE TABLE a (x)", "INSERT INTO a\nVALUES (';')"]
+        );
+    }
+
+    #[tokio::test]
+    async fn empty_database_is_migrated() {
+        let db = database().await;
+        let applied = migrate(&db).await.unwrap();
+        assert_eq!(applied.len(), MIGRATIONS.len());
+
+        let status = status(&db).await.unwrap();
+        assert_eq!(status.version(), latest_version());
+        assert!(status.pending.is_empty());
+    }
+
+    #[tokio::test]
+    async fn migrate_twice_does_nothing() {
+        let db = database().await;
+        migrate(&db).await.unwrap();
+        assert!(migrate(&db).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn pending_does_not_apply() {
+        let db = database().await;
+        assert_eq!(pending(&db).await.unwrap().len(), MIGRATIONS.len());
+        assert_eq!(status(&db).await.unwrap().version(), 0);
+    }
+
+    #[tokio::test]
+    async fn database_without_migrations_is_adopted() {
+        let db = database().await;
+        if db.get_database_backend() != DbBackend::Sqlite {
+            return; // only sqlite databases were created before migrations existed
+        }
+        execute(&*db, "CREATE TABLE `files` ( `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT, `name` text NOT NULL, `path` text NOT NULL, `mime` text NOT NULL, `size` integer NOT NULL, `group_id` text NOT NULL, `group_member_name` text NOT NULL )").await.unwrap();
+        execute(&*db, "INSERT INTO files (name, path, mime, size, group_id, group_member_name) VALUES ('a', '/a.mp4', 'video/mp4', 1, 'g', 'a.mp4')").await.unwrap();
+
+        migrate(&db).await.unwrap();
+        assert_eq!(count(&db, "files").await, 1);
+        assert_eq!(count(&db, "files_search").await, 1);
+    }
+
+    #[tokio::test]
+    async fn unreadable_status_is_error() {
+        let db = database().await;
+        execute(&*db, "CREATE TABLE schema_migrations (other text)")
+            .await
+            .unwrap();
+        assert!(status(&db).await.is_err());
+        assert!(migrate(&db).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn newer_database_is_refused() {
+        let db = database().await;
+        migrate(&db).await.unwrap();
+        execute(
+            &*db,
+            "INSERT INTO schema_migrations (version, name, applied_at) VALUES (9999, 'future', 0)",
+        )
+        .await
+        .unwrap();
+
+        assert!(status(&db).await.unwrap().is_newer_than_binary());
+        assert!(migrate(&db).await.is_err());
+    }
+}
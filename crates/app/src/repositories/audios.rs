@@ -1,5 +1,9 @@
+use super::{
+    page::{fetch_page, FileFilter, Page, PageRequest},
+    query,
+};
 use crate::entities::{file, File};
-use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, Order, QueryFilter, QueryOrder};
+use sea_orm::{ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter};
 
 #[derive(Clone)]
 pub struct Audios {
@@ -11,23 +15,26 @@ impl Audios {
         Self { db }
     }
 
-    pub async fn find_all(&self) -> Result<Vec<File>, String> {
-        file::Entity::find()
-            .filter(file::Column::Mime.like("audio/%"))
-            .order_by(file::Column::Name, Order::Asc)
-            .all(&self.db)
-            .await
-            .map(|e| e.into_iter().map(File::from).collect())
-            .map_err(|e| e.to_string())
+    pub async fn find_page(
+        &self,
+        filter: &FileFilter,
+        request: PageRequest,
+    ) -> Result<Page<File>, String> {
+        let select = file::Entity::find().filter(audio());
+        fetch_page(&self.db, select, filter, request).await
     }
 
     pub async fn find_by_id(&self, id: u64) -> Result<Option<File>, String> {
         file::Entity::find()
             .filter(file::Column::Id.eq(id))
-            .filter(file::Column::Mime.like("audio/%"))
+            .filter(audio())
             .one(&self.db)
             .await
             .map(|e| e.map(File::from))
             .map_err(|e| e.to_string())
     }
 }
+
+pub(super) fn audio() -> Condition {
+    query::mime_type("audio")
+}
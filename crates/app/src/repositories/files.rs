@@ -1,9 +1,15 @@
+use super::{
+    page::{fetch_page, FileFilter, Page, PageRequest},
+    query,
+    search::{self, SearchMetadata},
+};
 use crate::entities::file;
 use file::File;
 use sea_orm::{
-    ColumnTrait, Condition, DatabaseConnection, EntityTrait, Order, QueryFilter, QueryOrder, Set,
-    Unset,
+    ColumnTrait, DatabaseConnection, EntityTrait, FromQueryResult, Order, QueryFilter, QueryOrder,
+    QuerySelect, Set, Unset,
 };
+use std::collections::HashMap;
 
 #[derive(Clone)]
 pub struct FileRepository {
@@ -21,36 +27,53 @@ impl FileRepository {
         group_member_name: &str,
     ) -> Result<Option<File>, String> {
         file::Entity::find()
-            .filter(
-                Condition::all()
-                    .add(file::Column::GroupId.like(group_id))
-                    .add(file::Column::GroupMemberName.like(group_member_name)),
-            )
+            .filter(query::group_member(group_id, group_member_name))
             .one(&self.db)
             .await
             .map(|f| f.map(File::from))
             .map_err(|e| e.to_string())
     }
 
-    pub async fn find_all(&self) -> Result<Vec<File>, String> {
+    pub async fn find_all_by_group(&self, group_id: &str) -> Result<Vec<File>, String> {
         file::Entity::find()
-            .order_by(file::Column::Name, Order::Asc)
+            .filter(file::Column::GroupId.eq(group_id))
+            .order_by(file::Column::GroupMemberName, Order::Asc)
             .all(&self.db)
             .await
             .map(|f| f.into_iter().map(File::from).collect())
             .map_err(|e| e.to_string())
     }
 
+    pub async fn find_by_id(&self, id: u64) -> Result<Option<File>, String> {
+        file::Entity::find()
+            .filter(file::Column::Id.eq(id))
+            .one(&self.db)
+            .await
+            .map(|f| f.map(File::from))
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn find_page(
+        &self,
+        filter: &FileFilter,
+        request: PageRequest,
+    ) -> Result<Page<File>, String> {
+        let select = file::Entity::find();
+        fetch_page(&self.db, select, filter, request).await
+    }
+
     /// silently throws away files that are too large to be saved in database (size > i64::MAX)
     pub async fn insert_all(&self, files: Vec<InsertFile>) -> Result<(), String> {
         // SQLITE-Limitation: throws error "Execution Error: error returned from database: too many SQL variables" if there are too many inserts at once
+        // postgres allows at most 65535 variables per statement, chunks of 1024 files stay below both
         let mut inserts = files
             .into_iter()
-            .filter_map(|e| e.try_into().ok())
+            .filter_map(|e| e.clone().try_into().ok().map(|model| (model, e)))
             .peekable();
 
         while inserts.peek().is_some() {
-            let chunk: Vec<file::ActiveModel> = inserts.by_ref().take(1024).collect();
+            let (chunk, files): (Vec<file::ActiveModel>, Vec<InsertFile>) =
+                inserts.by_ref().take(1024).unzip();
             let result = file::Entity::insert_many(chunk)
                 .exec(&self.db)
                 .await
@@ -61,14 +84,61 @@ impl FileRepository {
                 println!("{}", result.as_ref().err().unwrap());
                 return result;
             }
+            search::index(&self.db, &files).await?;
         }
 
         Ok(())
     }
 
+    /// like `insert_all`, but files that are already saved with the same path are replaced
+    pub async fn replace_all(&self, files: Vec<InsertFile>) -> Result<(), String> {
+        let paths: Vec<String> = files.iter().map(|f| f.path.to_owned()).collect();
+        for chunk in paths.chunks(1024) {
+            search::delete_by_paths(&self.db, chunk).await?;
+            file::Entity::delete_many()
+                .filter(file::Column::Path.is_in(chunk.iter().map(String::as_str)))
+                .exec(&self.db)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        self.insert_all(files).await
+    }
+
+    /// when each saved path was added, so a rescan can keep it
+    pub async fn find_all_added(&self) -> Result<HashMap<String, u64>, String> {
+        #[derive(FromQueryResult)]
+        struct Added {
+            path: String,
+            added: i64,
+        }
+        let rows = file::Entity::find()
+            .select_only()
+            .column(file::Column::Path)
+            .column(file::Column::Added)
+            .into_model::<Added>()
+            .all(&self.db)
+            .await
+            .map_err(|e| e.to_string())?;
+        let added = rows
+            .into_iter()
+            .map(|row| (row.path, row.added.try_into().unwrap_or_default()));
+        Ok(added.collect())
+    }
+
+    /// every file with its search metadata in the order they were added
+    pub async fn find_all_with_metadata(&self) -> Result<Vec<InsertFile>, String> {
+        search::files_with_metadata(&self.db).await
+    }
+
+    pub async fn delete_all(&self) -> Result<(), String> {
+        // every path starts with the empty string
+        self.delete_by_path("").await
+    }
+
     pub async fn delete_by_path(&self, path: &str) -> Result<(), String> {
+        search::delete_by_path(&self.db, path).await?;
         file::Entity::delete_many()
-            .filter(file::Column::Path.like(&format!("{}%", path)))
+            .filter(query::path_starts_with(path))
             .exec(&self.db)
             .await
             .map(|_| ())
@@ -84,6 +154,9 @@ pub struct InsertFile {
     pub size: u64,
     pub group_id: String,
     pub group_member_name: String,
+    /// seconds since the unix epoch
+    pub added: u64,
+    pub metadata: SearchMetadata,
 }
 
 impl TryInto<file::ActiveModel> for InsertFile {
@@ -101,6 +174,10 @@ impl TryInto<file::ActiveModel> for InsertFile {
                 .map_err(|_| "file size is too big: max is i64::MAX".to_string())?),
             group_id: Set(self.group_id),
             group_member_name: Set(self.group_member_name),
+            added: Set(self
+                .added
+                .try_into()
+                .map_err(|_| "file added is too big: max is i64::MAX".to_string())?),
         })
     }
 }
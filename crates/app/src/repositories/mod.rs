@@ -1,10 +1,17 @@
 mod audios;
 mod files;
+mod page;
+mod query;
+mod search;
+mod users;
 mod videos;
 
 pub use audios::Audios;
 pub use files::FileRepository;
 pub use files::InsertFile;
+pub use page::{FileFilter, Page, PageRequest, Sort, SortOrder};
+pub use search::{SearchHit, SearchMetadata, SearchRepository, MATCH_END, MATCH_START};
+pub use users::UserRepository;
 pub use videos::Videos;
 
 use axum::{AddExtensionLayer, Router};
@@ -14,4 +21,6 @@ pub fn setup(router: Router, db: &DatabaseConnection) -> Router {
         .layer(AddExtensionLayer::new(FileRepository::new(db.clone())))
         .layer(AddExtensionLayer::new(Audios::new(db.clone())))
         .layer(AddExtensionLayer::new(Videos::new(db.clone())))
+        .layer(AddExtensionLayer::new(SearchRepository::new(db.clone())))
+        .layer(AddExtensionLayer::new(UserRepository::new(db.clone())))
 }
@@ -0,0 +1,334 @@
+#![allow(clippy::needless_return)]
+
+use super::query;
+use crate::entities::{file, File};
+use sea_orm::{
+    sea_query::Expr, ColumnTrait, Condition, DatabaseConnection, Order, QueryFilter, QueryOrder,
+    Select,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+pub const DEFAULT_PER_PAGE: usize = 50;
+pub const MAX_PER_PAGE: usize = 500;
+
+/// a page of a query result, pages start at 1
+#[derive(Clone, Copy, Debug)]
+pub struct PageRequest {
+    pub page: usize,
+    pub per_page: usize,
+    pub sort: Sort,
+    pub order: SortOrder,
+}
+
+impl Default for PageRequest {
+    fn default() -> Self {
+        Self {
+            page: 1,
+            per_page: DEFAULT_PER_PAGE,
+            sort: Sort::default(),
+            order: SortOrder::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    #[default]
+    Name,
+    Size,
+    /// when a scan found the file first
+    Added,
+}
+
+impl Sort {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Sort::Name => "name",
+            Sort::Size => "size",
+            Sort::Added => "added",
+        }
+    }
+
+    fn column(&self) -> file::Column {
+        match self {
+            Sort::Name => file::Column::Name,
+            Sort::Size => file::Column::Size,
+            Sort::Added => file::Column::Added,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    pub fn as_str(&self) -> &str {
+        match self {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        }
+    }
+
+    fn order(&self) -> Order {
+        match self {
+            SortOrder::Asc => Order::Asc,
+            SortOrder::Desc => Order::Desc,
+        }
+    }
+}
+
+impl PageRequest {
+    /// fails if page is 0 or per_page is not between 1 and MAX_PER_PAGE
+    pub fn new(page: Option<usize>, per_page: Option<usize>) -> Result<Self, String> {
+        let request = Self {
+            page: page.unwrap_or(1),
+            per_page: per_page.unwrap_or(DEFAULT_PER_PAGE),
+            ..Default::default()
+        };
+        if request.page == 0 {
+            return Err("page starts at 1".to_string());
+        }
+        if request.per_page == 0 || request.per_page > MAX_PER_PAGE {
+            return Err(format!("per_page has to be between 1 and {}", MAX_PER_PAGE));
+        }
+        return Ok(request);
+    }
+
+    pub fn sorted(self, sort: Option<Sort>, order: Option<SortOrder>) -> Self {
+        Self {
+            sort: sort.unwrap_or_default(),
+            order: order.unwrap_or_default(),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub page: usize,
+    pub per_page: usize,
+    pub total: usize,
+}
+
+impl<T> Page<T> {
+    /// number of pages, at least 1 even if there are no items
+    pub fn pages(&self) -> usize {
+        std::cmp::max(1, self.total.div_ceil(self.per_page))
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            page: self.page,
+            per_page: self.per_page,
+            total: self.total,
+        }
+    }
+}
+
+/// restricts which files are returned, empty fields match everything
+#[derive(Clone, Debug, Default)]
+pub struct FileFilter {
+    /// part of the name
+    pub query: Option<String>,
+    pub mime: Option<String>,
+    pub group_id: Option<String>,
+    /// only files below one of these directories, encoded and ending with a separator
+    pub roots: Option<Vec<String>>,
+}
+
+impl FileFilter {
+    pub(super) fn condition(&self) -> Condition {
+        let mut condition = Condition::all();
+        if let Some(query) = &self.query {
+            // like in sqlite, postgres compares case sensitive
+            condition = condition.add(Expr::cust_with_values(
+                "LOWER(\"files\".\"name\") LIKE LOWER(?) ESCAPE '!'",
+                vec![format!("%{}%", escape_like(query))],
+            ));
+        }
+        if let Some(mime) = &self.mime {
+            condition = condition.add(file::Column::Mime.eq(mime.as_str()));
+        }
+        if let Some(group_id) = &self.group_id {
+            condition = condition.add(file::Column::GroupId.eq(group_id.as_str()));
+        }
+        if let Some(roots) = &self.roots {
+            // an empty `any` is left out of the query and would match every file
+            let mut below = Condition::any().add(Expr::cust("1 = 0"));
+            for root in roots {
+                below = below.add(query::path_starts_with(root));
+            }
+            condition = condition.add(below);
+        }
+        return condition;
+    }
+}
+
+/// `%` and `_` of user input are not wildcards
+fn escape_like(value: &str) -> String {
+    value
+        .replace('!', "!!")
+        .replace('%', "!%")
+        .replace('_', "!_")
+}
+
+pub(super) async fn fetch_page(
+    db: &DatabaseConnection,
+    select: Select<file::Entity>,
+    filter: &FileFilter,
+    request: PageRequest,
+) -> Result<Page<File>, String> {
+    // the id makes the order of equal values stable between pages
+    let paginator = select
+        .filter(filter.condition())
+        .order_by(request.sort.column(), request.order.order())
+        .order_by(file::Column::Id, request.order.order())
+        .paginate(db, request.per_page);
+
+    let total = paginator.num_items().await.map_err(|e| e.to_string())?;
+    let items = paginator
+        .fetch_page(request.page - 1)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(Page {
+        items: items.into_iter().map(File::from).collect(),
+        page: request.page,
+        per_page: request.per_page,
+        total,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::database::{test_database, TestDatabase};
+    use crate::repositories::{FileRepository, InsertFile};
+    use sea_orm::EntityTrait;
+
+    /// files that are inserted later were added earlier, unlike their ids
+    async fn files(sizes: &[(&str, u64)]) -> TestDatabase {
+        let db = test_database().await;
+        crate::migrations::migrate(&db).await.unwrap();
+        let inserts = sizes
+            .iter()
+            .enumerate()
+            .map(|(i, (name, size))| InsertFile {
+                name: name.to_string(),
+                path: format!("/{}", name),
+                mime: "video/mp4".to_string(),
+                size: *size,
+                group_id: "group".to_string(),
+                group_member_name: name.to_string(),
+                added: (sizes.len() - i) as u64,
+                metadata: Default::default(),
+            })
+            .collect();
+        FileRepository::new(db.clone())
+            .insert_all(inserts)
+            .await
+            .unwrap();
+        return db;
+    }
+
+    async fn names(
+        db: &DatabaseConnection,
+        filter: &FileFilter,
+        request: PageRequest,
+    ) -> Vec<String> {
+        let page = fetch_page(db, file::Entity::find(), filter, request)
+            .await
+            .unwrap();
+        return page.items.into_iter().map(|f| f.name).collect();
+    }
+
+    #[tokio::test]
+    async fn sorted_by_name_by_default() {
+        let db = files(&[("b", 1), ("c", 3), ("a", 2)]).await;
+        let names = names(&db, &FileFilter::default(), PageRequest::default()).await;
+        assert_eq!(names, ["a", "b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn sorted_by_size_descending() {
+        let db = files(&[("b", 1), ("c", 3), ("a", 2)]).await;
+        let request = PageRequest::default().sorted(Some(Sort::Size), Some(SortOrder::Desc));
+        let names = names(&db, &FileFilter::default(), request).await;
+        assert_eq!(names, ["c", "a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn sorted_by_added() {
+        let db = files(&[("b", 1), ("c", 3), ("a", 2)]).await;
+        let request = PageRequest::default().sorted(Some(Sort::Added), None);
+        let names = names(&db, &FileFilter::default(), request).await;
+        assert_eq!(names, ["a", "c", "b"]);
+    }
+
+    #[tokio::test]
+    async fn pages_with_filter() {
+        let db = files(&[("ab", 1), ("b", 1), ("ac", 1), ("ad", 1)]).await;
+        let filter = FileFilter {
+            query: Some("a".to_string()),
+            ..Default::default()
+        };
+        let request = PageRequest::new(Some(2), Some(2)).unwrap();
+        let page = fetch_page(&db, file::Entity::find(), &filter, request)
+            .await
+            .unwrap();
+
+        assert_eq!(page.total, 3);
+        assert_eq!(page.pages(), 2);
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].name, "ad");
+    }
+
+    #[tokio::test]
+    async fn filter_by_roots() {
+        let db = files(&[("a/x", 1), ("ab/y", 1), ("b/z", 1)]).await;
+        let filter = |roots: &[&str]| FileFilter {
+            roots: Some(roots.iter().map(|r| r.to_string()).collect()),
+            ..Default::default()
+        };
+        let request = PageRequest::default();
+        assert_eq!(names(&db, &filter(&["/a/"]), request).await, ["a/x"]);
+        assert_eq!(
+            names(&db, &filter(&["/a/", "/b/"]), request).await,
+            ["a/x", "b/z"]
+        );
+        assert!(names(&db, &filter(&[]), request).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn filter_ignores_case_and_wildcards() {
+        let db = files(&[("Toy_Story", 1), ("ToyxStory", 1), ("100%", 1)]).await;
+        let filter = |query: &str| FileFilter {
+            query: Some(query.to_string()),
+            ..Default::default()
+        };
+        let request = PageRequest::default();
+        assert_eq!(names(&db, &filter("y_s"), request).await, ["Toy_Story"]);
+        assert_eq!(names(&db, &filter("TOY"), request).await.len(), 2);
+        assert_eq!(names(&db, &filter("0%"), request).await, ["100%"]);
+    }
+
+    #[test]
+    fn at_least_one_page() {
+        let page: Page<File> = Page {
+            items: Vec::new(),
+            page: 1,
+            per_page: 50,
+            total: 0,
+        };
+        assert_eq!(page.pages(), 1);
+    }
+}
@@ -0,0 +1,174 @@
+//! conditions that can use the indexes of the files table
+//!
+//! `LIKE` is case insensitive in sqlite and therefore can not use an index, it also treats "%"
+//! and "_" as wildcards. prefixes are matched as a range of strings instead
+
+#![allow(clippy::needless_return)]
+
+use crate::entities::file;
+use sea_orm::{ColumnTrait, Condition};
+
+/// greater than every string that starts with the prefix
+const MAX_CHAR: char = char::MAX;
+
+/// exact match on the index (group_id, group_member_name)
+pub fn group_member(group_id: &str, group_member_name: &str) -> Condition {
+    Condition::all()
+        .add(file::Column::GroupId.eq(group_id))
+        .add(file::Column::GroupMemberName.eq(group_member_name))
+}
+
+pub fn path_starts_with(prefix: &str) -> Condition {
+    Condition::all()
+        .add(file::Column::Path.gte(prefix))
+        .add(file::Column::Path.lt(upper_bound(prefix)))
+}
+
+/// "audio" matches all mimes starting with "audio/"
+pub fn mime_type(top_level: &str) -> Condition {
+    let prefix = format!("{}/", top_level);
+    Condition::all()
+        .add(file::Column::Mime.gte(prefix.as_str()))
+        .add(file::Column::Mime.lt(upper_bound(&prefix)))
+}
+
+pub fn upper_bound(prefix: &str) -> String {
+    format!("{}{}", prefix, MAX_CHAR)
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{audios, videos};
+    use super::*;
+    use crate::database::{test_database, TestDatabase};
+    use crate::entities::file;
+    use sea_orm::{
+        ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait, FromQueryResult, QueryFilter,
+        QueryTrait, Statement,
+    };
+
+    #[derive(FromQueryResult)]
+    struct Plan {
+        detail: String,
+    }
+
+    async fn database() -> TestDatabase {
+        let db = test_database().await;
+        crate::migrations::migrate(&db).await.unwrap();
+        return db;
+    }
+
+    /// the details of `EXPLAIN QUERY PLAN` joined by newlines, empty for other backends than sqlite
+    async fn query_plan(db: &DatabaseConnection, query: &impl QueryTrait) -> Option<String> {
+        if db.get_database_backend() != DbBackend::Sqlite {
+            return None;
+        }
+        let statement = query.build(DbBackend::Sqlite);
+        let explain = Statement {
+            sql: format!("EXPLAIN QUERY PLAN {}", statement.sql),
+            ..statement
+        };
+        let plan = Plan::find_by_statement(explain).all(db).await.unwrap();
+        let plan = plan.into_iter().map(|p| p.detail).collect::<Vec<_>>();
+        return Some(plan.join("\n"));
+    }
+
+    #[tokio::test]
+    async fn stream_lookup_uses_group_index() {
+        let db = database().await;
+        let query = file::Entity::find().filter(group_member("group", "name.mp4"));
+        let plan = match query_plan(&db, &query).await {
+            Some(plan) => plan,
+            None => return,
+        };
+        assert!(
+            plan.contains("USING INDEX files_group (group_id=? AND group_member_name=?)"),
+            "{}",
+            plan
+        );
+    }
+
+    #[tokio::test]
+    async fn group_lookup_uses_group_index() {
+        let db = database().await;
+        let query = file::Entity::find().filter(file::Column::GroupId.eq("group"));
+        let plan = match query_plan(&db, &query).await {
+            Some(plan) => plan,
+            None => return,
+        };
+        assert!(
+            plan.contains("USING INDEX files_group (group_id=?)"),
+            "{}",
+            plan
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_by_path_uses_path_index() {
+        let db = database().await;
+        let query = file::Entity::delete_many().filter(path_starts_with("./dir/"));
+        let plan = match query_plan(&db, &query).await {
+            Some(plan) => plan,
+            None => return,
+        };
+        assert!(plan.contains("USING INDEX files_path"), "{}", plan);
+    }
+
+    #[tokio::test]
+    async fn mime_filters_use_mime_index() {
+        let db = database().await;
+        let audios = file::Entity::find().filter(audios::audio());
+        let plan = match query_plan(&db, &audios).await {
+            Some(plan) => plan,
+            None => return,
+        };
+        assert!(plan.contains("USING INDEX files_mime"), "{}", plan);
+
+        let videos = file::Entity::find().filter(videos::visible_movies());
+        let plan = query_plan(&db, &videos).await.unwrap();
+        assert!(plan.contains("USING INDEX files_mime"), "{}", plan);
+    }
+
+    #[tokio::test]
+    async fn wildcards_are_literal() {
+        let db = database().await;
+        let files = super::super::FileRepository::new(db.clone());
+        let insert = |path: &str| super::super::InsertFile {
+            name: "name".to_string(),
+            path: path.to_string(),
+            mime: "video/mp4".to_string(),
+            size: 1,
+            group_id: "%".to_string(),
+            group_member_name: path.to_string(),
+            added: 0,
+            metadata: Default::default(),
+        };
+        files
+            .insert_all(vec![insert("/a_b/1.mp4"), insert("/axb/2.mp4")])
+            .await
+            .unwrap();
+
+        assert!(files
+            .find_by_group("x", "/a_b/1.mp4")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(files
+            .find_by_group("%", "/a_b/1.mp4")
+            .await
+            .unwrap()
+            .is_some());
+
+        files.delete_by_path("/a_b/").await.unwrap();
+        assert!(files
+            .find_by_group("%", "/a_b/1.mp4")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(files
+            .find_by_group("%", "/axb/2.mp4")
+            .await
+            .unwrap()
+            .is_some());
+    }
+}
@@ -0,0 +1,494 @@
+#![allow(clippy::needless_return)]
+
+use super::{files::InsertFile, query};
+use crate::{
+    database,
+    entities::{file, search::TABLE, File},
+};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait, FromQueryResult,
+    QueryFilter, Value,
+};
+use std::collections::HashMap;
+
+/// surrounds the matched terms in the highlighted texts of a `SearchHit`
+pub const MATCH_START: char = '\u{2}';
+pub const MATCH_END: char = '\u{3}';
+
+/// number of words of the plot around the first match
+const PLOT_SNIPPET_WORDS: usize = 24;
+
+/// searchable text of a file besides its name and path
+#[derive(Clone, Debug, Default)]
+pub struct SearchMetadata {
+    /// name without release tags like "1080p" or "x264"
+    pub title: String,
+    /// artist, album, title, ... of audio files
+    pub tags: String,
+    /// plot of the ".nfo" file next to a video
+    pub plot: String,
+}
+
+/// the texts contain the matched terms between `MATCH_START` and `MATCH_END`
+pub struct SearchHit {
+    pub file: File,
+    pub title: String,
+    pub path: String,
+    pub tags: String,
+    /// part of the plot around the matched terms
+    pub plot: String,
+}
+
+#[derive(FromQueryResult)]
+struct SearchRow {
+    id: i64,
+    name: String,
+    path: String,
+    mime: String,
+    size: i64,
+    group_id: String,
+    group_member_name: String,
+    added: i64,
+    title: String,
+    tags: String,
+    plot: String,
+}
+
+impl SearchRow {
+    fn into_hit(self, words: &[String]) -> SearchHit {
+        let file = file::Model {
+            id: self.id,
+            name: self.name,
+            path: self.path,
+            mime: self.mime,
+            size: self.size,
+            group_id: self.group_id,
+            group_member_name: self.group_member_name,
+            added: self.added,
+        };
+        SearchHit {
+            title: highlight(&self.title, words),
+            path: highlight(&file.path, words),
+            tags: highlight(&self.tags, words),
+            plot: snippet(&self.plot, words),
+            file: File::from(file),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SearchRepository {
+    db: DatabaseConnection,
+}
+
+impl SearchRepository {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// every word of `query` has to match the start of a word in the index, best matches first
+    ///
+    /// with `roots` only files below one of these directories are found, see `FileFilter::roots`
+    pub async fn search(
+        &self,
+        query: &str,
+        roots: Option<&[String]>,
+        limit: u64,
+    ) -> Result<Vec<SearchHit>, String> {
+        let words = words(query);
+        if words.is_empty() || roots.is_some_and(|r| r.is_empty()) {
+            return Ok(Vec::new());
+        }
+        let (below, mut below_values) = below_roots(roots.unwrap_or_default());
+
+        let backend = self.db.get_database_backend();
+        let columns = "files.id, files.name, files.path, files.mime, files.size, files.group_id, \
+            files.group_member_name, files.added, s.title, s.tags, s.plot";
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let statement = match backend {
+            DbBackend::Postgres => {
+                let expression = tsquery_expression(&words);
+                let sql = format!(
+                    "SELECT {columns} FROM {table} s JOIN files ON files.id = s.id \
+                    WHERE s.document @@ to_tsquery('simple', ?){below} \
+                    ORDER BY ts_rank(s.document, to_tsquery('simple', ?)) DESC, files.id LIMIT ?",
+                    columns = columns,
+                    table = TABLE,
+                    below = below
+                );
+                let mut values = vec![expression.clone().into()];
+                values.append(&mut below_values);
+                values.extend([expression.into(), limit.into()]);
+                database::statement(backend, &sql, values)
+            }
+            _ => {
+                let sql = format!(
+                    "SELECT {columns} FROM {table} s JOIN files ON files.id = s.rowid \
+                    WHERE s.{table} MATCH ?{below} ORDER BY s.rank LIMIT ?",
+                    columns = columns,
+                    table = TABLE,
+                    below = below
+                );
+                let mut values = vec![fts5_expression(&words).into()];
+                values.append(&mut below_values);
+                values.push(limit.into());
+                database::statement(backend, &sql, values)
+            }
+        };
+
+        let rows = SearchRow::find_by_statement(statement)
+            .all(&self.db)
+            .await
+            .map_err(|e| e.to_string())?;
+        return Ok(rows.into_iter().map(|r| r.into_hit(&words)).collect());
+    }
+}
+
+/// " AND (...)" restricting the paths like `query::path_starts_with`, empty without roots
+fn below_roots(roots: &[String]) -> (String, Vec<Value>) {
+    if roots.is_empty() {
+        return (String::new(), Vec::new());
+    }
+    let conditions = vec!["(files.path >= ? AND files.path < ?)"; roots.len()];
+    let values = roots
+        .iter()
+        .flat_map(|r| [r.to_owned().into(), query::upper_bound(r).into()])
+        .collect();
+    return (format!(" AND ({})", conditions.join(" OR ")), values);
+}
+
+/// the alphanumeric parts of the query, this removes the syntax of fts5 and tsquery
+fn words(query: &str) -> Vec<String> {
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+/// `"toy"* "sto"*`
+fn fts5_expression(words: &[String]) -> String {
+    let words: Vec<String> = words.iter().map(|w| format!("\"{}\"*", w)).collect();
+    return words.join(" ");
+}
+
+/// `'toy':* & 'sto':*`
+fn tsquery_expression(words: &[String]) -> String {
+    let words: Vec<String> = words.iter().map(|w| format!("'{}':*", w)).collect();
+    return words.join(" & ");
+}
+
+/// surrounds every word of `text` that starts with one of `words` with the match markers
+fn highlight(text: &str, words: &[String]) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut word = String::new();
+    for c in text.chars().chain(std::iter::once('\0')) {
+        if c.is_alphanumeric() {
+            word.push(c);
+            continue;
+        }
+        if !word.is_empty() {
+            let lowercase = word.to_lowercase();
+            if words.iter().any(|w| lowercase.starts_with(w.as_str())) {
+                output.push(MATCH_START);
+                output.push_str(&word);
+                output.push(MATCH_END);
+            } else {
+                output.push_str(&word);
+            }
+            word.clear();
+        }
+        if c != '\0' {
+            output.push(c);
+        }
+    }
+    return output;
+}
+
+/// the words around the first match of a long text
+fn snippet(text: &str, words: &[String]) -> String {
+    let highlighted: Vec<String> = text
+        .split_whitespace()
+        .map(|w| highlight(w, words))
+        .collect();
+    if highlighted.len() <= PLOT_SNIPPET_WORDS {
+        return highlighted.join(" ");
+    }
+
+    let first_match = highlighted
+        .iter()
+        .position(|w| w.contains(MATCH_START))
+        .unwrap_or(0);
+    let start = first_match
+        .saturating_sub(PLOT_SNIPPET_WORDS / 3)
+        .min(highlighted.len() - PLOT_SNIPPET_WORDS);
+    let end = start + PLOT_SNIPPET_WORDS;
+
+    let mut snippet = highlighted[start..end].join(" ");
+    if start > 0 {
+        snippet = format!("… {}", snippet);
+    }
+    if end < highlighted.len() {
+        snippet = format!("{} …", snippet);
+    }
+    return snippet;
+}
+
+/// sqlite uses the rowid of the fts5 table
+fn key_column(backend: DbBackend) -> &'static str {
+    match backend {
+        DbBackend::Postgres => "id",
+        _ => "rowid",
+    }
+}
+
+/// adds already inserted files to the index, they are looked up by their path
+pub(super) async fn index(db: &DatabaseConnection, files: &[InsertFile]) -> Result<(), String> {
+    let by_path: HashMap<&str, &InsertFile> = files.iter().map(|f| (f.path.as_str(), f)).collect();
+    let inserted = file::Entity::find()
+        .filter(file::Column::Path.is_in(by_path.keys().copied()))
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    if inserted.is_empty() {
+        return Ok(());
+    }
+
+    let mut values: Vec<Value> = Vec::new();
+    for model in &inserted {
+        let metadata = &by_path[model.path.as_str()].metadata;
+        values.push(model.id.into());
+        values.push(metadata.title.to_owned().into());
+        values.push(model.name.to_owned().into());
+        values.push(model.path.to_owned().into());
+        values.push(metadata.tags.to_owned().into());
+        values.push(metadata.plot.to_owned().into());
+    }
+    let backend = db.get_database_backend();
+    let placeholders = vec!["(?, ?, ?, ?, ?, ?)"; inserted.len()].join(", ");
+    let sql = match backend {
+        DbBackend::Postgres => format!(
+            "INSERT INTO {} (id, title, name, path, tags, plot) VALUES {} \
+            ON CONFLICT (id) DO UPDATE SET title = excluded.title, name = excluded.name, \
+            path = excluded.path, tags = excluded.tags, plot = excluded.plot",
+            TABLE, placeholders
+        ),
+        _ => format!(
+            "INSERT OR REPLACE INTO {} (rowid, title, name, path, tags, plot) VALUES {}",
+            TABLE, placeholders
+        ),
+    };
+
+    db.execute(database::statement(backend, &sql, values))
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// removes the files from the index that `FileRepository::delete_by_path` is going to delete
+pub(super) async fn delete_by_path(db: &DatabaseConnection, path: &str) -> Result<(), String> {
+    let backend = db.get_database_backend();
+    let sql = format!(
+        "DELETE FROM {} WHERE {} IN (SELECT id FROM files WHERE path >= ? AND path < ?)",
+        TABLE,
+        key_column(backend)
+    );
+    let values = vec![path.into(), query::upper_bound(path).into()];
+    db.execute(database::statement(backend, &sql, values))
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// removes the files with exactly these paths from the index
+pub(super) async fn delete_by_paths(
+    db: &DatabaseConnection,
+    paths: &[String],
+) -> Result<(), String> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+    let backend = db.get_database_backend();
+    let sql = format!(
+        "DELETE FROM {} WHERE {} IN (SELECT id FROM files WHERE path IN ({}))",
+        TABLE,
+        key_column(backend),
+        vec!["?"; paths.len()].join(", ")
+    );
+    let values = paths.iter().map(|p| p.to_owned().into()).collect();
+    db.execute(database::statement(backend, &sql, values))
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+#[derive(FromQueryResult)]
+struct FileWithMetadata {
+    name: String,
+    path: String,
+    mime: String,
+    size: i64,
+    group_id: String,
+    group_member_name: String,
+    added: i64,
+    title: Option<String>,
+    tags: Option<String>,
+    plot: Option<String>,
+}
+
+/// every file joined with its row of the index, ordered by id
+pub(super) async fn files_with_metadata(
+    db: &DatabaseConnection,
+) -> Result<Vec<InsertFile>, String> {
+    let backend = db.get_database_backend();
+    let sql = format!(
+        "SELECT files.name, files.path, files.mime, files.size, files.group_id, \
+        files.group_member_name, files.added, s.title, s.tags, s.plot \
+        FROM files LEFT JOIN {} s ON s.{} = files.id ORDER BY files.id",
+        TABLE,
+        key_column(backend)
+    );
+    let rows = FileWithMetadata::find_by_statement(database::statement(backend, &sql, vec![]))
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let files = rows.into_iter().map(|row| InsertFile {
+        name: row.name,
+        path: row.path,
+        mime: row.mime,
+        size: row.size.try_into().unwrap_or_default(),
+        group_id: row.group_id,
+        group_member_name: row.group_member_name,
+        added: row.added.try_into().unwrap_or_default(),
+        metadata: SearchMetadata {
+            title: row.title.unwrap_or_default(),
+            tags: row.tags.unwrap_or_default(),
+            plot: row.plot.unwrap_or_default(),
+        },
+    });
+    return Ok(files.collect());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        database::{test_database, TestDatabase},
+        repositories::FileRepository,
+    };
+
+    /// the database is dropped with the `TestDatabase`
+    async fn repositories() -> (TestDatabase, FileRepository, SearchRepository) {
+        let db = test_database().await;
+        crate::migrations::migrate(&db).await.unwrap();
+        let files = FileRepository::new(db.clone());
+        let inserts = vec![
+            insert(
+                "/movies/Toy.Story.1995.mkv",
+                "video/x-matroska",
+                "Toy Story 1995",
+            ),
+            insert("/music/song.mp3", "audio/mpeg", "song"),
+        ];
+        files.insert_all(inserts).await.unwrap();
+        let search = SearchRepository::new(db.clone());
+        return (db, files, search);
+    }
+
+    fn insert(path: &str, mime: &str, title: &str) -> InsertFile {
+        let name = path.rsplit('/').next().unwrap();
+        InsertFile {
+            name: name.to_string(),
+            path: path.to_string(),
+            mime: mime.to_string(),
+            size: 1,
+            group_id: "group".to_string(),
+            group_member_name: name.to_string(),
+            added: 0,
+            metadata: SearchMetadata {
+                title: title.to_string(),
+                tags: match mime.starts_with("audio/") {
+                    true => "Kevin MacLeod".to_string(),
+                    false => String::new(),
+                },
+                plot: String::new(),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn prefix_of_title_matches() {
+        let (_db, _, search) = repositories().await;
+        let hits = search.search("toy sto", None, 10).await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].file.path, "/movies/Toy.Story.1995.mkv");
+        assert_eq!(hits[0].title, "\u{2}Toy\u{3} \u{2}Story\u{3} 1995");
+    }
+
+    #[tokio::test]
+    async fn tags_and_path_match() {
+        let (_db, _, search) = repositories().await;
+        assert_eq!(search.search("macleod", None, 10).await.unwrap().len(), 1);
+        assert_eq!(search.search("music", None, 10).await.unwrap().len(), 1);
+        assert!(search.search("nothing", None, 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn deleted_files_are_removed_from_index() {
+        let (_db, files, search) = repositories().await;
+        files.delete_by_path("/movies").await.unwrap();
+        assert!(search.search("toy", None, 10).await.unwrap().is_empty());
+        assert_eq!(search.search("song", None, 10).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn only_files_below_roots_are_found() {
+        let (_db, _, search) = repositories().await;
+        let music = ["/music/".to_string()];
+        assert!(search
+            .search("toy", Some(&music), 10)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            search.search("song", Some(&music), 10).await.unwrap().len(),
+            1
+        );
+        assert!(search
+            .search("song", Some(&[]), 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn path_components_are_highlighted() {
+        let (_db, _, search) = repositories().await;
+        let hits = search.search("movie", None, 10).await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].path, "/\u{2}movies\u{3}/Toy.Story.1995.mkv");
+    }
+
+    #[test]
+    fn words_are_prefixes() {
+        let words = words("Toy sto");
+        assert_eq!(fts5_expression(&words), "\"toy\"* \"sto\"*");
+        assert_eq!(tsquery_expression(&words), "'toy':* & 'sto':*");
+    }
+
+    #[test]
+    fn query_syntax_is_removed() {
+        assert_eq!(words("a\" OR b* & !c:"), ["a", "or", "b", "c"]);
+        assert!(words(" -*").is_empty());
+    }
+
+    #[test]
+    fn snippet_around_match() {
+        let plot: Vec<String> = (0..100).map(|i| format!("w{}", i)).collect();
+        let snippet = snippet(&plot.join(" "), &["w50".to_string()]);
+        assert!(snippet.starts_with("… w42 "), "{}", snippet);
+        assert!(snippet.contains("\u{2}w50\u{3}"));
+        assert!(snippet.ends_with(" w65 …"), "{}", snippet);
+    }
+}
@@ -0,0 +1,153 @@
+#![allow(clippy::needless_return)]
+
+use crate::database;
+use crate::entities::{user, Role, User};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, Order, QueryFilter, QueryOrder,
+    Set, Unset,
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone)]
+pub struct UserRepository {
+    db: DatabaseConnection,
+}
+
+impl UserRepository {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    pub async fn count(&self) -> Result<usize, String> {
+        user::Entity::find()
+            .paginate(&self.db, 1)
+            .num_items()
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn find_by_id(&self, id: u64) -> Result<Option<User>, String> {
+        user::Entity::find()
+            .filter(user::Column::Id.eq(id))
+            .one(&self.db)
+            .await
+            .map(|u| u.map(User::from))
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn find_all(&self) -> Result<Vec<User>, String> {
+        user::Entity::find()
+            .order_by(user::Column::Name, Order::Asc)
+            .all(&self.db)
+            .await
+            .map(|u| u.into_iter().map(User::from).collect())
+            .map_err(|e| e.to_string())
+    }
+
+    /// the user and its password hash
+    pub async fn find_credentials(&self, name: &str) -> Result<Option<(User, String)>, String> {
+        let model = user::Entity::find()
+            .filter(user::Column::Name.eq(name))
+            .one(&self.db)
+            .await
+            .map_err(|e| e.to_string())?;
+        return Ok(model.map(|m| {
+            let hash = m.password_hash.to_owned();
+            (User::from(m), hash)
+        }));
+    }
+
+    pub async fn insert(
+        &self,
+        name: &str,
+        password_hash: &str,
+        role: Role,
+    ) -> Result<User, String> {
+        let model = user::ActiveModel {
+            id: Unset(None),
+            name: Set(name.to_string()),
+            password_hash: Set(password_hash.to_string()),
+            role: Set(role.as_str().to_string()),
+            created_at: Set(now()),
+            session_epoch: Set(0),
+        };
+        let result = user::Entity::insert(model)
+            .exec(&self.db)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(User {
+            id: result.last_insert_id.try_into().unwrap_or_default(),
+            name: name.to_string(),
+            role,
+            session_epoch: 0,
+        })
+    }
+
+    /// every account with its password hash in the order they were created
+    pub async fn find_all_accounts(&self) -> Result<Vec<user::Model>, String> {
+        user::Entity::find()
+            .order_by(user::Column::Id, Order::Asc)
+            .all(&self.db)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// adds the account or replaces the password hash, the role and the creation time of the one
+    /// with the same name, whose sessions end then
+    pub async fn replace(
+        &self,
+        name: &str,
+        password_hash: &str,
+        role: Role,
+        created_at: i64,
+    ) -> Result<(), String> {
+        let existing = user::Entity::find()
+            .filter(user::Column::Name.eq(name))
+            .one(&self.db)
+            .await
+            .map_err(|e| e.to_string())?;
+        let result = match existing {
+            Some(existing) => {
+                let model = user::ActiveModel {
+                    id: Set(existing.id),
+                    password_hash: Set(password_hash.to_string()),
+                    role: Set(role.as_str().to_string()),
+                    created_at: Set(created_at),
+                    session_epoch: Set(existing.session_epoch + 1),
+                    ..Default::default()
+                };
+                user::Entity::update(model).exec(&self.db).await.map(|_| ())
+            }
+            None => {
+                let model = user::ActiveModel {
+                    id: Unset(None),
+                    name: Set(name.to_string()),
+                    password_hash: Set(password_hash.to_string()),
+                    role: Set(role.as_str().to_string()),
+                    created_at: Set(created_at),
+                    session_epoch: Set(0),
+                };
+                user::Entity::insert(model).exec(&self.db).await.map(|_| ())
+            }
+        };
+        return result.map_err(|e| e.to_string());
+    }
+
+    /// the sessions of the user that were created before are not valid anymore
+    pub async fn end_sessions(&self, id: u64) -> Result<(), String> {
+        let update = database::statement(
+            self.db.get_database_backend(),
+            "UPDATE users SET session_epoch = session_epoch + 1 WHERE id = ?",
+            vec![(id as i64).into()],
+        );
+        self.db.execute(update).await.map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
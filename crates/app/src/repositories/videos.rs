@@ -1,8 +1,10 @@
+use super::{
+    page::{fetch_page, FileFilter, Page, PageRequest},
+    query,
+};
 use crate::entities::file;
 use file::File;
-use sea_orm::{
-    ColumnTrait, Condition, DatabaseConnection, EntityTrait, Order, QueryFilter, QueryOrder,
-};
+use sea_orm::{ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter};
 
 #[derive(Clone)]
 pub struct Videos {
@@ -14,14 +16,13 @@ impl Videos {
         Self { db }
     }
 
-    pub async fn find_all(&self) -> Result<Vec<File>, String> {
-        file::Entity::find()
-            .filter(visible_movies())
-            .order_by(file::Column::Name, Order::Asc)
-            .all(&self.db)
-            .await
-            .map(|e| e.into_iter().map(File::from).collect())
-            .map_err(|e| e.to_string())
+    pub async fn find_page(
+        &self,
+        filter: &FileFilter,
+        request: PageRequest,
+    ) -> Result<Page<File>, String> {
+        let select = file::Entity::find().filter(visible_movies());
+        fetch_page(&self.db, select, filter, request).await
     }
 
     pub async fn find_by_id(&self, id: u64) -> Result<Option<File>, String> {
@@ -38,17 +39,17 @@ impl Videos {
     }
 }
 
-fn visible_movies() -> Condition {
+pub(super) fn visible_movies() -> Condition {
     Condition::all()
         .add(
             Condition::any()
-                .add(file::Column::Mime.like("video/%"))
-                .add(file::Column::Mime.like("application/x-mpegURL"))
-                .add(file::Column::Mime.like("vnd.apple.mpegURL")),
+                .add(query::mime_type("video"))
+                .add(file::Column::Mime.eq("application/x-mpegURL"))
+                .add(file::Column::Mime.eq("vnd.apple.mpegURL")),
         )
         .add(
             Condition::all()
-                .add(file::Column::Mime.not_like("video/MP2T"))
-                .add(file::Column::Mime.not_like("video/vnd.dlna.mpeg-tts")),
+                .add(file::Column::Mime.ne("video/MP2T"))
+                .add(file::Column::Mime.ne("video/vnd.dlna.mpeg-tts")),
         )
 }
@@ -0,0 +1,58 @@
+//! the http server, ranges of files on the disk are sent with sendfile(2) where the platform has it
+
+use axum::{extract::ConnectInfo, AddExtensionLayer, Router};
+use futures::ready;
+use hyper::server::{accept::Accept, conn::AddrIncoming};
+use std::{
+    convert::Infallible,
+    future::Future,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::net::TcpListener;
+use tower::ServiceBuilder;
+
+/// serves the app until `shutdown` completes, no new connections are accepted after that
+///
+/// the requests have the address of the client as `ConnectInfo<SocketAddr>` and the
+/// `fs::Sendfile` of their connection as extension if it supports sendfile
+pub async fn serve(
+    listener: TcpListener,
+    app: Router,
+    shutdown: impl Future<Output = ()>,
+) -> hyper::Result<()> {
+    let incoming = Incoming(AddrIncoming::from_listener(listener)?);
+    let make_service = hyper::service::make_service_fn(move |connection: &fs::Connection| {
+        let client = connection.peer_addr().ok().map(ConnectInfo);
+        let service = ServiceBuilder::new()
+            .option_layer(client.map(AddExtensionLayer::new))
+            .option_layer(connection.sendfile().map(AddExtensionLayer::new))
+            .service(app.clone());
+        async move { Ok::<_, Infallible>(service) }
+    });
+
+    hyper::Server::builder(incoming)
+        // placeholders of sendfile are only recognized in the buffers that hyper queues
+        .http1_writev(true)
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown)
+        .await
+}
+
+/// the accepted connections as `fs::Connection`
+struct Incoming(AddrIncoming);
+
+impl Accept for Incoming {
+    type Conn = fs::Connection;
+    type Error = io::Error;
+
+    fn poll_accept(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        let accepted = ready!(Pin::new(&mut self.get_mut().0).poll_accept(cx));
+        let connection = accepted.map(|a| a.map(|a| fs::Connection::new(a.into_inner())));
+        Poll::Ready(connection)
+    }
+}
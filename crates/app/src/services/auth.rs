@@ -0,0 +1,380 @@
+#![allow(clippy::needless_return)]
+
+use crate::{
+    entities::{Role, User},
+    repositories::UserRepository,
+};
+use argon2::{password_hash::SaltString, Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use hmac::{Hmac, Mac};
+use rand_core::{OsRng, RngCore};
+use sha2::Sha256;
+use std::{
+    io,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{io::AsyncWriteExt, sync::Mutex, task};
+
+pub const SESSION_COOKIE: &str = "netflex_session";
+/// seconds until a session cookie expires
+pub const SESSION_LIFETIME: u64 = 30 * 24 * 60 * 60;
+/// bytes of the key of the session cookies
+const SESSION_KEY_LEN: usize = 32;
+
+/// password login and the signed session cookies
+#[derive(Clone)]
+pub struct AuthService {
+    users: UserRepository,
+    session_key: Arc<Vec<u8>>,
+    /// false until the first account was created
+    has_users: Arc<AtomicBool>,
+    setup: Arc<Mutex<()>>,
+}
+
+impl AuthService {
+    /// the session key is read from `key_file`, a new one is written if it does not exist
+    pub async fn load(users: UserRepository, key_file: &Path) -> Result<Self, String> {
+        let session_key = load_key(key_file)
+            .await
+            .map_err(|e| format!("session key {}: {}", key_file.display(), e))?;
+        let has_users = users.count().await? > 0;
+        Ok(Self {
+            users,
+            session_key: Arc::new(session_key),
+            has_users: Arc::new(AtomicBool::new(has_users)),
+            setup: Arc::new(Mutex::new(())),
+        })
+    }
+
+    /// no account exists yet, the first one is created by "/setup"
+    pub fn needs_setup(&self) -> bool {
+        !self.has_users.load(Ordering::Relaxed)
+    }
+
+    /// None if an account already exists
+    pub async fn create_first_user(
+        &self,
+        name: &str,
+        password: &str,
+    ) -> Result<Option<User>, String> {
+        let _only_one_setup = self.setup.lock().await;
+        if !self.needs_setup() || self.users.count().await? > 0 {
+            return Ok(None);
+        }
+        let user = self
+            .users
+            .insert(name, &hash(password).await?, Role::Admin)
+            .await?;
+        self.has_users.store(true, Ordering::Relaxed);
+        return Ok(Some(user));
+    }
+
+    /// None if the name is already taken
+    pub async fn create_user(
+        &self,
+        name: &str,
+        password: &str,
+        role: Role,
+    ) -> Result<Option<User>, String> {
+        if self.users.find_credentials(name).await?.is_some() {
+            return Ok(None);
+        }
+        let user = self
+            .users
+            .insert(name, &hash(password).await?, role)
+            .await?;
+        return Ok(Some(user));
+    }
+
+    /// None if the name or the password is wrong
+    pub async fn login(&self, name: &str, password: &str) -> Result<Option<User>, String> {
+        let (user, password_hash) = match self.users.find_credentials(name).await? {
+            Some(credentials) => credentials,
+            None => return Ok(None),
+        };
+        return match verify(password, password_hash).await? {
+            true => Ok(Some(user)),
+            false => Ok(None),
+        };
+    }
+
+    /// value of the session cookie: "<user id>.<session epoch>.<expires>.<signature>"
+    pub fn session(&self, user: &User) -> String {
+        self.sign(user.id, user.session_epoch, now() + SESSION_LIFETIME)
+    }
+
+    /// None if the cookie is invalid, expired, ended or its user does not exist anymore
+    pub async fn user_of_session(&self, session: &str) -> Result<Option<User>, String> {
+        let (user_id, epoch) = match self.verify_session(session) {
+            Some(session) => session,
+            None => return Ok(None),
+        };
+        let user = self.users.find_by_id(user_id).await?;
+        return Ok(user.filter(|u| u.session_epoch == epoch));
+    }
+
+    /// ends every session of the user of `session`, does nothing if it is not valid
+    pub async fn logout(&self, session: &str) -> Result<(), String> {
+        return match self.user_of_session(session).await? {
+            Some(user) => self.users.end_sessions(user.id).await,
+            None => Ok(()),
+        };
+    }
+
+    /// the token of the forms of a session
+    pub fn csrf_token(&self, session: &str) -> String {
+        encode_hex(&self.mac(&csrf_payload(session)).finalize().into_bytes())
+    }
+
+    pub fn verify_csrf_token(&self, session: &str, token: &str) -> bool {
+        let token = match decode_hex(token) {
+            Some(token) => token,
+            None => return false,
+        };
+        self.mac(&csrf_payload(session))
+            .verify_slice(&token)
+            .is_ok()
+    }
+
+    fn sign(&self, user_id: u64, epoch: u64, expires: u64) -> String {
+        let payload = format!("{}.{}.{}", user_id, epoch, expires);
+        let signature = encode_hex(&self.mac(&payload).finalize().into_bytes());
+        return format!("{}.{}", payload, signature);
+    }
+
+    /// the user id and the session epoch
+    fn verify_session(&self, session: &str) -> Option<(u64, u64)> {
+        let (payload, signature) = session.rsplit_once('.')?;
+        self.mac(payload)
+            .verify_slice(&decode_hex(signature)?)
+            .ok()?;
+
+        let mut parts = payload.split('.');
+        let (user_id, epoch, expires) = (parts.next()?, parts.next()?, parts.next()?);
+        if parts.next().is_some() || expires.parse::<u64>().ok()? < now() {
+            return None;
+        }
+        return Some((user_id.parse().ok()?, epoch.parse().ok()?));
+    }
+
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.session_key)
+            .expect("hmac accepts keys of any length");
+        mac.update(payload.as_bytes());
+        return mac;
+    }
+}
+
+/// differs from every session, so a token is never a valid signature of a session
+fn csrf_payload(session: &str) -> String {
+    format!("csrf:{}", session)
+}
+
+/// argon2 takes a while on purpose, so it runs on the blocking threads
+async fn hash(password: &str) -> Result<String, String> {
+    let password = password.to_string();
+    task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|h| h.to_string())
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+async fn verify(password: &str, password_hash: String) -> Result<bool, String> {
+    let password = password.to_string();
+    task::spawn_blocking(move || {
+        let password_hash = PasswordHash::new(&password_hash).map_err(|e| e.to_string())?;
+        let verified = Argon2::default().verify_password(password.as_bytes(), &password_hash);
+        Ok(verified.is_ok())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// the key is written only if the file does not exist, so it is never replaced
+async fn load_key(path: &Path) -> io::Result<Vec<u8>> {
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    match options.open(path).await {
+        Ok(mut file) => {
+            file.write_all(random_key().as_bytes()).await?;
+            file.sync_all().await?;
+        }
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e),
+    }
+    let key = tokio::fs::read_to_string(path).await?;
+    return decode_hex(key.trim())
+        .filter(|k| k.len() >= SESSION_KEY_LEN)
+        .ok_or_else(|| {
+            let message = format!("not {} bytes of hex", SESSION_KEY_LEN);
+            io::Error::new(io::ErrorKind::InvalidData, message)
+        });
+}
+
+fn random_key() -> String {
+    let mut key = [0u8; SESSION_KEY_LEN];
+    OsRng.fill_bytes(&mut key);
+    return encode_hex(&key);
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::database::{test_database, TestDatabase};
+    use testing::TempDir;
+
+    /// the database is dropped with the `TestDatabase`
+    async fn auth() -> (TestDatabase, AuthService) {
+        let db = test_database().await;
+        crate::migrations::migrate(&db).await.unwrap();
+        // the key is only read while loading
+        let dir = TempDir::new("key");
+        let auth = AuthService::load(UserRepository::new(db.clone()), &dir.join("session.key"))
+            .await
+            .unwrap();
+        (db, auth)
+    }
+
+    #[tokio::test]
+    async fn only_one_first_user() {
+        let (_db, auth) = auth().await;
+        assert!(auth.needs_setup());
+        let admin = auth.create_first_user("admin", "password").await.unwrap();
+        assert_eq!(admin.unwrap().role, Role::Admin);
+        assert!(!auth.needs_setup());
+        let second = auth.create_first_user("other", "password").await.unwrap();
+        assert!(second.is_none());
+    }
+
+    #[tokio::test]
+    async fn login_checks_password() {
+        let (_db, auth) = auth().await;
+        auth.create_first_user("admin", "password").await.unwrap();
+        assert!(auth.login("admin", "password").await.unwrap().is_some());
+        assert!(auth.login("admin", "wrong").await.unwrap().is_none());
+        assert!(auth.login("nobody", "password").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn names_are_unique() {
+        let (_db, auth) = auth().await;
+        auth.create_first_user("admin", "password").await.unwrap();
+        let guest = auth.create_user("guest", "password", Role::Guest).await;
+        assert_eq!(guest.unwrap().unwrap().role, Role::Guest);
+        let taken = auth.create_user("admin", "password", Role::Member).await;
+        assert!(taken.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn session_round_trip() {
+        let (_db, auth) = auth().await;
+        let admin = auth
+            .create_first_user("admin", "password")
+            .await
+            .unwrap()
+            .unwrap();
+        let session = auth.session(&admin);
+        assert_eq!(auth.user_of_session(&session).await.unwrap(), Some(admin));
+    }
+
+    #[tokio::test]
+    async fn logout_ends_all_sessions() {
+        let (_db, auth) = auth().await;
+        let admin = auth
+            .create_first_user("admin", "password")
+            .await
+            .unwrap()
+            .unwrap();
+        let (first, second) = (auth.session(&admin), auth.session(&admin));
+        auth.logout(&first).await.unwrap();
+        assert_eq!(auth.user_of_session(&first).await.unwrap(), None);
+        assert_eq!(auth.user_of_session(&second).await.unwrap(), None);
+
+        let admin = auth.login("admin", "password").await.unwrap().unwrap();
+        let session = auth.session(&admin);
+        assert_eq!(auth.user_of_session(&session).await.unwrap(), Some(admin));
+    }
+
+    #[tokio::test]
+    async fn key_survives_restarts() {
+        let dir = TempDir::new("key");
+        let path = dir.join("session.key");
+        let first = load_key(&path).await.unwrap();
+        assert_eq!(load_key(&path).await.unwrap(), first);
+        assert_eq!(first.len(), SESSION_KEY_LEN);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        std::fs::write(&path, "abc").unwrap();
+        assert!(load_key(&path).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn invalid_sessions() {
+        let (_db, auth) = auth().await;
+        let session = auth.sign(1, 0, now() + 60);
+        let (payload, signature) = session.rsplit_once('.').unwrap();
+        let forged = format!("2{}.{}", &payload[1..], signature);
+        assert_eq!(auth.verify_session(&session), Some((1, 0)));
+        assert_eq!(auth.verify_session(&forged), None);
+        assert_eq!(auth.verify_session(&auth.sign(1, 0, now() - 1)), None);
+        assert_eq!(auth.verify_session("garbage"), None);
+    }
+
+    #[tokio::test]
+    async fn csrf_token_belongs_to_session() {
+        let (_db, auth) = auth().await;
+        let session = auth.sign(1, 0, now() + 60);
+        let other = auth.sign(2, 0, now() + 60);
+        let token = auth.csrf_token(&session);
+        assert!(auth.verify_csrf_token(&session, &token));
+        assert!(!auth.verify_csrf_token(&other, &token));
+        assert!(!auth.verify_csrf_token(&session, "00"));
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        assert_eq!(
+            decode_hex(&encode_hex(&[0, 15, 255])),
+            Some(vec![0, 15, 255])
+        );
+        assert_eq!(decode_hex("zz"), None);
+        assert_eq!(decode_hex("abc"), None);
+    }
+}
@@ -0,0 +1,201 @@
+#![allow(clippy::needless_return)]
+
+use crate::config::{LibraryConfig, ScanConfig};
+use fs::{Vfs, WalkError};
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match,
+};
+use std::{
+    io::{Error, ErrorKind},
+    path::Path,
+    sync::Arc,
+};
+
+/// rules for the entries of a directory, read before it is entered
+pub const IGNORE_FILE: &str = ".netflexignore";
+
+/// decides which entries the scan skips, ignored directories are never read
+#[derive(Clone, Debug)]
+pub struct IgnoreRules {
+    /// the patterns of the config first, the ignore file of the innermost directory last
+    matchers: Vec<Arc<Gitignore>>,
+    include_hidden: bool,
+    extensions: Arc<Vec<String>>,
+    exclude_extensions: Arc<Vec<String>>,
+}
+
+impl IgnoreRules {
+    /// the rules of the config for the library at `root`
+    pub fn new(root: &Path, scan: &ScanConfig, library: &LibraryConfig) -> Result<Self, String> {
+        let mut builder = GitignoreBuilder::new(root);
+        for pattern in &scan.ignore {
+            builder
+                .add_line(None, pattern)
+                .map_err(|e| format!("invalid ignore pattern {}: {}", pattern, e))?;
+        }
+        let global = builder.build().map_err(|e| e.to_string())?;
+        Ok(Self {
+            matchers: vec![Arc::new(global)],
+            include_hidden: scan.include_hidden,
+            extensions: Arc::new(normalize(&library.extensions)),
+            exclude_extensions: Arc::new(normalize(&library.exclude_extensions)),
+        })
+    }
+
+    /// the rules for the entries of `dir`, including the ones of its ignore file
+    ///
+    /// only a missing ignore file means there are no rules, an ignore file that can not be read
+    /// is an error because the entries it ignores are unknown
+    pub async fn enter(&self, vfs: &dyn Vfs, dir: &Path) -> Result<Self, WalkError> {
+        let path = dir.join(IGNORE_FILE);
+        let content = match vfs.read(&path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(self.clone()),
+            Err(e) => return Err(WalkError::new(&path, e)),
+        };
+        let content = String::from_utf8(content)
+            .map_err(|e| WalkError::new(&path, Error::new(ErrorKind::InvalidData, e)))?;
+        let mut builder = GitignoreBuilder::new(dir);
+        for line in content.lines() {
+            // like git, an invalid line does not invalidate the others
+            let _ = builder.add_line(Some(path.to_owned()), line);
+        }
+        let mut rules = self.clone();
+        if let Ok(matcher) = builder.build() {
+            rules.matchers.push(Arc::new(matcher));
+        }
+        return Ok(rules);
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        if name == IGNORE_FILE || (!self.include_hidden && name.starts_with('.')) {
+            return true;
+        }
+        if !is_dir && !self.has_allowed_extension(path) {
+            return true;
+        }
+        // the innermost ignore file wins, like in git
+        for matcher in self.matchers.iter().rev() {
+            match matcher.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        return false;
+    }
+
+    fn has_allowed_extension(&self, path: &Path) -> bool {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if self.exclude_extensions.contains(&extension) {
+            return false;
+        }
+        return self.extensions.is_empty() || self.extensions.contains(&extension);
+    }
+}
+
+/// "MP4" and ".mp4" are both "mp4"
+fn normalize(extensions: &[String]) -> Vec<String> {
+    extensions
+        .iter()
+        .map(|e| e.trim_start_matches('.').to_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rules(root: &Path, scan: ScanConfig, library: LibraryConfig) -> IgnoreRules {
+        IgnoreRules::new(root, &scan, &library).unwrap()
+    }
+
+    #[test]
+    fn defaults_skip_hidden_and_downloads() {
+        let root = Path::new("/library");
+        let rules = rules(root, ScanConfig::default(), LibraryConfig::default());
+        assert!(rules.is_ignored(&root.join(".git"), true));
+        assert!(rules.is_ignored(&root.join("a/node_modules"), true));
+        assert!(rules.is_ignored(&root.join("movie.mp4.part"), false));
+        assert!(rules.is_ignored(&root.join("Thumbs.db"), false));
+        assert!(!rules.is_ignored(&root.join("movie.mp4"), false));
+        assert!(!rules.is_ignored(&root.join("node_modules"), false));
+    }
+
+    #[test]
+    fn hidden_files_can_be_included() {
+        let root = Path::new("/library");
+        let scan = ScanConfig {
+            include_hidden: true,
+            ..Default::default()
+        };
+        let rules = rules(root, scan, LibraryConfig::default());
+        assert!(!rules.is_ignored(&root.join(".hidden.mp4"), false));
+        assert!(rules.is_ignored(&root.join(IGNORE_FILE), false));
+    }
+
+    #[test]
+    fn extension_lists() {
+        let root = Path::new("/library");
+        let library = LibraryConfig {
+            extensions: vec!["MP4".to_string(), ".mkv".to_string()],
+            exclude_extensions: vec!["mkv".to_string()],
+            ..Default::default()
+        };
+        let rules = rules(root, ScanConfig::default(), library);
+        assert!(!rules.is_ignored(&root.join("a.mp4"), false));
+        assert!(!rules.is_ignored(&root.join("b.Mp4"), false));
+        assert!(rules.is_ignored(&root.join("c.mkv"), false));
+        assert!(rules.is_ignored(&root.join("d.txt"), false));
+        // directories have no extension
+        assert!(!rules.is_ignored(&root.join("dir.txt"), true));
+    }
+
+    #[tokio::test]
+    async fn ignore_files_apply_to_their_subtree() {
+        let root = Path::new("/library");
+        let vfs = fs::Memory::new()
+            .with_dir("/library/movies/extras")
+            .with_file(
+                "/library/movies/.netflexignore",
+                "extras/\n*.nfo\n!keep.nfo\n",
+            );
+        let rules = rules(root, ScanConfig::default(), LibraryConfig::default());
+        let movies = rules.enter(&vfs, &root.join("movies")).await.unwrap();
+
+        assert!(movies.is_ignored(&root.join("movies/extras"), true));
+        assert!(movies.is_ignored(&root.join("movies/a.nfo"), false));
+        assert!(!movies.is_ignored(&root.join("movies/keep.nfo"), false));
+        // the parent is not affected
+        assert!(!rules.is_ignored(&root.join("b.nfo"), false));
+
+        let extras = movies
+            .enter(&vfs, &root.join("movies/extras"))
+            .await
+            .unwrap();
+        assert!(extras.is_ignored(&root.join("movies/extras/c.nfo"), false));
+    }
+
+    #[tokio::test]
+    async fn unreadable_ignore_file_is_error() {
+        let root = Path::new("/library");
+        let vfs = fs::Memory::new()
+            .with_dir("/library/movies")
+            .with_file("/library/movies/.netflexignore", b"\xff\xfe".to_vec());
+        let rules = rules(root, ScanConfig::default(), LibraryConfig::default());
+
+        let error = rules.enter(&vfs, &root.join("movies")).await.unwrap_err();
+        assert_eq!(error.path(), root.join("movies").join(IGNORE_FILE));
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        // without an ignore file there are no further rules
+        assert!(rules.enter(&vfs, &root.join("other")).await.is_ok());
+    }
+}
@@ -0,0 +1,356 @@
+#![allow(clippy::needless_return)]
+
+use super::IgnoreRules;
+use crate::{
+    config::{LibraryConfig, ScanConfig, SymlinkPolicy},
+    entities::Role,
+};
+use ::fs::{Vfs, WalkOptions};
+use std::{
+    io,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+/// a directory of the config, its root is canonical
+#[derive(Clone, Debug)]
+pub struct Library {
+    vfs: Arc<dyn Vfs>,
+    root: PathBuf,
+    symlinks: SymlinkPolicy,
+    rules: IgnoreRules,
+    walk: WalkOptions,
+    guests: bool,
+    /// canonical paths of files that are never indexed or served, like the database
+    protected: Arc<Vec<PathBuf>>,
+}
+
+impl Library {
+    pub async fn open(
+        vfs: Arc<dyn Vfs>,
+        config: &LibraryConfig,
+        scan: &ScanConfig,
+        protected: Arc<Vec<PathBuf>>,
+    ) -> Result<Self, String> {
+        if config.path.is_empty() {
+            return Err("a library has no path".to_string());
+        }
+        let opened = async {
+            let root = vfs.canonicalize(Path::new(&config.path)).await?;
+            let is_dir = vfs.stat(&root).await?.is_dir;
+            Ok::<_, io::Error>((root, is_dir))
+        };
+        let (root, is_dir) = opened
+            .await
+            .map_err(|e| format!("library {} can not be opened: {}", config.path, e))?;
+        if !is_dir {
+            return Err(format!("library {} is not a directory", config.path));
+        }
+        Ok(Self {
+            vfs,
+            rules: IgnoreRules::new(&root, scan, config)?,
+            root,
+            symlinks: config.symlinks,
+            walk: WalkOptions {
+                follow_symlinks: config.symlinks != SymlinkPolicy::Skip,
+                max_depth: config.max_depth,
+                same_filesystem: config.same_filesystem,
+                ..Default::default()
+            },
+            guests: config.guests,
+            protected,
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// the file system the library is read from
+    pub fn vfs(&self) -> &Arc<dyn Vfs> {
+        &self.vfs
+    }
+
+    pub fn symlinks(&self) -> SymlinkPolicy {
+        self.symlinks
+    }
+
+    /// guests see the files of the library
+    pub fn guests(&self) -> bool {
+        self.guests
+    }
+
+    /// the rules of the config, without the ones of the ignore files
+    pub fn rules(&self) -> &IgnoreRules {
+        &self.rules
+    }
+
+    /// which directories the scan enters
+    pub fn walk_options(&self) -> WalkOptions {
+        self.walk
+    }
+
+    /// false for symlinks the policy does not allow, `path` is an entry found by the scan
+    pub async fn admits(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        let metadata = match self.vfs.stat(path).await {
+            Ok(metadata) => metadata,
+            Err(_) => return false,
+        };
+        if !metadata.is_symlink {
+            return true;
+        }
+        let target = match self.vfs.canonicalize(path).await {
+            Ok(target) => target,
+            Err(_) => return false,
+        };
+        return match self.symlinks {
+            SymlinkPolicy::Skip => false,
+            SymlinkPolicy::WithinRoot => target.starts_with(&self.root) && !metadata.is_dir,
+            // the scan enters every directory only once, even if links form a cycle
+            SymlinkPolicy::Follow => true,
+        };
+    }
+
+    /// true for the database and the config, `path` is an entry found by the scan
+    pub async fn is_protected(&self, path: impl AsRef<Path>, is_symlink: bool) -> bool {
+        let path = path.as_ref();
+        let named = self
+            .protected
+            .iter()
+            .any(|p| p.file_name() == path.file_name());
+        // only links lead to a protected file under another name
+        if !named && !is_symlink && self.symlinks != SymlinkPolicy::Follow {
+            return false;
+        }
+        return match self.vfs.canonicalize(path).await {
+            Ok(canonical) => self.protected.contains(&canonical),
+            Err(_) => true,
+        };
+    }
+}
+
+/// the libraries of the config, every indexed and served path has to be inside of one
+#[derive(Clone, Debug)]
+pub struct Libraries {
+    vfs: Arc<dyn Vfs>,
+    libraries: Arc<Vec<Library>>,
+}
+
+impl Libraries {
+    /// the `protected` files are left out of every library
+    pub async fn open(
+        vfs: Arc<dyn Vfs>,
+        configs: &[LibraryConfig],
+        scan: &ScanConfig,
+        protected: &[PathBuf],
+    ) -> Result<Self, String> {
+        let mut canonical = Vec::with_capacity(protected.len());
+        for path in protected {
+            canonical.push(canonicalize_file(path).await);
+        }
+        let protected = Arc::new(canonical);
+        let mut libraries = Vec::with_capacity(configs.len());
+        for config in configs {
+            let library = Library::open(vfs.clone(), config, scan, protected.clone()).await?;
+            libraries.push(library);
+        }
+        Ok(Self {
+            vfs,
+            libraries: Arc::new(libraries),
+        })
+    }
+
+    pub fn all(&self) -> &[Library] {
+        &self.libraries
+    }
+
+    /// the file system the files of the libraries are read from
+    pub fn vfs(&self) -> &Arc<dyn Vfs> {
+        &self.vfs
+    }
+
+    /// the roots of the libraries whose files `role` may see, encoded like the paths of the
+    /// database and ending with a separator. `None` if the role sees every file
+    pub fn visible_roots(&self, role: Role) -> Option<Vec<String>> {
+        if role > Role::Guest {
+            return None;
+        }
+        let roots = self
+            .libraries
+            .iter()
+            .filter(|l| l.guests)
+            .map(|l| {
+                let mut root = ::fs::encode_path(&l.root);
+                if !root.ends_with(std::path::MAIN_SEPARATOR) {
+                    root.push(std::path::MAIN_SEPARATOR);
+                }
+                root
+            })
+            .collect();
+        return Some(roots);
+    }
+
+    /// the canonical path of a file of the catalog, `path` is encoded like in the database
+    ///
+    /// fails with `PermissionDenied` if the path or the target of a symlink in it is outside of
+    /// the libraries, relative paths of older scans are relative to the working directory
+    pub async fn resolve(&self, path: &str) -> io::Result<PathBuf> {
+        let path = std::path::absolute(::fs::decode_path(path))?;
+        if path.components().any(|c| c == Component::ParentDir) {
+            return Err(outside(&path));
+        }
+        let library = self
+            .libraries
+            .iter()
+            .find(|l| path.starts_with(&l.root))
+            .ok_or_else(|| outside(&path))?;
+
+        let canonical = self.vfs.canonicalize(&path).await?;
+        if library.symlinks != SymlinkPolicy::Follow && !canonical.starts_with(&library.root) {
+            return Err(outside(&path));
+        }
+        if library.protected.contains(&canonical) {
+            return Err(outside(&path));
+        }
+        return Ok(canonical);
+    }
+}
+
+/// the journals of a database may not exist yet, then only their directory is canonical
+async fn canonicalize_file(path: &Path) -> PathBuf {
+    if let Ok(canonical) = tokio::fs::canonicalize(path).await {
+        return canonical;
+    }
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_owned());
+    let dir = match (path.parent(), path.file_name()) {
+        (Some(dir), Some(name)) => tokio::fs::canonicalize(dir).await.map(|d| d.join(name)),
+        _ => return path,
+    };
+    return dir.unwrap_or(path);
+}
+
+fn outside(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!("{} is outside of the libraries", path.display()),
+    )
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use testing::TempDir;
+
+    /// "<tmp>/library" with "video.mp4" and the protected "db.sqlite", and "<tmp>/secret.txt"
+    /// outside of it
+    fn setup(name: &str) -> TempDir {
+        let dir = TempDir::new(&format!("library-{}", name));
+        std::fs::create_dir_all(dir.join("library/dir")).unwrap();
+        std::fs::write(dir.join("library/video.mp4"), b"video").unwrap();
+        std::fs::write(dir.join("secret.txt"), b"secret").unwrap();
+        std::fs::write(dir.join("library/db.sqlite"), b"database").unwrap();
+        symlink(dir.join("library/db.sqlite"), dir.join("library/db.mp4")).unwrap();
+        symlink(dir.join("secret.txt"), dir.join("library/escape.txt")).unwrap();
+        symlink(
+            dir.join("library/video.mp4"),
+            dir.join("library/dir/video.mp4"),
+        )
+        .unwrap();
+        return dir;
+    }
+
+    async fn libraries(dir: &Path, symlinks: SymlinkPolicy) -> Libraries {
+        let config = LibraryConfig {
+            path: dir.join("library").to_string_lossy().to_string(),
+            symlinks,
+            ..Default::default()
+        };
+        let protected = [dir.join("library/db.sqlite")];
+        Libraries::open(
+            Arc::new(::fs::Local),
+            &[config],
+            &ScanConfig::default(),
+            &protected,
+        )
+        .await
+        .unwrap()
+    }
+
+    fn path(dir: &Path, relative: &str) -> String {
+        let root = std::fs::canonicalize(dir).unwrap();
+        root.join(relative).to_string_lossy().to_string()
+    }
+
+    #[tokio::test]
+    async fn files_inside_are_resolved() {
+        let dir = setup("inside");
+        let libraries = libraries(&dir, SymlinkPolicy::WithinRoot).await;
+        let video = libraries.resolve(&path(&dir, "library/video.mp4")).await;
+        assert!(video.is_ok());
+        let link = libraries
+            .resolve(&path(&dir, "library/dir/video.mp4"))
+            .await;
+        assert_eq!(link.unwrap(), video.unwrap());
+    }
+
+    #[tokio::test]
+    async fn paths_outside_are_denied() {
+        let dir = setup("outside");
+        let libraries = libraries(&dir, SymlinkPolicy::WithinRoot).await;
+        for denied in ["secret.txt", "library/escape.txt", "library/../secret.txt"] {
+            let error = libraries.resolve(&path(&dir, denied)).await.unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::PermissionDenied, "{}", denied);
+        }
+    }
+
+    #[tokio::test]
+    async fn protected_files_are_denied() {
+        let dir = setup("protected");
+        for policy in [SymlinkPolicy::WithinRoot, SymlinkPolicy::Follow] {
+            let libraries = libraries(&dir, policy).await;
+            let library = &libraries.all()[0];
+            for denied in ["library/db.sqlite", "library/db.mp4"] {
+                let path = path(&dir, denied);
+                let error = libraries.resolve(&path).await.unwrap_err();
+                assert_eq!(error.kind(), io::ErrorKind::PermissionDenied, "{}", denied);
+                assert!(library.is_protected(&path, denied.ends_with("mp4")).await);
+            }
+            assert!(
+                !library
+                    .is_protected(path(&dir, "library/video.mp4"), false)
+                    .await
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn follow_allows_links_outside() {
+        let dir = setup("follow");
+        let libraries = libraries(&dir, SymlinkPolicy::Follow).await;
+        assert!(libraries
+            .resolve(&path(&dir, "library/escape.txt"))
+            .await
+            .is_ok());
+        let library = &libraries.all()[0];
+        assert!(library.admits(&path(&dir, "library/escape.txt")).await);
+    }
+
+    #[tokio::test]
+    async fn scan_admits_by_policy() {
+        let dir = setup("admits");
+        let within = libraries(&dir, SymlinkPolicy::WithinRoot).await;
+        let within = &within.all()[0];
+        assert!(within.admits(&path(&dir, "library/video.mp4")).await);
+        assert!(within.admits(&path(&dir, "library/dir/video.mp4")).await);
+        assert!(!within.admits(&path(&dir, "library/escape.txt")).await);
+
+        let skip = libraries(&dir, SymlinkPolicy::Skip).await;
+        assert!(
+            !skip.all()[0]
+                .admits(&path(&dir, "library/dir/video.mp4"))
+                .await
+        );
+    }
+}
@@ -0,0 +1,128 @@
+#![allow(clippy::needless_return)]
+
+use crate::repositories::SearchMetadata;
+use fs::Vfs;
+use lofty::{file::TaggedFileExt, tag::Accessor};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::path::PathBuf;
+use tokio::task;
+
+/// words of release names that are not part of the title, everything after them is removed
+const RELEASE_TAGS: &[&str] = &[
+    "2160p", "1080p", "1080i", "720p", "576p", "480p", "4k", "uhd", "hdr", "x264", "x265", "h264",
+    "h265", "hevc", "xvid", "divx", "bluray", "blu", "brrip", "bdrip", "webrip", "web", "webdl",
+    "hdtv", "dvdrip", "dvd", "remux", "10bit", "aac", "ac3", "dts", "proper", "repack",
+];
+
+static REGEX_BRACKETS: Lazy<Regex> = Lazy::new(|| Regex::new(r"\[[^\]]*\]|\{[^}]*\}").unwrap());
+static REGEX_PLOT: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<plot>(.*?)</plot>").unwrap());
+
+/// collects the searchable text of a file, missing tags or ".nfo" files are ignored
+pub async fn read(vfs: &dyn Vfs, file: &fs::File, mime: &str) -> SearchMetadata {
+    let mut metadata = SearchMetadata {
+        title: clean_title(&file.name()),
+        ..Default::default()
+    };
+    // tags are only read from files on the disk
+    if let (true, Some(path)) = (mime.starts_with("audio/"), vfs.local_path(file.path())) {
+        metadata.tags = audio_tags(path).await;
+    }
+    if mime.starts_with("video/") {
+        metadata.plot = nfo_plot(vfs, file).await;
+    }
+    return metadata;
+}
+
+/// "Toy.Story.1995.1080p.BluRay.x264-GROUP" => "Toy Story 1995"
+fn clean_title(name: &str) -> String {
+    let name = REGEX_BRACKETS.replace_all(name, " ");
+    let mut words = Vec::new();
+    for word in name.split(|c: char| c == '.' || c == '_' || c.is_whitespace()) {
+        let normalized = word.to_lowercase().replace('-', "");
+        if RELEASE_TAGS.contains(&normalized.as_str()) {
+            break;
+        }
+        if !word.is_empty() {
+            words.push(word);
+        }
+    }
+    return words.join(" ");
+}
+
+async fn audio_tags(path: PathBuf) -> String {
+    let read = task::spawn_blocking(move || {
+        let tagged = lofty::read_from_path(path).ok()?;
+        let tag = tagged.primary_tag().or_else(|| tagged.first_tag())?;
+        let values = [tag.title(), tag.artist(), tag.album(), tag.genre()];
+        let values: Vec<String> = values.iter().flatten().map(|v| v.to_string()).collect();
+        Some(values.join(" "))
+    });
+    return read.await.ok().flatten().unwrap_or_default();
+}
+
+/// kodi writes the plot of "movie.mkv" to "movie.nfo" or "movie.nfo" of the directory
+async fn nfo_plot(vfs: &dyn Vfs, file: &fs::File) -> String {
+    for nfo in [
+        file.path().with_extension("nfo"),
+        file.path_of_dir().join("movie.nfo"),
+    ] {
+        if let Ok(Ok(content)) = vfs.read(&nfo).await.map(String::from_utf8) {
+            if let Some(plot) = REGEX_PLOT.captures(&content) {
+                return unescape_xml(plot[1].trim());
+            }
+        }
+    }
+    return String::new();
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn title_without_release_tags() {
+        assert_eq!(
+            clean_title("Toy.Story.1995.1080p.BluRay.x264-GROUP"),
+            "Toy Story 1995"
+        );
+        assert_eq!(
+            clean_title("[group] My_Show S01E02 WEB-DL"),
+            "My Show S01E02"
+        );
+        assert_eq!(clean_title("toystory"), "toystory");
+    }
+
+    #[tokio::test]
+    async fn tags_of_mp3() {
+        let tags = audio_tags("./tests/data/music.mp3".into()).await;
+        assert!(tags.contains("Kevin MacLeod"), "{}", tags);
+    }
+
+    #[tokio::test]
+    async fn tags_of_file_without_tags() {
+        let tags = audio_tags("./tests/data/test-file.txt".into()).await;
+        assert!(tags.is_empty());
+    }
+
+    #[tokio::test]
+    async fn plot_of_nfo() {
+        let vfs = fs::Memory::new()
+            .with_file("/movies/movie.mkv", "")
+            .with_file(
+                "/movies/movie.nfo",
+                "<movie><title>T</title><plot>Toys &amp; a cowboy.</plot></movie>",
+            );
+
+        let file = fs::File::new("/movies/movie.mkv", 0);
+        assert_eq!(nfo_plot(&vfs, &file).await, "Toys & a cowboy.");
+    }
+}
@@ -1,12 +1,62 @@
+mod auth;
+mod ignore_rules;
+mod library;
+mod metadata;
+mod shutdown;
+mod streams;
+mod transcoder;
 mod updater;
-pub use updater::UpdateService;
+pub use auth::{AuthService, SESSION_COOKIE, SESSION_LIFETIME};
+pub use ignore_rules::{IgnoreRules, IGNORE_FILE};
+pub use library::{Libraries, Library};
+pub use shutdown::Shutdown;
+pub use streams::{ActiveStream, Slot, StreamService, StreamStatus};
+pub use transcoder::{ExternalTranscoder, TranscodeError, TranscodeService, TranscodeStream};
+pub(crate) use updater::calc_group_id;
+pub use updater::{ScanRefused, UpdateService, UpdateStatus};
 
 use axum::{AddExtensionLayer, Router};
 use sea_orm::DatabaseConnection;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use crate::repositories::FileRepository;
-pub fn setup(router: Router, db: &DatabaseConnection) -> Router {
-    router.layer(AddExtensionLayer::new(UpdateService::new(
-        FileRepository::new(db.clone()),
-    )))
+use crate::config::Config;
+use crate::repositories::{FileRepository, UserRepository};
+pub async fn setup(
+    router: Router,
+    db: &DatabaseConnection,
+    config: &Config,
+    protected: Vec<PathBuf>,
+    shutdown: &Shutdown,
+) -> Result<Router, String> {
+    if config.libraries.is_empty() {
+        return Err(
+            "no library is configured, add a [[libraries]] section with a path".to_string(),
+        );
+    }
+    let transcoder = ExternalTranscoder::new(&config.transcoder);
+    let vfs: Arc<dyn fs::Vfs> = match config.scan.archives {
+        true => Arc::new(fs::Archives::new(Arc::new(fs::Local))),
+        false => Arc::new(fs::Local),
+    };
+    let libraries = Libraries::open(vfs, &config.libraries, &config.scan, &protected).await?;
+    let key_file = Path::new(&config.auth.key_file);
+    let auth = AuthService::load(UserRepository::new(db.clone()), key_file).await?;
+    let router = router
+        .layer(AddExtensionLayer::new(auth))
+        .layer(AddExtensionLayer::new(UpdateService::new(
+            FileRepository::new(db.clone()),
+            libraries.clone(),
+            shutdown.clone(),
+        )))
+        .layer(AddExtensionLayer::new(libraries))
+        .layer(AddExtensionLayer::new(shutdown.clone()))
+        .layer(AddExtensionLayer::new(TranscodeService::new(
+            Arc::new(transcoder),
+            &config.transcoder,
+        )))
+        .layer(AddExtensionLayer::new(StreamService::new(
+            &config.streaming,
+        )));
+    Ok(router)
 }
@@ -0,0 +1,115 @@
+#![allow(clippy::needless_return)]
+
+use crate::database::Pool;
+use std::{future::Future, sync::Arc};
+use tokio::sync::{OwnedRwLockReadGuard, RwLock};
+use tokio_util::sync::CancellationToken;
+
+/// coordinates the graceful shutdown, triggered by the settings page or a signal
+#[derive(Clone)]
+pub struct Shutdown {
+    token: CancellationToken,
+    /// background tasks hold a read guard until they stopped at a safe point
+    tasks: Arc<RwLock<()>>,
+    pool: Pool,
+}
+
+/// keeps `Shutdown::finish` waiting while it exists
+pub type TaskGuard = OwnedRwLockReadGuard<()>;
+
+impl Shutdown {
+    pub fn new(pool: Pool) -> Self {
+        Self {
+            token: CancellationToken::new(),
+            tasks: Arc::new(RwLock::new(())),
+            pool,
+        }
+    }
+
+    /// calling it again has no effect
+    pub fn trigger(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// completes once `trigger` was called
+    pub fn triggered(&self) -> impl Future<Output = ()> + Send + 'static {
+        let token = self.token.clone();
+        async move { token.cancelled().await }
+    }
+
+    /// None if the shutdown was already triggered
+    pub async fn task(&self) -> Option<TaskGuard> {
+        if self.is_triggered() {
+            return None;
+        }
+        let guard = self.tasks.clone().read_owned().await;
+        // `finish` may have been called while waiting for the guard
+        if self.is_triggered() {
+            return None;
+        }
+        return Some(guard);
+    }
+
+    /// triggers the shutdown, waits for the background tasks and closes the database connections
+    pub async fn finish(&self) {
+        self.trigger();
+        let _no_tasks = self.tasks.write().await;
+        self.pool.close().await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::database;
+    use std::time::Duration;
+
+    async fn shutdown() -> Shutdown {
+        let (_, pool) = database::open("sqlite::memory:").await.unwrap();
+        return Shutdown::new(pool);
+    }
+
+    #[tokio::test]
+    async fn finish_waits_for_tasks() {
+        let shutdown = shutdown().await;
+        let task = shutdown.task().await.unwrap();
+
+        let finish = shutdown.clone();
+        let finish = tokio::spawn(async move { finish.finish().await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(shutdown.is_triggered());
+        assert!(!finish.is_finished());
+
+        drop(task);
+        tokio::time::timeout(Duration::from_secs(1), finish)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn no_tasks_after_trigger() {
+        let shutdown = shutdown().await;
+        shutdown.trigger();
+        shutdown.triggered().await;
+        assert!(shutdown.task().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn no_task_if_triggered_while_waiting() {
+        let shutdown = shutdown().await;
+        let write = shutdown.tasks.clone().write_owned().await;
+
+        let waiting = shutdown.clone();
+        let waiting = tokio::spawn(async move { waiting.task().await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        shutdown.trigger();
+        drop(write);
+
+        assert!(waiting.await.unwrap().is_none());
+    }
+}
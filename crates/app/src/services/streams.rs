@@ -0,0 +1,216 @@
+#![allow(clippy::needless_return)]
+
+use crate::config::StreamingConfig;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// keeps track of the responses that are currently streaming files
+#[derive(Clone)]
+pub struct StreamService {
+    config: StreamingConfig,
+    /// None if the number of active streams is unlimited
+    slots: Option<Arc<Semaphore>>,
+    active: Arc<Mutex<HashMap<u64, Arc<Stats>>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl StreamService {
+    pub fn new(config: &StreamingConfig) -> Self {
+        let slots = match config.max_active {
+            0 => None,
+            max => Some(Arc::new(Semaphore::new(max))),
+        };
+
+        Self {
+            config: config.to_owned(),
+            slots,
+            active: Arc::new(Mutex::new(HashMap::new())),
+            next_id: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// returns None if the maximum number of active streams is reached
+    pub fn reserve(&self) -> Option<Slot> {
+        let permit = match &self.slots {
+            Some(slots) => Some(slots.clone().try_acquire_owned().ok()?),
+            None => None,
+        };
+        Some(Slot { _permit: permit })
+    }
+
+    /// seconds until a client should try again if `reserve` failed
+    pub fn retry_after(&self) -> u64 {
+        self.config.retry_after
+    }
+
+    /// registers a stream, it is removed as soon as the returned value is dropped
+    pub fn start(&self, slot: Slot, client: String, file: String) -> ActiveStream {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let stats = Arc::new(Stats {
+            client,
+            file,
+            started: Instant::now(),
+            sent: AtomicU64::new(0),
+        });
+        self.lock().insert(id, stats.clone());
+
+        ActiveStream {
+            id,
+            stats,
+            rate_limit: match self.config.rate_limit {
+                0 => None,
+                limit => Some(limit),
+            },
+            streams: self.clone(),
+            _slot: slot,
+        }
+    }
+
+    /// all active streams, the oldest first
+    pub fn active(&self) -> Vec<StreamStatus> {
+        let mut active: Vec<(u64, StreamStatus)> = self
+            .lock()
+            .iter()
+            .map(|(id, stats)| (*id, StreamStatus::from(stats.as_ref())))
+            .collect();
+        active.sort_by_key(|(id, _)| *id);
+        return active.into_iter().map(|(_, status)| status).collect();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, Arc<Stats>>> {
+        // the map stays consistent even if a thread panicked while holding the lock
+        self.active.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// a reserved place in the limit of active streams
+pub struct Slot {
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+struct Stats {
+    client: String,
+    file: String,
+    started: Instant,
+    sent: AtomicU64,
+}
+
+pub struct ActiveStream {
+    id: u64,
+    stats: Arc<Stats>,
+    rate_limit: Option<u64>,
+    streams: StreamService,
+    _slot: Slot,
+}
+
+impl ActiveStream {
+    /// bytes per second
+    pub fn rate_limit(&self) -> Option<u64> {
+        self.rate_limit
+    }
+
+    pub fn add_sent(&self, bytes: u64) {
+        self.stats.sent.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
+
+impl Drop for ActiveStream {
+    fn drop(&mut self) {
+        self.streams.lock().remove(&self.id);
+    }
+}
+
+pub struct StreamStatus {
+    pub client: String,
+    pub file: String,
+    /// bytes
+    pub sent: u64,
+    /// seconds
+    pub duration: f64,
+}
+
+impl StreamStatus {
+    /// average bytes per second since the stream started
+    pub fn throughput(&self) -> u64 {
+        if self.duration <= 0.0 {
+            return 0;
+        }
+        (self.sent as f64 / self.duration) as u64
+    }
+}
+
+impl From<&Stats> for StreamStatus {
+    fn from(stats: &Stats) -> Self {
+        Self {
+            client: stats.client.to_owned(),
+            file: stats.file.to_owned(),
+            sent: stats.sent.load(Ordering::Relaxed),
+            duration: stats.started.elapsed().as_secs_f64(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn service(max_active: usize) -> StreamService {
+        StreamService::new(&StreamingConfig {
+            max_active,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn limit_of_active_streams() {
+        let streams = service(1);
+        let slot = streams.reserve().unwrap();
+        assert!(streams.reserve().is_none());
+
+        drop(slot);
+        assert!(streams.reserve().is_some());
+    }
+
+    #[test]
+    fn unlimited_streams() {
+        let streams = service(0);
+        let slots: Vec<Slot> = (0..100).filter_map(|_| streams.reserve()).collect();
+        assert_eq!(slots.len(), 100);
+    }
+
+    #[test]
+    fn active_streams_are_listed_until_dropped() {
+        let streams = service(0);
+        let first = streams.start(streams.reserve().unwrap(), "client".into(), "a".into());
+        let second = streams.start(streams.reserve().unwrap(), "client".into(), "b".into());
+        first.add_sent(10);
+
+        let active = streams.active();
+        assert_eq!(active.len(), 2);
+        assert_eq!(active[0].file, "a");
+        assert_eq!(active[0].sent, 10);
+
+        drop(first);
+        let active = streams.active();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].file, "b");
+        drop(second);
+    }
+
+    #[test]
+    fn stream_keeps_slot() {
+        let streams = service(1);
+        let active = streams.start(streams.reserve().unwrap(), "c".into(), "f".into());
+        assert!(streams.reserve().is_none());
+
+        drop(active);
+        assert!(streams.reserve().is_some());
+    }
+}
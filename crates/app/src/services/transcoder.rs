@@ -0,0 +1,251 @@
+#![allow(clippy::needless_return)]
+
+use crate::config::TranscoderConfig;
+use crate::entities::File;
+use axum::{async_trait, body::Bytes};
+use futures::Stream;
+use std::{
+    ffi::{OsStr, OsString},
+    fmt, io,
+    pin::Pin,
+    process::Stdio,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tokio::{
+    process::{Child, ChildStdout, Command},
+    sync::{OwnedSemaphorePermit, Semaphore},
+};
+use tokio_util::io::ReaderStream;
+
+const INPUT_PLACEHOLDER: &str = "{input}";
+
+/// converts a file into a format browsers can play
+#[async_trait]
+pub trait Transcoder: Send + Sync {
+    /// mime of the transcoded output
+    fn mime(&self) -> String;
+
+    async fn transcode(&self, file: &File) -> Result<TranscodeStream, String>;
+}
+
+/// transcodes by spawning an external program (e.g. ffmpeg) and reading its stdout
+pub struct ExternalTranscoder {
+    command: Vec<String>,
+    mime: String,
+}
+
+impl ExternalTranscoder {
+    pub fn new(config: &TranscoderConfig) -> Self {
+        Self {
+            command: config.command.to_owned(),
+            mime: config.mime.to_owned(),
+        }
+    }
+}
+
+#[async_trait]
+impl Transcoder for ExternalTranscoder {
+    fn mime(&self) -> String {
+        self.mime.to_owned()
+    }
+
+    async fn transcode(&self, file: &File) -> Result<TranscodeStream, String> {
+        let (program, args) = self
+            .command
+            .split_first()
+            .ok_or_else(|| "transcoder command is empty".to_string())?;
+        let input = fs::decode_path(&file.path);
+        let args = args.iter().map(|a| with_input(a, input.as_os_str()));
+
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("could not start transcoder '{}': {}", program, e))?;
+
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| "transcoder has no stdout".to_string())?;
+
+        Ok(TranscodeStream {
+            output: ReaderStream::new(stdout),
+            _child: child,
+            _permit: None,
+        })
+    }
+}
+
+/// output of a running transcoding
+/// the process is killed when the stream is dropped (e.g. the client disconnects)
+pub struct TranscodeStream {
+    output: ReaderStream<ChildStdout>,
+    _child: Child,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl TranscodeStream {
+    /// the slot is released when the transcoding ends
+    fn keep_slot(mut self, permit: OwnedSemaphorePermit) -> Self {
+        self._permit = Some(permit);
+        self
+    }
+}
+
+impl Stream for TranscodeStream {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.output).poll_next(cx)
+    }
+}
+
+/// why a transcoding did not start
+#[derive(Debug)]
+pub enum TranscodeError {
+    /// all slots are taken, the client should retry after this many seconds
+    Busy(u64),
+    Failed(String),
+}
+
+impl fmt::Display for TranscodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranscodeError::Busy(_) => {
+                write!(f, "too many transcodings are running, try again later")
+            }
+            TranscodeError::Failed(m) => write!(f, "{}", m),
+        }
+    }
+}
+
+/// limits the number of transcodings running at the same time
+#[derive(Clone)]
+pub struct TranscodeService {
+    transcoder: Arc<dyn Transcoder>,
+    slots: Arc<Semaphore>,
+    retry_after: u64,
+}
+
+impl TranscodeService {
+    pub fn new(transcoder: Arc<dyn Transcoder>, config: &TranscoderConfig) -> Self {
+        Self {
+            transcoder,
+            slots: Arc::new(Semaphore::new(config.max_concurrent)),
+            retry_after: config.retry_after,
+        }
+    }
+
+    pub fn mime(&self) -> String {
+        self.transcoder.mime()
+    }
+
+    /// fails immediately if the maximum number of transcodings is reached
+    pub async fn transcode(&self, file: &File) -> Result<TranscodeStream, TranscodeError> {
+        let permit = self
+            .slots
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| TranscodeError::Busy(self.retry_after))?;
+
+        let stream = self
+            .transcoder
+            .transcode(file)
+            .await
+            .map_err(TranscodeError::Failed)?;
+        return Ok(stream.keep_slot(permit));
+    }
+}
+
+/// replaces the placeholder without converting the path to text
+fn with_input(arg: &str, input: &OsStr) -> OsString {
+    let mut parts = arg.split(INPUT_PLACEHOLDER);
+    let mut output = OsString::from(parts.next().unwrap_or_default());
+    for part in parts {
+        output.push(input);
+        output.push(part);
+    }
+    return output;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::TryStreamExt;
+
+    const STUB: &str = "./tests/bin/transcoder-stub.sh";
+    const INPUT: &str = "./tests/data/music.mp3";
+
+    fn stub_config() -> TranscoderConfig {
+        TranscoderConfig {
+            command: vec!["sh".to_string(), STUB.to_string(), "{input}".to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn input_file() -> File {
+        File {
+            id: 1,
+            name: "music".to_string(),
+            path: INPUT.to_string(),
+            mime: "audio/mpeg".to_string(),
+            size: 0,
+            group_id: "1".to_string(),
+            group_member_name: "music.mp3".to_string(),
+            added: 0,
+        }
+    }
+
+    fn service(config: &TranscoderConfig, max_concurrent: usize) -> TranscodeService {
+        let config = TranscoderConfig {
+            max_concurrent,
+            retry_after: 7,
+            ..config.to_owned()
+        };
+        TranscodeService::new(Arc::new(ExternalTranscoder::new(&config)), &config)
+    }
+
+    #[tokio::test]
+    async fn pipes_output_of_command() {
+        let service = service(&stub_config(), 1);
+        let stream = service.transcode(&input_file()).await.unwrap();
+        let output: Vec<Bytes> = stream.try_collect().await.unwrap();
+
+        let expected = tokio::fs::read(INPUT).await.unwrap();
+        assert_eq!(output.concat(), expected);
+    }
+
+    #[tokio::test]
+    async fn concurrency_limit() {
+        let service = service(&stub_config(), 1);
+        let running = service.transcode(&input_file()).await.unwrap();
+
+        let busy = service.transcode(&input_file()).await;
+        assert!(matches!(busy, Err(TranscodeError::Busy(7))));
+        drop(running);
+        assert!(service.transcode(&input_file()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn missing_program_is_error() {
+        let config = TranscoderConfig {
+            command: vec!["./tests/bin/not_found".to_string()],
+            ..Default::default()
+        };
+        assert!(service(&config, 1).transcode(&input_file()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn failed_start_releases_slot() {
+        let config = TranscoderConfig {
+            command: vec![],
+            ..Default::default()
+        };
+        let service = service(&config, 1);
+        assert!(service.transcode(&input_file()).await.is_err());
+        assert_eq!(service.slots.available_permits(), 1);
+    }
+}
@@ -1,211 +1,503 @@
+#![allow(clippy::needless_return)]
+
 use std::{
-    collections::hash_map::DefaultHasher,
+    collections::{hash_map::DefaultHasher, HashMap},
     fmt::Display,
     hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
+use super::{metadata, IgnoreRules, Libraries, Library, Shutdown};
 use crate::repositories::{FileRepository, InsertFile};
-use futures::future::join_all;
-use once_cell::sync::Lazy;
+use futures::{future::join_all, StreamExt};
+use serde::Serialize;
 use tokio::{sync::Mutex, task};
+use utoipa::ToSchema;
 
-static UPDATE_LOCK: Lazy<Mutex<u8>> = Lazy::new(|| Mutex::new(0));
+/// files are inserted in chunks of this size
+const INSERT_BATCH: usize = 1024;
 
 #[derive(Clone)]
 pub struct UpdateService {
     files: FileRepository,
+    libraries: Libraries,
+    status: Arc<std::sync::Mutex<UpdateStatus>>,
+    /// held while a scan is running, the database of an app is scanned once at a time
+    lock: Arc<Mutex<()>>,
+    shutdown: Shutdown,
+}
+
+#[derive(Clone, Debug, Default, Serialize, ToSchema)]
+pub struct UpdateStatus {
+    pub running: bool,
+    /// seconds since the unix epoch
+    pub last_started: Option<u64>,
+    /// seconds since the unix epoch
+    pub last_finished: Option<u64>,
+    /// errors of the last run
+    pub errors: Vec<String>,
+}
+
+/// why a scan was not started
+#[derive(Debug, PartialEq)]
+pub enum ScanRefused {
+    Running,
+    ShuttingDown,
+}
+
+impl Display for ScanRefused {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScanRefused::Running => write!(f, "a scan is already running"),
+            ScanRefused::ShuttingDown => write!(f, "the server is shutting down"),
+        }
+    }
 }
 
 impl UpdateService {
-    pub fn new(files: FileRepository) -> Self {
-        Self { files }
+    pub fn new(files: FileRepository, libraries: Libraries, shutdown: Shutdown) -> Self {
+        Self {
+            files,
+            libraries,
+            status: Default::default(),
+            lock: Default::default(),
+            shutdown,
+        }
     }
 
-    pub async fn run(self, path: String) -> Result<(), String> {
-        let lock = UPDATE_LOCK.try_lock().map_err(|e| e.to_string())?;
+    pub fn is_running(&self) -> bool {
+        self.lock.try_lock().is_err()
+    }
+
+    pub fn status(&self) -> UpdateStatus {
+        let mut status = self.lock_status().clone();
+        status.running = self.is_running();
+        return status;
+    }
+
+    /// starts a scan of every library in the background, it stops before the old files are deleted
+    /// if the server shuts down, once they are deleted the shutdown waits until all are inserted again
+    pub async fn run(self) -> Result<(), ScanRefused> {
+        let lock = self
+            .lock
+            .clone()
+            .try_lock_owned()
+            .map_err(|_| ScanRefused::Running)?;
+        let task = self
+            .shutdown
+            .task()
+            .await
+            .ok_or(ScanRefused::ShuttingDown)?;
+        self.update_status(|s| {
+            s.last_started = Some(now());
+            s.errors.clear();
+        });
+
         task::spawn(async move {
             let _keep_lock_in_scope = lock;
+            let _delay_shutdown = task;
+
+            // the files are inserted again, but keep when they were found first
+            let added = self.files.find_all_added().await;
+            self.record_error(&added, "error while reading when files were added");
+            let added = match added {
+                Ok(added) => added,
+                Err(e) => {
+                    println!("error while reading when files were added: {}", e);
+                    self.update_status(|s| s.last_finished = Some(now()));
+                    return;
+                }
+            };
 
-            let result = self.delete_old_entries(&path).await;
+            if self.shutdown.is_triggered() {
+                let message = "stopped before deleting old files: the server is shutting down";
+                println!("{}", message);
+                self.update_status(|s| {
+                    s.errors.push(message.to_string());
+                    s.last_finished = Some(now());
+                });
+                return;
+            }
+
+            // files of libraries that were removed from the config are deleted as well
+            let result = self.files.delete_all().await;
+            self.record_error(&result, "error while deleting old files");
             print_error(result, "error while deleting old files");
 
-            let result = self.find_and_insert_new_entries(path).await;
-            print_error(result, "error while inserting new files");
+            for library in self.libraries.all() {
+                let result = self.find_and_insert_new_entries(library, &added).await;
+                self.record_error(&result, "error while inserting new files");
+                print_error(result, "error while inserting new files");
+            }
+
+            self.update_status(|s| s.last_finished = Some(now()));
         });
         Ok(())
     }
 
-    async fn delete_old_entries(&self, path: &str) -> Result<(), String> {
-        self.files.delete_by_path(path).await?;
-        Ok(())
+    fn lock_status(&self) -> std::sync::MutexGuard<'_, UpdateStatus> {
+        self.status.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn update_status(&self, update: impl FnOnce(&mut UpdateStatus)) {
+        update(&mut self.lock_status());
     }
 
-    async fn find_and_insert_new_entries(self, path: String) -> Result<(), String> {
-        let mut to_check = vec![fs::Directory::new(path)];
-        while !to_check.is_empty() {
-            let content = collect_content(&to_check).await;
-            to_check = content.dirs;
-            self.files
-                .insert_all(into_file_insert(content.files))
-                .await?;
+    fn record_error<T>(&self, result: &Result<T, impl Display>, message: &str) {
+        if let Err(e) = result {
+            self.update_status(|s| s.errors.push(format!("{}: {}", message, e)));
+        }
+    }
+
+    /// files that are not in `added` are added now
+    async fn find_and_insert_new_entries(
+        &self,
+        library: &Library,
+        added: &HashMap<String, u64>,
+    ) -> Result<(), String> {
+        let now = now();
+        let mut scan = LibraryScan::new(library).await;
+        loop {
+            let scanned = scan.next_files(INSERT_BATCH).await;
+            for error in &scanned.errors {
+                println!("error while reading {}", error);
+            }
+            self.update_status(|s| {
+                let errors = scanned.errors.iter();
+                s.errors
+                    .extend(errors.map(|e| format!("error while reading {}", e)));
+            });
+            let inserts = into_file_insert(library.vfs().as_ref(), scanned.files).await;
+            let inserts = inserts.into_iter().map(|insert| InsertFile {
+                added: added.get(&insert.path).copied().unwrap_or(now),
+                ..insert
+            });
+            self.files.insert_all(inserts.collect()).await?;
+            if scanned.finished {
+                return Ok(());
+            }
         }
-        Ok(())
     }
 }
 
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
 fn print_error<T>(result: Result<T, impl Display>, message: &str) {
     if result.is_err() {
         println!("{}: {}", message, result.err().unwrap());
     }
 }
 
+/// a walk through a library that skips ignored entries and the symlinks the library does not
+/// allow, ignored directories are never read
+struct LibraryScan<'a> {
+    library: &'a Library,
+    walker: fs::Walker,
+    /// the rules for the entries of each entered directory
+    rules: HashMap<PathBuf, IgnoreRules>,
+    /// the ignore file of the root could not be read, nothing is scanned then
+    root_error: Option<fs::WalkError>,
+}
+
 #[derive(Default)]
-struct DirContent {
+struct ScannedFiles {
     files: Vec<fs::File>,
-    dirs: Vec<fs::Directory>,
+    /// directories and entries that could not be read
+    errors: Vec<fs::WalkError>,
+    /// there are no more files
+    finished: bool,
+}
+
+impl<'a> LibraryScan<'a> {
+    async fn new(library: &'a Library) -> Self {
+        let root = library.root();
+        let (rules, root_error) = match library.rules().enter(library.vfs().as_ref(), root).await {
+            Ok(rules) => (HashMap::from([(root.to_owned(), rules)]), None),
+            Err(e) => (HashMap::new(), Some(e)),
+        };
+        Self {
+            library,
+            walker: fs::Walker::new(library.vfs().clone(), root, library.walk_options()),
+            rules,
+            root_error,
+        }
+    }
+
+    /// at most `max` files
+    async fn next_files(&mut self, max: usize) -> ScannedFiles {
+        let mut output = ScannedFiles::default();
+        if let Some(error) = self.root_error.take() {
+            output.errors.push(error);
+            output.finished = true;
+            return output;
+        }
+        while output.files.len() < max {
+            let entry = match self.walker.next().await {
+                Some(Ok(entry)) => entry,
+                Some(Err(e)) => {
+                    output.errors.push(e);
+                    continue;
+                }
+                None => {
+                    output.finished = true;
+                    break;
+                }
+            };
+            let path = entry.path().to_owned();
+            let rules = path
+                .parent()
+                .and_then(|p| self.rules.get(p))
+                .unwrap_or_else(|| self.library.rules());
+            let allowed = !rules.is_ignored(&path, entry.is_dir())
+                && (!entry.is_symlink() || self.library.admits(&path).await)
+                && (entry.is_dir() || !self.library.is_protected(&path, entry.is_symlink()).await);
+            if !allowed {
+                self.walker.skip_current_dir();
+                continue;
+            }
+            if entry.is_dir() {
+                // the entries of a directory whose ignore file can not be read are unknown
+                match rules.enter(self.library.vfs().as_ref(), &path).await {
+                    Ok(rules) => self.rules.insert(path, rules),
+                    Err(e) => {
+                        output.errors.push(e);
+                        self.walker.skip_current_dir();
+                        continue;
+                    }
+                };
+            }
+            output.files.extend(entry.file());
+        }
+        return output;
+    }
 }
 
-fn into_file_insert(files: Vec<fs::File>) -> Vec<InsertFile> {
-    files
-        .into_iter()
-        .filter_map(|f| f.try_into().ok())
-        .collect()
+async fn into_file_insert(vfs: &dyn fs::Vfs, files: Vec<fs::File>) -> Vec<InsertFile> {
+    let inserts = files.into_iter().map(|f| async move {
+        let metadata = metadata::read(vfs, &f, &f.mime().ok()?).await;
+        let insert: InsertFile = f.try_into().ok()?;
+        Some(InsertFile { metadata, ..insert })
+    });
+    join_all(inserts).await.into_iter().flatten().collect()
 }
 
 impl TryInto<InsertFile> for fs::File {
     type Error = String;
 
     fn try_into(self) -> Result<InsertFile, Self::Error> {
+        let path = fs::encode_path(self.path());
+        let group_member_name = self
+            .path()
+            .file_name()
+            .map(|n| fs::encode_path(Path::new(n)))
+            .unwrap_or_default();
+        // the directory keeps its trailing separator, like in the paths of backups
+        let dir = &path[..path.len() - group_member_name.len()];
         Ok(InsertFile {
             name: self.name(),
-            path: self.path(),
             mime: self.mime().map_err(|e| e.to_string())?,
             size: self.size(),
-            group_id: calc_group_id(&self.path_of_dir()),
-            group_member_name: self.name_with_extension(),
+            group_id: calc_group_id(dir),
+            group_member_name,
+            path,
+            // the scan knows if the file was found before
+            added: 0,
+            metadata: Default::default(),
         })
     }
 }
 
-fn calc_group_id(path: &str) -> String {
+/// files of the same directory share a group id
+pub(crate) fn calc_group_id(path: &str) -> String {
     let mut hasher = DefaultHasher::new();
     path.hash(&mut hasher);
     format!("{}", hasher.finish())
 }
 
-async fn collect_content(elements: &[fs::Directory]) -> DirContent {
-    let async_checks = elements.iter().map(|e| e.elements());
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::{LibraryConfig, ScanConfig};
+    use crate::services::Libraries;
 
-    let entries: Vec<fs::Entry> = join_all(async_checks)
-        .await
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .flatten()
-        .collect();
+    async fn library_in(vfs: Arc<dyn fs::Vfs>, path: &str) -> Library {
+        let config = LibraryConfig {
+            path: path.to_string(),
+            ..Default::default()
+        };
+        let libraries = Libraries::open(vfs, &[config], &ScanConfig::default(), &[])
+            .await
+            .unwrap();
+        return libraries.all()[0].clone();
+    }
 
-    let mut output = DirContent::default();
-    entries.into_iter().for_each(|f| match f {
-        fs::Entry::File(f) => output.files.push(f),
-        fs::Entry::Directory(d) => output.dirs.push(d),
-    });
+    async fn library(path: &str) -> Library {
+        return library_in(Arc::new(fs::Local), path).await;
+    }
 
-    return output;
-}
+    async fn names(scan: &mut LibraryScan<'_>, max: usize) -> Vec<String> {
+        let mut names: Vec<String> = scan
+            .next_files(max)
+            .await
+            .files
+            .iter()
+            .map(|f| f.name_with_extension())
+            .collect();
+        names.sort();
+        return names;
+    }
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use testing::{
-        error::{Error, Result},
-        functions::assert_vec_equal,
-    };
+    #[tokio::test]
+    async fn files_of_all_dirs() {
+        let library = library("./tests/data").await;
+        let mut scan = LibraryScan::new(&library).await;
+        let names = names(&mut scan, INSERT_BATCH).await;
+        assert_eq!(
+            names,
+            [
+                "music.mp3",
+                "test-file.txt",
+                "test-file.yml",
+                "test1.txt",
+                "test2.txt",
+                "test3.txt",
+                "toystory.mp4",
+            ]
+        );
+    }
 
     #[tokio::test]
-    async fn files_and_dirs() -> Result<()> {
-        let result = collect_content(&vec![fs::Directory::new("./tests/data".to_owned())]).await;
-        let expected_files: Vec<fs::File> = into_fs_file(&vec![
-            "./tests/data/test-file.txt",
-            "./tests/data/test-file.yml",
-            "./tests/data/music.mp3",
-            "./tests/data/toystory.mp4",
-        ])
-        .await?;
-        let expected_dirs: Vec<fs::Directory> = into_fs_dir(&vec![
-            "./tests/data/dir1",
-            "./tests/data/dir2",
-            "./tests/data/dir3",
-        ])
-        .await;
-
-        assert_vec_equal(
-            &result.files,
-            &expected_files,
-            "incorrect number of files found",
-        )?;
-        assert_vec_equal(
-            &result.dirs,
-            &expected_dirs,
-            "incorrect number of dirs found",
-        )?;
-        Ok(())
+    async fn files_in_batches() {
+        let library = library("./tests/data").await;
+        let mut scan = LibraryScan::new(&library).await;
+        let first = scan.next_files(4).await;
+        assert_eq!(first.files.len(), 4);
+        assert!(!first.finished);
+        let rest = scan.next_files(4).await;
+        assert_eq!(rest.files.len(), 3);
+        assert!(rest.finished);
     }
 
     #[tokio::test]
-    async fn multiple_dirs_as_input() -> Result<()> {
-        let input_dirs: Vec<fs::Directory> = into_fs_dir(&vec![
-            "./tests/data/dir1",
-            "./tests/data/dir2",
-            "./tests/data/dir3",
-        ])
-        .await;
-        let result = collect_content(&input_dirs).await;
-        let expected_files: Vec<fs::File> = into_fs_file(&vec![
-            "./tests/data/dir1/test1.txt",
-            "./tests/data/dir2/test2.txt",
-            "./tests/data/dir3/test3.txt",
-        ])
-        .await?;
-
-        assert_vec_equal(&result.files, &expected_files, "did not find all files")?;
-        assert_vec_equal(&result.dirs, &Vec::new(), "found non existing dirs")?;
-        Ok(())
+    async fn ignored_dirs_are_not_read() {
+        let vfs = fs::Memory::new()
+            .with_file("/library/movies/movie.mp4", "movie")
+            .with_file("/library/skip/skipped.mp4", "movie")
+            .with_file("/library/.netflexignore", "skip/\n");
+
+        let library = library_in(Arc::new(vfs), "/library").await;
+        let mut scan = LibraryScan::new(&library).await;
+        assert_eq!(names(&mut scan, INSERT_BATCH).await, ["movie.mp4"]);
+        assert!(!scan.rules.contains_key(Path::new("/library/skip")));
+    }
+
+    #[tokio::test]
+    async fn inserts_are_read_from_the_library() {
+        let vfs = fs::Memory::new()
+            .with_file("/library/Toy.Story.1995.mp4", "movie")
+            .with_file(
+                "/library/Toy.Story.1995.nfo",
+                "<movie><plot>Toys</plot></movie>",
+            );
+
+        let library = library_in(Arc::new(vfs), "/library").await;
+        let files = LibraryScan::new(&library).await.next_files(10).await.files;
+        let inserts = into_file_insert(library.vfs().as_ref(), files).await;
+        let movie = inserts.iter().find(|i| i.mime == "video/mp4").unwrap();
+        assert_eq!(movie.path, "/library/Toy.Story.1995.mp4");
+        assert_eq!(movie.size, 5);
+        assert_eq!(movie.metadata.title, "Toy Story 1995");
+        assert_eq!(movie.metadata.plot, "Toys");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn paths_are_saved_losslessly() {
+        use std::os::unix::ffi::OsStrExt;
+        let path = Path::new(std::ffi::OsStr::from_bytes(b"/movies/caf\xe9 100%.mp4"));
+        let insert: InsertFile = fs::File::new(path, 1).try_into().unwrap();
+
+        assert_eq!(insert.path, "/movies/caf%E9 100%25.mp4");
+        assert_eq!(insert.group_member_name, "caf%E9 100%25.mp4");
+        assert_eq!(insert.group_id, calc_group_id("/movies/"));
+        assert_eq!(fs::decode_path(&insert.path), path);
     }
 
+    #[cfg(unix)]
     #[tokio::test]
-    async fn non_existing_dir() {
-        let dir = vec![fs::Directory::new("./tests/data/not_found".to_owned())];
-        let result = collect_content(&dir).await;
-        assert!(result.files.is_empty());
-        assert!(result.dirs.is_empty());
+    async fn unreadable_entries_are_errors() {
+        let dir = testing::TempDir::new("scan-error");
+        std::fs::write(dir.join("movie.mp4"), b"movie").unwrap();
+        std::os::unix::fs::symlink(dir.join("missing"), dir.join("broken.mp4")).unwrap();
+
+        let library = library(&dir.to_string_lossy()).await;
+        let scanned = LibraryScan::new(&library).await.next_files(10).await;
+        assert_eq!(scanned.files.len(), 1);
+        assert_eq!(scanned.errors.len(), 1);
+        assert!(scanned.errors[0].path().ends_with("broken.mp4"));
     }
 
     #[tokio::test]
-    async fn empty_input() -> Result<()> {
-        let result = collect_content(&Vec::new()).await;
-        if !result.files.is_empty() {
-            return Err(Error::msg(format!("files not empty: {:?}", result.files)));
-        }
-        if !result.dirs.is_empty() {
-            return Err(Error::msg(format!("dirs not empty: {:?}", result.dirs)));
-        }
-        Ok(())
+    async fn unreadable_ignore_files_are_errors() {
+        let vfs = fs::Memory::new()
+            .with_file("/library/a.mp4", "a")
+            .with_file("/library/movies/b.mp4", "b")
+            .with_file("/library/movies/.netflexignore", b"\xff".to_vec());
+        let library = library_in(Arc::new(vfs), "/library").await;
+
+        let scanned = LibraryScan::new(&library).await.next_files(10).await;
+        let names: Vec<String> = scanned.files.iter().map(|f| f.name()).collect();
+        assert_eq!(names, ["a"]);
+        assert_eq!(scanned.errors.len(), 1);
+        assert!(scanned.errors[0].path().ends_with("movies/.netflexignore"));
     }
 
-    async fn into_fs_file(paths: &[&str]) -> Result<Vec<fs::File>> {
-        let mut output = Vec::new();
-        for p in paths {
-            let file = fs::File::new_from_path(p).await?;
-            output.push(file);
-        }
-        return Ok(output);
+    #[tokio::test]
+    async fn unreadable_ignore_file_of_root_stops_the_scan() {
+        let vfs = fs::Memory::new()
+            .with_file("/library/a.mp4", "a")
+            .with_file("/library/.netflexignore", b"\xff".to_vec());
+        let library = library_in(Arc::new(vfs), "/library").await;
+
+        let scanned = LibraryScan::new(&library).await.next_files(10).await;
+        assert!(scanned.files.is_empty());
+        assert_eq!(scanned.errors.len(), 1);
+        assert!(scanned.finished);
     }
 
-    async fn into_fs_dir(paths: &[&str]) -> Vec<fs::Directory> {
-        let mut output = Vec::new();
-        for p in paths {
-            let dir = fs::Directory::new(p.to_string());
-            output.push(dir);
-        }
-        return output;
+    #[tokio::test]
+    async fn members_of_archives_are_indexed() {
+        use std::io::Write;
+        let dir = testing::TempDir::new("scan-zip");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(dir.join("Album.zip")).unwrap());
+        let options = zip::write::FileOptions::default();
+        zip.start_file("01 - Track.mp3", options).unwrap();
+        zip.write_all(b"track").unwrap();
+        zip.finish().unwrap();
+
+        let vfs = Arc::new(fs::Archives::new(Arc::new(fs::Local)));
+        let library = library_in(vfs, &dir.to_string_lossy()).await;
+        let files = LibraryScan::new(&library).await.next_files(10).await.files;
+        let inserts = into_file_insert(library.vfs().as_ref(), files).await;
+
+        assert_eq!(inserts.len(), 1);
+        let root = fs::encode_path(library.root());
+        assert_eq!(
+            inserts[0].path,
+            format!("{}/Album.zip!/01 - Track.mp3", root)
+        );
+        assert_eq!(inserts[0].group_member_name, "01 - Track.mp3");
+        assert_eq!(
+            inserts[0].group_id,
+            calc_group_id(&format!("{}/Album.zip!/", root))
+        );
+        assert_eq!(inserts[0].size, 5);
     }
 }
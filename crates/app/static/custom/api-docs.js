@@ -0,0 +1,91 @@
+// renders "/api/openapi.json" without any external resources
+const METHOD_COLORS = { get: "primary", post: "success", put: "warning", patch: "warning", delete: "danger" };
+
+function schema_name(schema) {
+	if (!schema) {
+		return "";
+	}
+	if (schema.$ref) {
+		const name = schema.$ref.split("/").pop();
+		return `<a href="#schema-${name}">${name}</a>`;
+	}
+	if (schema.type === "array") {
+		return `${schema_name(schema.items)}[]`;
+	}
+	if (schema.allOf) {
+		return schema.allOf.map(schema_name).join(" + ");
+	}
+	if (schema.properties) {
+		return "object";
+	}
+	return [].concat(schema.type || "any").join(" | ");
+}
+
+function text(value) {
+	return $("<div>").text(value || "").html();
+}
+
+function render_operation(path, method, operation) {
+	const parameters = (operation.parameters || []).map(p => `
+		<tr>
+			<td><code>${text(p.name)}</code></td>
+			<td>${p.in}</td>
+			<td>${schema_name(p.schema)}${p.required ? "" : " (optional)"}</td>
+			<td>${text(p.description)}</td>
+		</tr>`).join("");
+
+	const responses = Object.entries(operation.responses || {}).map(([status, response]) => {
+		const content = Object.entries(response.content || {})
+			.map(([mime, media]) => `${mime} ${schema_name(media.schema)}`)
+			.join(", ");
+		return `<tr><td>${status}</td><td>${text(response.description)}</td><td>${content}</td></tr>`;
+	}).join("");
+
+	return `
+		<div class="card mb-3">
+			<div class="card-header">
+				<span class="badge badge-${METHOD_COLORS[method] || "secondary"} text-uppercase">${method}</span>
+				<code>${text(path)}</code>
+				<span class="text-muted ml-2">${text(operation.summary)}</span>
+			</div>
+			<div class="card-body">
+				${parameters ? `<table class="table table-sm"><thead><tr><th>Parameter</th><th>In</th><th>Type</th><th></th></tr></thead><tbody>${parameters}</tbody></table>` : ""}
+				<table class="table table-sm mb-0"><thead><tr><th>Status</th><th>Description</th><th>Content</th></tr></thead><tbody>${responses}</tbody></table>
+			</div>
+		</div>`;
+}
+
+function render_schema(name, schema) {
+	const parts = schema.allOf || [schema];
+	const rows = parts.flatMap(part => {
+		if (part.$ref) {
+			return [`<tr><td colspan="3">all fields of ${schema_name(part)}</td></tr>`];
+		}
+		const required = part.required || [];
+		return Object.entries(part.properties || {}).map(([field, property]) => `
+			<tr>
+				<td><code>${text(field)}</code></td>
+				<td>${schema_name(property)}${required.includes(field) ? "" : " (optional)"}</td>
+				<td>${text(property.description)}</td>
+			</tr>`);
+	}).join("");
+
+	return `
+		<div class="card mb-3" id="schema-${name}">
+			<div class="card-header"><code>${text(name)}</code> <span class="text-muted ml-2">${text(schema.description)}</span></div>
+			<div class="card-body"><table class="table table-sm mb-0"><tbody>${rows}</tbody></table></div>
+		</div>`;
+}
+
+$.getJSON("/api/openapi.json", function (spec) {
+	$("#title").text(`${spec.info.title} API ${spec.info.version}`);
+	$("#description").text(spec.info.description || "");
+
+	const operations = Object.entries(spec.paths).flatMap(([path, item]) =>
+		Object.entries(item).map(([method, operation]) => render_operation(path, method, operation)));
+	$("#operations").html(operations.join(""));
+
+	const schemas = Object.entries((spec.components || {}).schemas || {})
+		.map(([name, schema]) => render_schema(name, schema));
+	$("#schemas").html(schemas.join(""));
+});
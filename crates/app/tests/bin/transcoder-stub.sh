@@ -0,0 +1,3 @@
+#!/bin/sh
+# stand-in for ffmpeg in tests: "transcodes" by copying the input to stdout
+cat "$1"
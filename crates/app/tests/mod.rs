@@ -1 +1,4 @@
+// the integration tests run the app on an in-memory sqlite database
+#![cfg(feature = "sqlite")]
+
 mod test;
@@ -0,0 +1,163 @@
+use super::common::{body_json, config, get, init_app, init_app_with_config, post, scan};
+use axum::http::StatusCode;
+use testing::TempDir;
+
+mod list {
+    use super::*;
+
+    #[tokio::test]
+    async fn empty_page() {
+        for uri in ["/api/v1/files", "/api/v1/videos", "/api/v1/audios"] {
+            let app = init_app().await;
+            let response = get(app, uri).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+
+            let body = body_json(response).await;
+            assert_eq!(body["items"], serde_json::json!([]));
+            assert_eq!(body["page"], 1);
+            assert_eq!(body["total"], 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn pagination_parameters() {
+        let app = init_app().await;
+        let response = get(app, "/api/v1/files?page=2&per_page=10&q=test")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = body_json(response).await;
+        assert_eq!(body["page"], 2);
+        assert_eq!(body["per_page"], 10);
+    }
+
+    #[tokio::test]
+    async fn invalid_page_is_bad_request() {
+        let app = init_app().await;
+        let response = get(app, "/api/v1/files?page=0").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = body_json(response).await;
+        assert_eq!(body["error"]["status"], 400);
+    }
+
+    #[tokio::test]
+    async fn malformed_parameter_is_bad_request() {
+        let app = init_app().await;
+        let response = get(app, "/api/v1/videos?per_page=abc").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
+
+mod find {
+    use super::*;
+
+    #[tokio::test]
+    async fn missing_file_is_not_found() {
+        for uri in [
+            "/api/v1/files/1",
+            "/api/v1/files/1/stream",
+            "/api/v1/videos/1",
+            "/api/v1/audios/1",
+        ] {
+            let app = init_app().await;
+            let response = get(app, uri).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", uri);
+
+            let body = body_json(response).await;
+            assert_eq!(body["error"]["status"], 404);
+            assert!(body["error"]["message"].is_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_id_is_bad_request() {
+        let app = init_app().await;
+        let response = get(app, "/api/v1/files/abc").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
+
+mod scan {
+    use super::*;
+
+    #[tokio::test]
+    async fn status() {
+        let app = init_app().await;
+        let response = get(app, "/api/v1/scan").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = body_json(response).await;
+        assert!(body["running"].is_boolean());
+        assert!(body["errors"].is_array());
+    }
+
+    #[tokio::test]
+    async fn second_start_is_conflict() {
+        use sea_orm::{ConnectionTrait, DbBackend, Statement};
+
+        let dir = TempDir::new("scan");
+        let url = format!("sqlite://{}/db.sqlite?mode=rwc", dir.display());
+        let app = init_app_with_config(&url, &config("./tests/data")).await;
+
+        // the scan waits for the write lock of this transaction, so it is still running
+        let db = app::database::connect(&url).await.unwrap();
+        let transaction = db.begin().await.unwrap();
+        let lock =
+            Statement::from_string(DbBackend::Sqlite, "DELETE FROM files WHERE id < 0".into());
+        transaction.execute(lock).await.unwrap();
+
+        let response = post(app.clone(), "/api/v1/scan").await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let response = post(app.clone(), "/api/v1/scan").await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let body = body_json(response).await;
+        assert_eq!(body["error"]["message"], "a scan is already running");
+
+        transaction.rollback().await.unwrap();
+        while body_json(get(app.clone(), "/api/v1/scan").await.unwrap()).await["running"] == true {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let status = body_json(get(app, "/api/v1/scan").await.unwrap()).await;
+        assert_eq!(status["errors"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn database_and_config_are_not_indexed_or_served() {
+        let temp_dir = TempDir::new("protected");
+        std::fs::write(temp_dir.join("movie.mp4"), b"movie").unwrap();
+        std::fs::write(temp_dir.join("netflex.toml"), b"").unwrap();
+        let dir = std::fs::canonicalize(&temp_dir).unwrap();
+        let url = format!("sqlite://{}/db.sqlite?mode=rwc", dir.display());
+        let mut config = config(&dir.to_string_lossy());
+        config.path = Some(dir.join("netflex.toml"));
+        let app = init_app_with_config(&url, &config).await;
+
+        scan(app.clone()).await;
+        let files = body_json(get(app.clone(), "/api/v1/files").await.unwrap()).await;
+        let names: Vec<_> = files["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| f["group_member_name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["movie.mp4"]);
+
+        // a catalog of an older version may still contain the database
+        let db = app::database::connect(&url).await.unwrap();
+        let path = dir.join("db.sqlite").to_string_lossy().to_string();
+        let sql = format!(
+            "INSERT INTO files (name, path, mime, size, group_id, group_member_name) \
+             VALUES ('db', '{}', 'application/octet-stream', 1, 'g', 'db.sqlite')",
+            path
+        );
+        let statement = sea_orm::Statement::from_string(sea_orm::DbBackend::Sqlite, sql);
+        let id = sea_orm::ConnectionTrait::execute(&db, statement)
+            .await
+            .unwrap()
+            .last_insert_id();
+        let response = get(app, &format!("/download/{}", id)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
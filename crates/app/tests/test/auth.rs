@@ -0,0 +1,281 @@
+use super::common::{
+    body_json, get, get_accepting, init_anonymous_app, init_app, init_app_with_admin, post,
+    post_form, session_cookie, with_cookie, ADMIN_NAME, ADMIN_PASSWORD,
+};
+use axum::http::{header, StatusCode};
+
+fn location(response: &axum::http::Response<axum::body::BoxBody>) -> &str {
+    response.headers()[header::LOCATION].to_str().unwrap()
+}
+
+mod setup {
+    use super::*;
+
+    #[tokio::test]
+    async fn first_run_redirects_to_setup() {
+        let app = init_anonymous_app().await;
+        let response = get(app.clone(), "/videos").await.unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(location(&response), "/setup");
+
+        let response = get(app, "/setup").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn short_password_is_rejected() {
+        let app = init_anonymous_app().await;
+        let form = "name=admin&password=short&repeat=short";
+        let response = post_form(app, "/setup", form).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(session_cookie(&response).is_none());
+    }
+
+    #[tokio::test]
+    async fn only_once() {
+        let app = init_app().await;
+        let form = "name=other&password=password&repeat=password";
+        let response = post_form(app, "/setup", form).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(location(&response), "/login");
+        assert!(session_cookie(&response).is_none());
+    }
+}
+
+mod login {
+    use super::*;
+
+    #[tokio::test]
+    async fn pages_redirect_to_login() {
+        let (app, _) = init_app_with_admin().await;
+        let response = get(app, "/videos?page=2").await.unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(location(&response), "/login?next=%2Fvideos%3Fpage%3D2");
+    }
+
+    #[tokio::test]
+    async fn api_gets_401() {
+        let (app, _) = init_app_with_admin().await;
+        let response = get(app, "/api/v1/videos").await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(body_json(response).await["error"]["status"], 401);
+    }
+
+    #[tokio::test]
+    async fn post_gets_401() {
+        let (app, _) = init_app_with_admin().await;
+        let response = post(app.clone(), "/settings/shutdown").await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = get_accepting(app, "/settings", "application/json")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    }
+
+    #[tokio::test]
+    async fn static_files_are_public() {
+        let (app, _) = init_app_with_admin().await;
+        let response = get(app, "/static/custom/styles.css").await.unwrap();
+        assert_ne!(response.status(), StatusCode::SEE_OTHER);
+        assert_ne!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn wrong_password() {
+        let (app, _) = init_app_with_admin().await;
+        let form = format!("name={}&password=wrong", ADMIN_NAME);
+        let response = post_form(app, "/login", &form).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(session_cookie(&response).is_none());
+    }
+
+    #[tokio::test]
+    async fn redirects_to_next() {
+        let (app, _) = init_app_with_admin().await;
+        let form = format!(
+            "name={}&password={}&next=%2Fvideos",
+            ADMIN_NAME, ADMIN_PASSWORD
+        );
+        let response = post_form(app, "/login", &form).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(location(&response), "/videos");
+        assert!(session_cookie(&response).is_some());
+    }
+
+    #[tokio::test]
+    async fn session_is_accepted() {
+        let app = init_app().await;
+        let response = get(app, "/videos").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn forged_session_is_rejected() {
+        let (app, _) = init_app_with_admin().await;
+        let forged = "netflex_session=1.99999999999.00".parse().unwrap();
+        let response = get(with_cookie(app, forged), "/videos").await.unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    }
+
+    #[tokio::test]
+    async fn logout_clears_the_cookie() {
+        let app = init_app().await;
+        let response = post(app, "/logout").await.unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let set_cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+        assert!(set_cookie.contains("Max-Age=0"));
+    }
+
+    #[tokio::test]
+    async fn logout_ends_the_session() {
+        let app = init_app().await;
+        let response = get(app.clone(), "/api/v1/scan").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        post(app.clone(), "/logout").await.unwrap();
+        let response = get(app, "/api/v1/scan").await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
+
+mod roles {
+    use super::*;
+    use crate::test::common::{
+        app_with_config, config, find_file, init_app_with_role, scan, with_accounts,
+    };
+    use axum::{body::BoxBody, http::Response, Router};
+    use testing::TempDir;
+
+    #[tokio::test]
+    async fn admin_reaches_everything() {
+        let app = init_app().await;
+        let response = get(app.clone(), "/settings").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = get(app, "/api/v1/scan").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn member_streams_but_has_no_settings() {
+        let app = init_app_with_role("member").await;
+        let response = get(app.clone(), "/videos").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        // passes the guard, but the file does not exist
+        let response = get(app.clone(), "/stream/1/a.mp4").await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = get(app.clone(), "/settings").await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = post(app.clone(), "/settings/refresh").await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = post(app, "/settings/shutdown").await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn guest_has_no_settings() {
+        let app = init_app_with_role("guest").await;
+        let response = get(app.clone(), "/videos").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = get(app.clone(), "/api/v1/files").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        // passes the guard, but the file does not exist
+        let response = get(app.clone(), "/stream/1/a.mp4").await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = get(app.clone(), "/settings").await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = post(app, "/settings/refresh").await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    /// "<dir>/public/movie.mp4" is in a library for guests, "<dir>/private/secret.mp4" is not
+    async fn guest_and_admin_with_libraries() -> (Router, Router, TempDir) {
+        let dir = TempDir::new("guests");
+        for (library, file) in [("public", "movie.mp4"), ("private", "secret.mp4")] {
+            std::fs::create_dir_all(dir.join(library)).unwrap();
+            std::fs::write(dir.join(library).join(file), b"video").unwrap();
+        }
+        let mut config = config(&dir.join("private").to_string_lossy());
+        config.libraries.push(app::config::LibraryConfig {
+            path: dir.join("public").to_string_lossy().to_string(),
+            guests: true,
+            ..Default::default()
+        });
+        let app = app_with_config("sqlite::memory:", &config).await;
+        let (admin, guest) = with_accounts(app, "guest").await;
+        scan(admin.clone()).await;
+        (admin, guest, dir)
+    }
+
+    async fn text(response: Response<BoxBody>) -> String {
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn guest_only_sees_libraries_for_guests() {
+        let (admin, guest, _dir) = guest_and_admin_with_libraries().await;
+        for uri in ["/files", "/videos", "/search?q=mp4"] {
+            let page = text(get(guest.clone(), uri).await.unwrap()).await;
+            assert!(page.contains("movie"), "{}", uri);
+            assert!(!page.contains("secret"), "{}", uri);
+            let page = text(get(admin.clone(), uri).await.unwrap()).await;
+            assert!(page.contains("secret"), "{}", uri);
+        }
+
+        for uri in ["/api/v1/files", "/api/v1/videos"] {
+            let page = body_json(get(guest.clone(), uri).await.unwrap()).await;
+            assert_eq!(page["total"], 1, "{}", uri);
+            assert_eq!(page["items"][0]["name"], "movie", "{}", uri);
+        }
+
+        let secret = find_file(admin.clone(), "secret.mp4").await;
+        for uri in [
+            format!("/videos/{}", secret["id"]),
+            format!("/api/v1/files/{}", secret["id"]),
+            format!("/api/v1/files/{}/stream", secret["id"]),
+            format!("/api/v1/videos/{}", secret["id"]),
+        ] {
+            let response = get(guest.clone(), &uri).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", uri);
+        }
+    }
+
+    /// the urls of the api item of the file
+    async fn file_urls(admin: &Router, name: &str) -> Vec<String> {
+        let file = find_file(admin.clone(), name).await;
+        let urls = ["stream", "download", "download_group"];
+        urls.iter()
+            .map(|u| file["urls"][u].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn guest_streams_only_libraries_for_guests() {
+        let (admin, guest, _dir) = guest_and_admin_with_libraries().await;
+        for uri in file_urls(&admin, "movie.mp4").await {
+            let response = get(guest.clone(), &uri).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+        }
+        for uri in file_urls(&admin, "secret.mp4").await {
+            let response = get(guest.clone(), &uri).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", uri);
+            let response = get(admin.clone(), &uri).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+        }
+        let secret = find_file(admin, "secret.mp4").await;
+        let transcode = secret["urls"]["transcode"].as_str().unwrap();
+        let response = get(guest, transcode).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn api_errors_are_json() {
+        let app = init_app_with_role("member").await;
+        let response = post(app, "/api/v1/scan").await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(body_json(response).await["error"]["status"], 403);
+    }
+}
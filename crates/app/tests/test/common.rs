@@ -1,24 +1,210 @@
 use axum::Router;
 use axum::{
-    body::Body,
-    http::{Method, Request},
+    body::{Body, BoxBody},
+    http::{header, HeaderName, HeaderValue, Method, Request, Response, StatusCode},
 };
-use tower::util::Oneshot;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::time::Duration;
+use testing::TempDir;
+use tower::util::{MapRequestLayer, Oneshot};
 use tower::ServiceExt;
 
+pub const ADMIN_NAME: &str = "admin";
+pub const ADMIN_PASSWORD: &str = "correct horse";
+
+/// every request is sent with the session of the admin and its csrf token
 pub async fn init_app() -> Router {
-    app::app("sqlite::memory:").await.unwrap()
+    let (app, session) = init_app_with_admin().await;
+    with_session(app, session).await
+}
+
+/// like `init_app` with another database and config
+pub async fn init_app_with_config(database_url: &str, config: &app::Config) -> Router {
+    let app = app_with_config(database_url, config).await;
+    let session = setup_admin(app.clone()).await;
+    with_session(app, session).await
+}
+
+/// the admin account exists, the requests have no session
+pub async fn init_app_with_admin() -> (Router, HeaderValue) {
+    let app = init_anonymous_app().await;
+    let session = setup_admin(app.clone()).await;
+    (app, session)
+}
+
+async fn setup_admin(app: Router) -> HeaderValue {
+    let form = format!(
+        "name={}&password={}&repeat={}",
+        ADMIN_NAME, ADMIN_PASSWORD, ADMIN_PASSWORD
+    );
+    let response = post_form(app, "/setup", &form).await.unwrap();
+    session_cookie(&response).expect("the setup should log in")
+}
+
+/// every request is sent with the session of a new account with this role
+///
+/// only admins see the csrf token on the settings page, so the requests have the "Origin" of
+/// the server instead like the ones of a browser
+pub async fn init_app_with_role(role: &str) -> Router {
+    let (_, app) = with_accounts(init_anonymous_app().await, role).await;
+    app
+}
+
+/// creates the admin and an account with this role in an app without accounts, returns the app
+/// with the session of the admin and the one with the session of the other account
+pub async fn with_accounts(app: Router, role: &str) -> (Router, Router) {
+    let admin_session = setup_admin(app.clone()).await;
+    let admin = with_session(app.clone(), admin_session).await;
+    let form = format!("name={}&password={}&role={}", role, ADMIN_PASSWORD, role);
+    let response = post_form(admin.clone(), "/settings/users", &form)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+
+    let form = format!("name={}&password={}", role, ADMIN_PASSWORD);
+    let response = post_form(app.clone(), "/login", &form).await.unwrap();
+    let session = session_cookie(&response).expect("the new account should log in");
+    let account = with_headers(
+        app,
+        vec![
+            (header::COOKIE, session),
+            (header::HOST, HeaderValue::from_static("localhost")),
+            (header::ORIGIN, HeaderValue::from_static("http://localhost")),
+        ],
+    );
+    (admin, account)
+}
+
+/// sends the session and the csrf token of the settings page with every request
+async fn with_session(app: Router, session: HeaderValue) -> Router {
+    let response = get(with_cookie(app.clone(), session.clone()), "/settings")
+        .await
+        .unwrap();
+    let token = csrf_token(response)
+        .await
+        .expect("the settings have a form");
+    with_headers(
+        app,
+        vec![(header::COOKIE, session), (CSRF_HEADER.clone(), token)],
+    )
+}
+
+pub static CSRF_HEADER: Lazy<HeaderName> = Lazy::new(|| HeaderName::from_static("x-csrf-token"));
+static REGEX_CSRF_TOKEN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"name="csrf_token" value="([0-9a-f]+)""#).unwrap());
+
+/// the token of the first form of the page
+pub async fn csrf_token(response: Response<BoxBody>) -> Option<HeaderValue> {
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let body = String::from_utf8_lossy(&body);
+    let token = REGEX_CSRF_TOKEN.captures(&body)?;
+    HeaderValue::from_str(&token[1]).ok()
+}
+
+/// sends the cookie with every request
+pub fn with_cookie(app: Router, cookie: HeaderValue) -> Router {
+    with_headers(app, vec![(header::COOKIE, cookie)])
+}
+
+pub fn with_headers(app: Router, headers: Vec<(HeaderName, HeaderValue)>) -> Router {
+    app.layer(MapRequestLayer::new(move |mut request: Request<Body>| {
+        for (name, value) in &headers {
+            request.headers_mut().insert(name.clone(), value.clone());
+        }
+        request
+    }))
+}
+
+/// before the first account was created
+pub async fn init_anonymous_app() -> Router {
+    app_with_config("sqlite::memory:", &config("./tests/data")).await
 }
 
-fn get_request(uri: &str) -> Request<Body> {
+/// the app without accounts with a new session key, the key is only read while starting
+pub async fn app_with_config(database_url: &str, config: &app::Config) -> Router {
+    let dir = TempDir::new("key");
+    let mut config = config.clone();
+    config.auth.key_file = dir.join("session.key").to_string_lossy().to_string();
+    app::app_with_config(database_url, &config).await.unwrap()
+}
+
+/// the default config with a single library
+pub fn config(library: &str) -> app::Config {
+    let library = app::config::LibraryConfig {
+        path: library.to_string(),
+        ..Default::default()
+    };
+    app::Config {
+        libraries: vec![library],
+        ..Default::default()
+    }
+}
+
+/// "name=value" of the session cookie set by the response
+pub fn session_cookie(response: &Response<BoxBody>) -> Option<HeaderValue> {
+    let set_cookie = response.headers().get(header::SET_COOKIE)?.to_str().ok()?;
+    let cookie = set_cookie.split(';').next()?;
+    HeaderValue::from_str(cookie).ok()
+}
+
+fn request(method: Method, uri: &str) -> Request<Body> {
     Request::builder()
-        .method(Method::GET)
+        .method(method)
         .uri(uri)
         .body(Body::empty())
         .unwrap()
 }
 
 pub fn get(app: Router, uri: &str) -> Oneshot<Router, Request<Body>> {
-    let request = get_request(uri);
+    let request = request(Method::GET, uri);
+    app.oneshot(request)
+}
+
+pub fn get_accepting(app: Router, uri: &str, accept: &str) -> Oneshot<Router, Request<Body>> {
+    let mut request = request(Method::GET, uri);
+    request
+        .headers_mut()
+        .insert("Accept", accept.parse().unwrap());
+    app.oneshot(request)
+}
+
+pub fn post(app: Router, uri: &str) -> Oneshot<Router, Request<Body>> {
+    let request = request(Method::POST, uri);
     app.oneshot(request)
 }
+
+pub fn post_form(app: Router, uri: &str, form: &str) -> Oneshot<Router, Request<Body>> {
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(form.to_string()))
+        .unwrap();
+    app.oneshot(request)
+}
+
+/// starts a scan of the libraries and waits until it finished
+pub async fn scan(app: Router) {
+    let response = post(app.clone(), "/api/v1/scan").await.unwrap();
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    while body_json(get(app.clone(), "/api/v1/scan").await.unwrap()).await["running"] == true {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+/// the item of the api for the file called `name`
+pub async fn find_file(app: Router, name: &str) -> serde_json::Value {
+    let files = body_json(get(app, "/api/v1/files?per_page=100").await.unwrap()).await;
+    let file = files["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|f| f["group_member_name"] == name);
+    file.expect("the file should be indexed").clone()
+}
+
+pub async fn body_json(response: Response<BoxBody>) -> serde_json::Value {
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
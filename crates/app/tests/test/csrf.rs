@@ -0,0 +1,98 @@
+use super::common::{
+    csrf_token, get, init_app_with_admin, post_form, with_cookie, with_headers, ADMIN_NAME,
+    ADMIN_PASSWORD,
+};
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    Router,
+};
+
+/// the app with the session of the admin but without its token, and the token
+async fn app_and_token() -> (Router, String) {
+    let (app, session) = init_app_with_admin().await;
+    let app = with_cookie(app, session);
+    let response = get(app.clone(), "/settings").await.unwrap();
+    let token = csrf_token(response).await.unwrap();
+    (app, token.to_str().unwrap().to_string())
+}
+
+fn with_origin(app: Router, origin: &'static str) -> Router {
+    with_headers(
+        app,
+        vec![
+            (header::HOST, HeaderValue::from_static("localhost:8080")),
+            (header::ORIGIN, HeaderValue::from_static(origin)),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn form_without_token_is_rejected() {
+    let (app, _) = app_and_token().await;
+    let response = post_form(app, "/settings/shutdown", "").await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn form_with_token_is_accepted() {
+    let (app, token) = app_and_token().await;
+    let form = format!("csrf_token={}", token);
+    let response = post_form(app, "/settings/shutdown", &form).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn wrong_token_is_rejected() {
+    let (app, token) = app_and_token().await;
+    let form = format!("csrf_token={}00", token);
+    let response = post_form(app, "/settings/shutdown", &form).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn foreign_origin_is_rejected_even_with_token() {
+    let (app, token) = app_and_token().await;
+    let app = with_origin(app, "http://evil.com");
+    let form = format!("csrf_token={}", token);
+    let response = post_form(app, "/settings/shutdown", &form).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn own_origin_without_token_is_accepted() {
+    let (app, _) = app_and_token().await;
+    let app = with_origin(app, "http://localhost:8080");
+    let response = post_form(app, "/settings/shutdown", "").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn login_from_foreign_origin_is_rejected() {
+    let (app, _) = init_app_with_admin().await;
+    let form = format!("name={}&password={}", ADMIN_NAME, ADMIN_PASSWORD);
+    let response = post_form(app.clone(), "/login", &form).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    let set_cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+    assert!(set_cookie.contains("SameSite=Lax"));
+
+    let app = with_origin(app, "http://evil.com");
+    let response = post_form(app, "/login", &form).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn long_form_is_rejected() {
+    let (app, token) = app_and_token().await;
+    let form = format!("csrf_token={}&padding={}", token, "a".repeat(64 * 1024));
+    let response = post_form(app, "/settings/shutdown", &form).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn invalid_session_is_no_session() {
+    let (app, _) = init_app_with_admin().await;
+    let app = with_cookie(app, HeaderValue::from_static("netflex_session=garbage"));
+    let form = format!("name={}&password={}", ADMIN_NAME, ADMIN_PASSWORD);
+    let response = post_form(app, "/login", &form).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+}
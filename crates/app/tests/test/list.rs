@@ -0,0 +1,99 @@
+use super::common::{body_json, config, find_file, get, init_app, init_app_with_config, scan};
+use axum::{body::BoxBody, http::Response, http::StatusCode, Router};
+use std::time::Duration;
+use testing::TempDir;
+
+/// the lists of "/files", "/videos" and "/audios" with files of this extension
+const LISTS: [(&str, &str); 3] = [("/files", "txt"), ("/videos", "mp4"), ("/audios", "mp3")];
+
+/// "match-a" has 3 bytes, "match-b" 1 and "match-c" 2, "other" does not match "q=match"
+async fn app_with_library(extension: &str) -> (Router, TempDir) {
+    let dir = TempDir::new("list");
+    for (name, content) in [
+        ("match-a", "aaa"),
+        ("match-b", "b"),
+        ("match-c", "cc"),
+        ("other", "oooo"),
+    ] {
+        let file = dir.join(format!("{}.{}", name, extension));
+        std::fs::write(file, content).unwrap();
+    }
+
+    let app = init_app_with_config("sqlite::memory:", &config(&dir.to_string_lossy())).await;
+    scan(app.clone()).await;
+    (app, dir)
+}
+
+async fn html(response: Response<BoxBody>) -> String {
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+/// the names of the files in the order they are listed
+fn names(html: &str) -> Vec<&'static str> {
+    let mut names: Vec<_> = ["match-a", "match-b", "match-c", "other"]
+        .into_iter()
+        .filter_map(|name| html.find(name).map(|position| (position, name)))
+        .collect();
+    names.sort();
+    names.into_iter().map(|(_, name)| name).collect()
+}
+
+#[tokio::test]
+async fn search_sort_and_page() {
+    for (list, extension) in LISTS {
+        let (app, _dir) = app_with_library(extension).await;
+        let page = |query: &str| get(app.clone(), &format!("{}?{}", list, query));
+
+        let response = page("q=match&sort=size&order=desc&per_page=2&page=1")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{}", list);
+        let first = html(response).await;
+        assert_eq!(names(&first), ["match-a", "match-c"], "{}", list);
+        assert!(first.contains("Found: 3"), "{}", list);
+        assert!(first.contains("Page 1 of 2"), "{}", list);
+
+        let response = page("q=match&sort=size&order=desc&per_page=2&page=2")
+            .await
+            .unwrap();
+        assert_eq!(names(&html(response).await), ["match-b"], "{}", list);
+
+        let response = page("sort=name&order=desc&per_page=3").await.unwrap();
+        let names = names(&html(response).await);
+        assert_eq!(names, ["other", "match-c", "match-b"], "{}", list);
+    }
+}
+
+#[tokio::test]
+async fn invalid_sort_is_bad_request() {
+    for (list, _) in LISTS {
+        let app = init_app().await;
+        let uri = format!("{}?sort=unknown", list);
+        let response = get(app, &uri).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn rescans_keep_when_files_were_added() {
+    let (app, dir) = app_with_library("txt").await;
+    let added = find_file(app.clone(), "match-a.txt").await["added"].clone();
+
+    // added is saved in seconds
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    std::fs::write(dir.join("new.txt"), "n").unwrap();
+    scan(app.clone()).await;
+
+    assert_eq!(find_file(app.clone(), "match-a.txt").await["added"], added);
+    let newest = get(
+        app.clone(),
+        "/api/v1/files?sort=added&order=desc&per_page=1",
+    );
+    let newest = body_json(newest.await.unwrap()).await;
+    assert_eq!(newest["items"][0]["group_member_name"], "new.txt");
+
+    let response = get(app, "/files?sort=added").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(html(response).await.contains("value=\"added\" selected"));
+}
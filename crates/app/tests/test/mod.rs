@@ -1,6 +1,13 @@
+mod api;
 mod audios;
+mod auth;
 mod common;
+mod csrf;
 mod files;
+mod list;
 mod videos;
 //mod refresh;
+mod openapi;
+mod search;
+mod settings;
 mod stream;
@@ -0,0 +1,99 @@
+use super::common::{body_json, get, init_app};
+use axum::{
+    body::Body,
+    http::{Method, Request, StatusCode},
+    Router,
+};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use tower::ServiceExt;
+
+const METHODS: [Method; 5] = [
+    Method::GET,
+    Method::POST,
+    Method::PUT,
+    Method::PATCH,
+    Method::DELETE,
+];
+
+static REGEX_API_PATH: Lazy<Regex> = Lazy::new(|| Regex::new(r#""(/api/[^"]*)""#).unwrap());
+static REGEX_PARAMETER: Lazy<Regex> = Lazy::new(|| Regex::new(r":(\w+)").unwrap());
+
+/// paths of the router, axum does not expose them except in its debug output
+fn api_paths(app: &Router) -> Vec<String> {
+    let debug = format!("{:?}", app);
+    let mut paths: Vec<String> = REGEX_API_PATH
+        .captures_iter(&debug)
+        .map(|c| c[1].to_string())
+        .collect();
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// methods with a handler, every other method is rejected with 405
+async fn methods_of(app: &Router, path: &str) -> Vec<Method> {
+    let uri = REGEX_PARAMETER.replace_all(path, "1").to_string();
+    let mut methods = Vec::new();
+    for method in METHODS {
+        let request = Request::builder()
+            .method(method.clone())
+            .uri(&uri)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        if response.status() != StatusCode::METHOD_NOT_ALLOWED {
+            methods.push(method);
+        }
+    }
+    methods
+}
+
+#[tokio::test]
+async fn spec_is_openapi_3_1() {
+    let response = get(init_app().await, "/api/openapi.json").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let spec = body_json(response).await;
+    assert_eq!(spec["openapi"], "3.1.0");
+    assert!(spec["components"]["schemas"]["FileItem"].is_object());
+}
+
+#[tokio::test]
+async fn every_route_is_described() {
+    let app = init_app().await;
+    let spec = body_json(get(app.clone(), "/api/openapi.json").await.unwrap()).await;
+
+    let paths = api_paths(&app);
+    assert!(paths.contains(&"/api/v1/files/:id".to_string()));
+
+    for path in paths {
+        let spec_path = REGEX_PARAMETER.replace_all(&path, "{$1}").to_string();
+        let methods = methods_of(&app, &path).await;
+        assert!(!methods.is_empty(), "no methods found for {}", path);
+
+        for method in methods {
+            let operation = &spec["paths"][&spec_path][method.as_str().to_lowercase()];
+            assert!(
+                operation.is_object(),
+                "{} {} is missing in the openapi spec",
+                method,
+                spec_path
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn every_described_route_exists() {
+    let app = init_app().await;
+    let spec = body_json(get(app.clone(), "/api/openapi.json").await.unwrap()).await;
+    let paths: Vec<String> = api_paths(&app)
+        .iter()
+        .map(|p| REGEX_PARAMETER.replace_all(p, "{$1}").to_string())
+        .collect();
+
+    for spec_path in spec["paths"].as_object().unwrap().keys() {
+        assert!(paths.contains(spec_path), "{} does not exist", spec_path);
+    }
+}
@@ -0,0 +1,93 @@
+use super::common::{body_json, config, get, init_app, init_app_with_config, scan};
+use axum::{body::BoxBody, http::Response, http::StatusCode, Router};
+use testing::TempDir;
+
+/// "Cowboy Bebop" matches "cowboy" in its title, path and plot, "Toy Story" only in its plot
+async fn app_with_library() -> (Router, TempDir) {
+    let dir = TempDir::new("search");
+    for (name, content) in [
+        ("Cowboy.Bebop.1998.720p.mp4", ""),
+        (
+            "Cowboy.Bebop.1998.720p.nfo",
+            "<movie><plot>A cowboy hunts bounties, cowboy style.</plot></movie>",
+        ),
+        ("Toy.Story.1995.1080p.BluRay.mp4", ""),
+        (
+            "Toy.Story.1995.1080p.BluRay.nfo",
+            "<movie><plot>A cowboy doll feels threatened by a space ranger.</plot></movie>",
+        ),
+        ("notes.txt", "cowboy"),
+    ] {
+        std::fs::write(dir.join(name), content).unwrap();
+    }
+
+    let app = init_app_with_config("sqlite::memory:", &config(&dir.to_string_lossy())).await;
+    scan(app.clone()).await;
+    (app, dir)
+}
+
+async fn html(response: Response<BoxBody>) -> String {
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+mod get {
+    use super::*;
+
+    #[tokio::test]
+    async fn status_code() {
+        for uri in ["/search", "/search?q=toy%20sto", "/search?q=%22%2A"] {
+            let app = init_app().await;
+            let response = get(app, uri).await.unwrap();
+
+            assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn results_are_ranked_and_highlighted() {
+        let (app, _dir) = app_with_library().await;
+
+        let response = get(app, "/search?q=cowboy").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let html = html(response).await;
+
+        // the content of "notes.txt" is not indexed, the name of "Cowboy.Bebop.1998.720p.nfo" is
+        assert!(html.contains("Found: 3"), "{}", html);
+        assert!(!html.contains("notes"), "{}", html);
+        let videos = html
+            .find("Videos <small class=\"text-muted\">2</small>")
+            .unwrap();
+        let bebop = html.find("<b><mark>Cowboy</mark> Bebop 1998</b>").unwrap();
+        let toy_story = html.find("<b>Toy Story 1995</b>").unwrap();
+        let files = html
+            .find("Files <small class=\"text-muted\">1</small>")
+            .unwrap();
+        let nfo = html
+            .find("<mark>Cowboy</mark>.Bebop.1998.720p.nfo")
+            .unwrap();
+        assert!(videos < bebop && bebop < toy_story && toy_story < files && files < nfo);
+        assert!(
+            html.contains("<mark>Cowboy</mark>.Bebop.1998.720p.mp4"),
+            "{}",
+            html
+        );
+        assert!(html.contains("A <mark>cowboy</mark> hunts bounties, <mark>cowboy</mark> style."));
+        assert!(html.contains("A <mark>cowboy</mark> doll feels threatened by a space ranger."));
+    }
+
+    #[tokio::test]
+    async fn api_finds_names_only() {
+        let (app, _dir) = app_with_library().await;
+
+        let response = get(app, "/api/v1/videos?q=cowboy").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let page = body_json(response).await;
+
+        assert_eq!(page["total"], 1);
+        assert_eq!(
+            page["items"][0]["group_member_name"],
+            "Cowboy.Bebop.1998.720p.mp4"
+        );
+    }
+}
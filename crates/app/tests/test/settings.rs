@@ -1,4 +1,4 @@
-use super::common::{get, init_app};
+use super::common::{get, init_app, post};
 use axum::http::StatusCode;
 
 mod get {
@@ -12,3 +12,17 @@ mod get {
         assert_eq!(response.status(), StatusCode::OK);
     }
 }
+
+mod shutdown {
+    use super::*;
+
+    #[tokio::test]
+    async fn no_scan_after_shutdown() {
+        let app = init_app().await;
+        let response = post(app.clone(), "/settings/shutdown").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = post(app, "/api/v1/scan").await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
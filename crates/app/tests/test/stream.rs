@@ -1,14 +1,271 @@
-use super::common::{get, init_app};
-use axum::http::StatusCode;
+use super::common::{
+    body_json, config, find_file, get, get_accepting, init_app, init_app_with_config, scan,
+    with_headers,
+};
+use axum::http::{header, HeaderValue, StatusCode};
 
 mod get {
     use super::*;
 
     #[tokio::test]
-    async fn status_code() {
+    async fn unknown_file_is_not_found() {
         let app = init_app().await;
         let response = get(app, "/stream/1234/114").await.unwrap();
 
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let content_type = response.headers()["Content-Type"].to_str().unwrap();
+        assert!(content_type.starts_with("text/html"));
+    }
+
+    #[tokio::test]
+    async fn error_as_json() {
+        let app = init_app().await;
+        let response = get_accepting(app, "/stream/1234/114", "application/json")
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = body_json(response).await;
+        assert_eq!(body["error"]["status"], 404);
+        assert_eq!(body["error"]["message"], "114 does not exist");
+    }
+}
+
+mod range {
+    use super::*;
+    use axum::{body::BoxBody, http::Response, Router};
+
+    /// "toystory.mp4" has 12 bytes
+    async fn request(uri_of: &str, range: &str) -> Response<BoxBody> {
+        let app = init_app().await;
+        scan(app.clone()).await;
+        let file = find_file(app.clone(), "toystory.mp4").await;
+        let uri = file["urls"][uri_of].as_str().unwrap().to_owned();
+        let range = HeaderValue::from_str(range).unwrap();
+        let app: Router = with_headers(app, vec![(header::RANGE, range)]);
+        get(app, &uri).await.unwrap()
+    }
+
+    fn content_range(response: &Response<BoxBody>) -> &str {
+        response.headers()[header::CONTENT_RANGE].to_str().unwrap()
+    }
+
+    #[tokio::test]
+    async fn suffix_is_the_end_of_the_file() {
+        let response = request("download", "bytes=-4").await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(content_range(&response), "bytes 8-11/12");
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(
+            body,
+            std::fs::read("./tests/data/toystory.mp4").unwrap()[8..]
+        );
+    }
+
+    #[tokio::test]
+    async fn end_is_cut_at_end_of_file() {
+        let header = format!("bytes=0-{}", u64::MAX);
+        let response = request("stream", &header).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(content_range(&response), "bytes 0-11/12");
+    }
+
+    #[tokio::test]
+    async fn start_after_end_of_file_is_not_satisfiable() {
+        for uri_of in ["stream", "download"] {
+            let response = request(uri_of, "bytes=12-").await;
+            assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+            assert_eq!(content_range(&response), "bytes */12");
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_range_is_bad_request() {
+        for range in ["bytes=5-1", "bytes=x-"] {
+            let response = request("stream", range).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", range);
+        }
+    }
+
+    #[tokio::test]
+    async fn unsupported_range_is_ignored() {
+        for range in ["items=0-1", "bytes=0-1,3-4"] {
+            let response = request("download", range).await;
+            assert_eq!(response.status(), StatusCode::OK, "{}", range);
+            assert!(!response.headers().contains_key(header::CONTENT_RANGE));
+            let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            assert_eq!(body, std::fs::read("./tests/data/toystory.mp4").unwrap());
+        }
+    }
+}
+
+mod server {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// the raw response of the server to a get request with this range
+    async fn request(uri: &str, range: &str) -> Vec<u8> {
+        let app = init_app().await;
+        scan(app.clone()).await;
+        let file = find_file(app.clone(), "toystory.mp4").await;
+        let uri = file["urls"][uri].as_str().unwrap().to_owned();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(app::server::serve(listener, app, async {
+            let _ = stopped.await;
+        }));
+
+        let mut socket = TcpStream::connect(address).await.unwrap();
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nRange: {}\r\nConnection: close\r\n\r\n",
+            uri, range
+        );
+        socket.write_all(request.as_bytes()).await.unwrap();
+        let mut response = Vec::new();
+        socket.read_to_end(&mut response).await.unwrap();
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+        response
+    }
+
+    fn body(response: &[u8]) -> &[u8] {
+        let end_of_head = response.windows(4).position(|w| w == b"\r\n\r\n");
+        &response[end_of_head.expect("a complete head") + 4..]
+    }
+
+    #[tokio::test]
+    async fn sends_ranges_of_files() {
+        let content = std::fs::read("./tests/data/toystory.mp4").unwrap();
+        for uri in ["stream", "download"] {
+            let response = request(uri, "bytes=2-9").await;
+            let head = String::from_utf8_lossy(&response);
+            assert!(head.starts_with("HTTP/1.1 206"), "{}", head);
+            assert!(head.contains("content-range: bytes 2-9/12"), "{}", head);
+            assert_eq!(body(&response), &content[2..10]);
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_whole_files() {
+        let response = request("download", "items=0-1").await;
+        let head = String::from_utf8_lossy(&response);
+        assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+        let content = std::fs::read("./tests/data/toystory.mp4").unwrap();
+        assert_eq!(body(&response), content);
+    }
+}
+
+mod slots {
+    use super::*;
+
+    #[tokio::test]
+    async fn without_free_slot_is_unavailable() {
+        let mut config = config("./tests/data");
+        config.streaming.max_active = 1;
+        config.streaming.retry_after = 7;
+        let app = init_app_with_config("sqlite::memory:", &config).await;
+        scan(app.clone()).await;
+        let file = find_file(app.clone(), "toystory.mp4").await;
+        let uri = file["urls"]["stream"].as_str().unwrap();
+
+        // the slot is taken until the body is dropped
+        let active = get(app.clone(), uri).await.unwrap();
+        assert_eq!(active.status(), StatusCode::OK);
+        let response = get_accepting(app.clone(), uri, "application/json")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "7");
+        let body = body_json(response).await;
+        assert_eq!(body["error"]["status"], 503);
+
+        drop(active);
+        let response = get(app, uri).await.unwrap();
         assert_eq!(response.status(), StatusCode::OK);
     }
 }
+
+mod transcode {
+    use super::*;
+
+    #[tokio::test]
+    async fn without_free_slot_is_unavailable() {
+        let mut config = config("./tests/data");
+        config.transcoder.max_concurrent = 0;
+        config.transcoder.retry_after = 7;
+        let app = init_app_with_config("sqlite::memory:", &config).await;
+        scan(app.clone()).await;
+        let file = find_file(app.clone(), "toystory.mp4").await;
+        let uri = file["urls"]["transcode"].as_str().unwrap();
+
+        let response = get_accepting(app, uri, "application/json").await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "7");
+        let body = body_json(response).await;
+        assert_eq!(body["error"]["status"], 503);
+    }
+}
+
+mod names {
+    use super::*;
+    use testing::TempDir;
+
+    #[tokio::test]
+    async fn encoded_names_are_shown_decoded() {
+        let dir = TempDir::new("names");
+        std::fs::write(dir.join("50%.mp4"), b"video").unwrap();
+        let app = init_app_with_config("sqlite::memory:", &config(&dir.to_string_lossy())).await;
+        scan(app.clone()).await;
+        // the database and the api keep the encoded name
+        let file = find_file(app.clone(), "50%25.mp4").await;
+
+        let download = file["urls"]["download"].as_str().unwrap();
+        let response = get(app.clone(), download).await.unwrap();
+        let disposition = response.headers()[header::CONTENT_DISPOSITION].to_str();
+        assert!(disposition.unwrap().contains("filename=\"50%.mp4\""));
+
+        let archive = file["urls"]["download_group"].as_str().unwrap();
+        let response = get(app.clone(), archive).await.unwrap();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(body.windows(7).any(|name| name == b"50%.mp4"));
+
+        let player = format!("/videos/{}", file["id"]);
+        let response = get(app.clone(), &player).await.unwrap();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let html = String::from_utf8(body.to_vec()).unwrap();
+        let stream = format!("/stream/{}/50%2525.mp4", file["group_id"].as_str().unwrap());
+        assert!(html.contains(&stream), "{}", html);
+        let response = get(app, &stream).await.unwrap();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"video");
+    }
+
+    #[tokio::test]
+    async fn names_with_hash_are_one_path_segment() {
+        let dir = TempDir::new("names-hash");
+        std::fs::write(dir.join("Part #2.mp4"), b"video").unwrap();
+        let app = init_app_with_config("sqlite::memory:", &config(&dir.to_string_lossy())).await;
+        scan(app.clone()).await;
+        let file = find_file(app.clone(), "Part #2.mp4").await;
+
+        let transcode = file["urls"]["transcode"].as_str().unwrap();
+        assert!(transcode.ends_with("/Part%20%232.mp4"), "{}", transcode);
+        let stream = file["urls"]["stream"].as_str().unwrap();
+        assert!(stream.ends_with("/Part%20%232.mp4"), "{}", stream);
+        let response = get(app.clone(), stream).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"video");
+
+        // the source of the player, mp4 is played without the transcoder
+        let player = format!("/videos/{}", file["id"]);
+        let response = get(app, &player).await.unwrap();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let html = String::from_utf8(body.to_vec()).unwrap();
+        assert!(html.contains(&format!("src=\"{}\"", stream)), "{}", html);
+    }
+}
@@ -11,4 +11,20 @@ mod get {
 
         assert_eq!(response.status(), StatusCode::OK);
     }
+
+    #[tokio::test]
+    async fn unknown_video_is_not_found() {
+        let app = init_app().await;
+        let response = get(app, "/videos/1234").await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn unknown_page_is_not_found() {
+        let app = init_app().await;
+        let response = get(app, "/does-not-exist").await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
 }
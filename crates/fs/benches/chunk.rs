@@ -0,0 +1,88 @@
+//! compares sending a file range to a socket by reading it into memory (`File::chunk`)
+//! with the zero-copy paths (`File::send_to` and the placeholders of `File::sendfile` written
+//! to a `Connection` like the server does)
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use fs::{File, Range};
+use futures::StreamExt;
+use testing::TempDir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::Runtime;
+
+const FILE_SIZE: u64 = 16 * 1024 * 1024;
+const RANGE_SIZES: [u64; 3] = [64 * 1024, 1024 * 1024, FILE_SIZE];
+
+fn create_file() -> (TempDir, File) {
+    let dir = TempDir::new("bench-chunk");
+    let path = dir.join("chunk.bin");
+    std::fs::write(&path, vec![7; FILE_SIZE as usize]).expect("could not create bench file");
+    (dir, File::new(path, FILE_SIZE))
+}
+
+/// returns the sending side of a local tcp connection whose receiving side discards everything
+async fn connect() -> TcpStream {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let (mut receiver, _) = listener.accept().await.unwrap();
+        let mut buffer = vec![0; 1 << 16];
+        while let Ok(n) = receiver.read(&mut buffer).await {
+            if n == 0 {
+                break;
+            }
+        }
+    });
+
+    TcpStream::connect(address).await.unwrap()
+}
+
+fn bench(c: &mut Criterion) {
+    let (_dir, file) = create_file();
+    let runtime = Runtime::new().unwrap();
+    let mut socket = runtime.block_on(connect());
+    #[cfg(target_os = "linux")]
+    let mut connection = fs::Connection::new(runtime.block_on(connect()));
+
+    let mut group = c.benchmark_group("send range");
+    for size in RANGE_SIZES {
+        let range = Range::new(0, size);
+        group.throughput(Throughput::Bytes(size));
+
+        group.bench_with_input(BenchmarkId::new("read_to_end", size), &range, |b, range| {
+            b.iter(|| {
+                runtime.block_on(async {
+                    let chunk = file.chunk(range).await.unwrap();
+                    socket.write_all(&chunk).await.unwrap();
+                })
+            })
+        });
+
+        #[cfg(target_os = "linux")]
+        group.bench_with_input(BenchmarkId::new("sendfile", size), &range, |b, range| {
+            b.iter(|| runtime.block_on(file.send_to(range, &socket)).unwrap())
+        });
+
+        #[cfg(target_os = "linux")]
+        group.bench_with_input(
+            BenchmarkId::new("placeholders", size),
+            &range,
+            |b, range| {
+                let sendfile = connection.sendfile().unwrap();
+                b.iter(|| {
+                    runtime.block_on(async {
+                        let mut placeholders = file.sendfile(range, &sendfile).await.unwrap();
+                        while let Some(placeholder) = placeholders.next().await {
+                            connection.write_all(&placeholder.unwrap()).await.unwrap();
+                        }
+                    })
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
@@ -1,63 +1,85 @@
-use crate::path::Path;
+#![allow(clippy::needless_return)]
+
+use super::walk::WalkOptions;
+use super::walker::{WalkError, Walker};
 use crate::Entry;
 use crate::File;
+use crate::Local;
 use futures::future::join_all;
+use futures::StreamExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::fs::read_dir;
 use tokio::io::Result;
 
 #[derive(Debug, PartialEq)]
 pub struct Directory {
-    path: String,
+    path: PathBuf,
 }
 
 impl Directory {
-    pub fn new(path: String) -> Self {
-        let path = path.replace("\\", "/");
-        Self { path }
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
     }
 
-    pub fn path(&self) -> String {
-        self.path.to_owned()
+    pub fn path(&self) -> &Path {
+        &self.path
     }
 
-    pub async fn elements(&self) -> Result<Vec<Entry>> {
+    /// fails if the directory can not be read, entries that can not be read are errors with their
+    /// path like in `Walker`
+    pub async fn elements(&self) -> Result<Vec<std::result::Result<Entry, WalkError>>> {
         let mut entries = read_dir(&self.path).await?;
 
-        let mut paths: Vec<Path> = Vec::new();
-        while let Ok(Some(entry)) = entries.next_entry().await {
-            paths.push(entry.into());
+        let mut paths: Vec<PathBuf> = Vec::new();
+        let mut errors = Vec::new();
+        loop {
+            match entries.next_entry().await {
+                Ok(Some(entry)) => paths.push(entry.path()),
+                Ok(None) => break,
+                Err(e) => {
+                    // the rest of the directory can not be listed
+                    errors.push(Err(WalkError::new(&self.path, e)));
+                    break;
+                }
+            }
         }
 
-        let async_new_entries = paths.into_iter().map(String::from).map(Entry::new);
-        let elements = join_all(async_new_entries)
-            .await
-            .into_iter()
-            .filter_map(|f| f.ok())
-            .collect();
+        let async_new_entries = paths.into_iter().map(|path| async move {
+            Entry::new(&path)
+                .await
+                .map_err(|e| WalkError::new(&path, e))
+        });
+        let mut elements = join_all(async_new_entries).await;
+        elements.extend(errors);
 
         return Ok(elements);
     }
 
-    // returns all files in this directory and all sub/subsub/... directories
-    pub async fn files_recursively(&self) -> Vec<File> {
-        let mut files_to_output = Vec::new();
-        let mut dirs_to_check = vec![Directory::new(self.path.to_string())];
+    /// all files in this directory and all sub/subsub/... directories, entries that can not be read
+    /// are errors like in `Walker`
+    pub async fn files_recursively(&self) -> Vec<std::result::Result<File, WalkError>> {
+        self.files_recursively_with(WalkOptions::default()).await
+    }
 
-        while !dirs_to_check.is_empty() {
-            let async_checks = dirs_to_check.iter().map(|e| e.elements());
-            let entries: Vec<Entry> = join_all(async_checks)
-                .await
-                .into_iter()
-                .filter_map(|e| e.ok())
-                .flatten()
-                .collect();
-
-            entries.into_iter().for_each(|f| match f {
-                Entry::File(f) => files_to_output.push(f),
-                Entry::Directory(d) => dirs_to_check.push(d),
-            });
-        }
+    /// like `files_recursively`, but only enters the directories the options allow
+    pub async fn files_recursively_with(
+        &self,
+        options: WalkOptions,
+    ) -> Vec<std::result::Result<File, WalkError>> {
+        self.walk(options)
+            .filter_map(|entry| async move {
+                match entry {
+                    Ok(entry) => entry.file().map(Ok),
+                    Err(e) => Some(Err(e)),
+                }
+            })
+            .collect()
+            .await
+    }
 
-        return files_to_output;
+    /// every file and directory below this directory, see `Walker`
+    pub fn walk(&self, options: WalkOptions) -> Walker {
+        Walker::new(Arc::new(Local), &self.path, options)
     }
 }
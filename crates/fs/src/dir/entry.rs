@@ -2,6 +2,7 @@ use crate::dir::directory::Directory;
 use crate::error;
 use crate::file::File;
 use std::io::Result;
+use std::path::PathBuf;
 use tokio::fs::metadata;
 
 #[derive(Debug, PartialEq)]
@@ -11,7 +12,8 @@ pub enum Entry {
 }
 
 impl Entry {
-    pub async fn new(path: String) -> Result<Self> {
+    pub async fn new(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
         let meta = metadata(&path).await?;
 
         if meta.is_file() {
@@ -24,6 +26,9 @@ impl Entry {
             return Ok(Entry::Directory(dir));
         }
 
-        error::invalid_input(format!("path is neither file nor dir: '{}'", path))
+        error::invalid_input(format!(
+            "path is neither file nor dir: '{}'",
+            path.display()
+        ))
     }
 }
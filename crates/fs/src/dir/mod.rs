@@ -1,5 +1,9 @@
 mod directory;
 mod entry;
+mod walk;
+mod walker;
 
 pub use directory::Directory;
 pub use entry::Entry;
+pub use walk::WalkOptions;
+pub use walker::{WalkEntry, WalkError, Walker};
@@ -0,0 +1,83 @@
+#![allow(clippy::needless_return)]
+
+use super::walker::WalkEntry;
+use std::collections::HashSet;
+
+/// options of a recursive traversal
+#[derive(Clone, Copy, Debug)]
+pub struct WalkOptions {
+    /// enter directories that are symlinks, no directory is entered twice
+    pub follow_symlinks: bool,
+    /// levels of subdirectories that are entered, `Some(0)` only reads the directory itself
+    pub max_depth: Option<usize>,
+    /// do not enter directories of other file systems, like mount points
+    pub same_filesystem: bool,
+    /// directories that are read at the same time by a `Walker`, and entries of each of them
+    pub concurrency: usize,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        Self {
+            follow_symlinks: true,
+            max_depth: None,
+            same_filesystem: false,
+            concurrency: 16,
+        }
+    }
+}
+
+/// decides which directories a `Walker` enters
+///
+/// every entered directory is remembered by its device and inode, so a cycle of symlinks ends
+/// the second time a directory is reached
+#[derive(Debug)]
+pub(crate) struct Traversal {
+    options: WalkOptions,
+    device: u64,
+    visited: HashSet<DirId>,
+}
+
+impl Traversal {
+    /// the root counts as entered
+    pub(super) fn starting_at(root: &WalkEntry, options: WalkOptions) -> Self {
+        let mut visited = HashSet::new();
+        visited.extend(root.id());
+        Self {
+            options,
+            device: root.device(),
+            visited,
+        }
+    }
+
+    /// false if `entry` was entered before or the options exclude it, the subdirectories of the
+    /// root have depth 1
+    pub(super) fn admit(&mut self, entry: &WalkEntry) -> bool {
+        if !entry.is_dir() {
+            return false;
+        }
+        if self
+            .options
+            .max_depth
+            .is_some_and(|max| entry.depth() > max)
+        {
+            return false;
+        }
+        if !self.options.follow_symlinks && entry.is_symlink() {
+            return false;
+        }
+        if self.options.same_filesystem && entry.device() != self.device {
+            return false;
+        }
+        return match entry.id() {
+            Some(id) => self.visited.insert(id),
+            None => false,
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(super) struct DirId {
+    pub(super) device: u64,
+    pub(super) inode: u64,
+}
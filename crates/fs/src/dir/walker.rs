@@ -0,0 +1,264 @@
+use super::walk::{DirId, Traversal, WalkOptions};
+use crate::dir::directory::Directory;
+use crate::{File, Vfs};
+use futures::future::BoxFuture;
+use futures::stream::{FuturesUnordered, Stream, StreamExt};
+use futures::FutureExt;
+use std::collections::VecDeque;
+use std::fmt::{self, Display};
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::SystemTime;
+
+/// a file or directory found by a `Walker`, symlinks are described by their target
+#[derive(Clone, Debug)]
+pub struct WalkEntry {
+    path: PathBuf,
+    depth: usize,
+    is_dir: bool,
+    is_symlink: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+    inode: u64,
+    device: u64,
+}
+
+impl WalkEntry {
+    pub(super) async fn read(
+        vfs: &dyn Vfs,
+        path: PathBuf,
+        depth: usize,
+    ) -> Result<Self, WalkError> {
+        let meta = vfs
+            .stat(&path)
+            .await
+            .map_err(|e| WalkError::new(&path, e))?;
+        Ok(Self {
+            depth,
+            is_dir: meta.is_dir,
+            is_symlink: meta.is_symlink,
+            size: meta.len,
+            modified: meta.modified,
+            inode: meta.inode,
+            device: meta.device,
+            path,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 1 for the entries of the directory the walk started at
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    pub fn is_file(&self) -> bool {
+        !self.is_dir
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.is_symlink
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// not available on every platform
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    /// made up by file systems without inodes
+    pub fn inode(&self) -> u64 {
+        self.inode
+    }
+
+    pub fn file(&self) -> Option<File> {
+        self.is_file()
+            .then(|| File::new(self.path.to_owned(), self.size))
+    }
+
+    pub fn directory(&self) -> Option<Directory> {
+        self.is_dir.then(|| Directory::new(self.path.to_owned()))
+    }
+
+    pub(super) fn device(&self) -> u64 {
+        self.device
+    }
+
+    /// identifies a directory independently of the path it was reached by
+    pub(super) fn id(&self) -> Option<DirId> {
+        self.is_dir.then_some(DirId {
+            device: self.device,
+            inode: self.inode,
+        })
+    }
+}
+
+/// an entry or directory that could not be read
+#[derive(Debug)]
+pub struct WalkError {
+    path: PathBuf,
+    error: Error,
+}
+
+impl WalkError {
+    pub fn new(path: &Path, error: Error) -> Self {
+        Self {
+            path: path.to_owned(),
+            error,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.error.kind()
+    }
+
+    pub fn into_error(self) -> Error {
+        self.error
+    }
+}
+
+impl Display for WalkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.error)
+    }
+}
+
+impl std::error::Error for WalkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// a stream of the entries below a directory, without the directory itself
+///
+/// a directory is entered when the entry after it is polled, unless `skip_current_dir` was called
+/// before. the order of the entries is not defined
+pub struct Walker {
+    vfs: Arc<dyn Vfs>,
+    options: WalkOptions,
+    start: Option<BoxFuture<'static, Result<WalkEntry, WalkError>>>,
+    traversal: Option<Traversal>,
+    current_dir: Option<WalkEntry>,
+    pending: VecDeque<WalkEntry>,
+    reads: FuturesUnordered<BoxFuture<'static, Vec<Result<WalkEntry, WalkError>>>>,
+    ready: VecDeque<Result<WalkEntry, WalkError>>,
+}
+
+impl Walker {
+    pub fn new(vfs: Arc<dyn Vfs>, root: impl Into<PathBuf>, options: WalkOptions) -> Self {
+        let root = root.into();
+        let start = {
+            let vfs = vfs.clone();
+            async move { WalkEntry::read(vfs.as_ref(), root, 0).await }.boxed()
+        };
+        Self {
+            vfs,
+            options,
+            start: Some(start),
+            traversal: None,
+            current_dir: None,
+            pending: VecDeque::new(),
+            reads: FuturesUnordered::new(),
+            ready: VecDeque::new(),
+        }
+    }
+
+    /// the directory that was returned last is not entered
+    pub fn skip_current_dir(&mut self) {
+        self.current_dir = None;
+    }
+}
+
+impl Stream for Walker {
+    type Item = Result<WalkEntry, WalkError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if let Some(start) = this.start.as_mut() {
+            let root = ready!(start.as_mut().poll(cx));
+            this.start = None;
+            match root {
+                Ok(root) if root.is_dir() => {
+                    this.traversal = Some(Traversal::starting_at(&root, this.options));
+                    this.pending.push_back(root);
+                }
+                Ok(root) => {
+                    let error = Error::new(ErrorKind::InvalidInput, "not a directory");
+                    return Poll::Ready(Some(Err(WalkError::new(&root.path, error))));
+                }
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+        }
+        if let (Some(dir), Some(traversal)) = (this.current_dir.take(), this.traversal.as_mut()) {
+            if traversal.admit(&dir) {
+                this.pending.push_back(dir);
+            }
+        }
+
+        loop {
+            if let Some(item) = this.ready.pop_front() {
+                if let Ok(entry) = &item {
+                    this.current_dir = entry.is_dir().then(|| entry.clone());
+                }
+                return Poll::Ready(Some(item));
+            }
+            while this.reads.len() < this.options.concurrency.max(1) {
+                match this.pending.pop_front() {
+                    Some(dir) => {
+                        let read = read_entries(
+                            this.vfs.clone(),
+                            dir.path,
+                            dir.depth + 1,
+                            this.options.concurrency.max(1),
+                        );
+                        this.reads.push(read.boxed());
+                    }
+                    None => break,
+                }
+            }
+            match ready!(this.reads.poll_next_unpin(cx)) {
+                Some(entries) => this.ready.extend(entries),
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+/// the entries of a directory, entries that can not be read are errors
+///
+/// at most `concurrency` entries are read at the same time
+async fn read_entries(
+    vfs: Arc<dyn Vfs>,
+    dir: PathBuf,
+    depth: usize,
+    concurrency: usize,
+) -> Vec<Result<WalkEntry, WalkError>> {
+    let paths = match vfs.list(&dir).await {
+        Ok(paths) => paths,
+        Err(e) => return vec![Err(WalkError::new(&dir, e))],
+    };
+    let vfs = vfs.as_ref();
+    let reads = paths
+        .into_iter()
+        .map(|path| WalkEntry::read(vfs, path, depth));
+    return futures::stream::iter(reads)
+        .buffer_unordered(concurrency)
+        .collect()
+        .await;
+}
@@ -1,88 +1,77 @@
+#![allow(clippy::needless_return)]
+
 use super::range::Range;
 use crate::error;
-use once_cell::sync::Lazy;
-use regex::Regex;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use std::path::{Path, PathBuf};
 use tokio::fs::metadata;
 use tokio::fs::File as TokioFile;
 use tokio::io::{AsyncReadExt, AsyncSeekExt, Result, SeekFrom};
+use tokio_util::io::ReaderStream;
 
 pub type Bytes = Vec<u8>;
+/// the content of a file, whatever it is read from
+pub type FileStream = BoxStream<'static, Result<bytes::Bytes>>;
 
 #[derive(Debug, PartialEq)]
 pub struct File {
-    path: String,
+    path: PathBuf,
     size: u64,
 }
 
-static REGEX_NAME: Lazy<Regex> = Lazy::new(|| Regex::new(r"([^/]+$)").unwrap());
-static REGEX_EXTENSION: Lazy<Regex> = Lazy::new(|| Regex::new(r"(\.[^.]+$)").unwrap());
-
 impl File {
     /// size is only used to return f.size() not asynchroniously
-    pub fn new(path: String, size: u64) -> Self {
-        let path = path.replace("\\", "/");
-        Self { path, size }
+    pub fn new(path: impl Into<PathBuf>, size: u64) -> Self {
+        Self {
+            path: path.into(),
+            size,
+        }
     }
 
-    pub async fn new_from_path(path: &str) -> Result<Self> {
+    pub async fn new_from_path(path: impl AsRef<Path>) -> Result<Self> {
         Ok(Self {
-            path: path.to_owned(),
+            path: path.as_ref().to_owned(),
             size: metadata(path).await?.len(),
         })
     }
 
-    pub fn path(&self) -> String {
-        self.path.to_owned()
+    pub fn path(&self) -> &Path {
+        &self.path
     }
 
-    /// example: for path '/my/dir/my-file.txt' => '/my/dir/'
-    pub fn path_of_dir(&self) -> String {
-        let name_with_extension = self.name_with_extension();
-        let mut chars = self.path.chars();
-        for _ in 0..name_with_extension.len() {
-            chars.next_back();
-        }
-        return chars.as_str().to_owned();
+    /// example: for path '/my/dir/my-file.txt' => '/my/dir'
+    pub fn path_of_dir(&self) -> &Path {
+        self.path.parent().unwrap_or_else(|| Path::new(""))
     }
 
-    /// returns name with extension 'textfile.txt' (captures everything after last '/' in path)
-    /// if no '/' exists -> returns ""
+    /// returns name with extension 'textfile.txt', "" if the path has no name
     pub fn name_with_extension(&self) -> String {
-        let regex_name_match = match REGEX_NAME.captures(&self.path) {
-            Some(s) => s.get(0),
-            None => return String::from(""),
-        };
-
-        let name_with_extension = match regex_name_match {
-            Some(s) => s.as_str(),
-            None => return String::from(""),
-        };
-
-        return name_with_extension.to_string();
+        self.path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
     }
 
     pub fn size(&self) -> u64 {
         self.size
     }
 
-    /// returns everything between last "/" and last "." => "/path/name.adsf.extension" -> "name.asdf"
+    /// returns the name without the last extension => "/path/name.adsf.extension" -> "name.asdf"
     pub fn name(&self) -> String {
-        let name_with_extension = self.name_with_extension();
-
-        let regex_extension_match = match REGEX_EXTENSION.captures(&self.path) {
-            Some(s) => s.get(0),
-            None => return name_with_extension.to_owned(),
-        };
-
-        return match regex_extension_match {
-            Some(s) => name_with_extension.replace(s.as_str(), ""),
-            None => return name_with_extension,
-        };
+        self.path
+            .file_stem()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
     }
 
     /// guesses by evaluating the file extension
     pub fn mime(&self) -> Result<String> {
-        if self.path.ends_with(".m3u8") {
+        let is_playlist = self
+            .path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("m3u8"));
+        if is_playlist {
             return Ok("application/x-mpegURL".to_string());
         }
 
@@ -91,7 +80,7 @@ impl File {
             Some(s) => Ok(s.to_string()),
             None => error::other(format!(
                 "could not guess MimeType for path: '{}'",
-                self.path
+                self.path.display()
             )),
         };
     }
@@ -99,13 +88,21 @@ impl File {
     /// "chunk-size == range.offset" if file is large enough
     pub async fn chunk(&self, range: &Range) -> Result<Bytes> {
         let mut buffer = Bytes::new();
-        let mut file = TokioFile::open(self.path.to_owned()).await?;
+        let mut file = TokioFile::open(&self.path).await?;
 
         file.seek(SeekFrom::Start(range.start())).await?;
         file.take(range.offset()).read_to_end(&mut buffer).await?;
 
         return Ok(buffer);
     }
+
+    /// like `chunk` but reads the content lazily while the stream is polled
+    pub async fn stream(&self, range: &Range) -> Result<FileStream> {
+        let mut file = TokioFile::open(&self.path).await?;
+
+        file.seek(SeekFrom::Start(range.start())).await?;
+        return Ok(ReaderStream::new(file.take(range.offset())).boxed());
+    }
 }
 
 #[cfg(test)]
@@ -116,10 +113,28 @@ mod test {
     async fn path() {
         assert_eq!(
             File::new("/path/name.extension".to_owned(), 0).path(),
-            String::from("/path/name.extension")
+            Path::new("/path/name.extension")
         );
     }
 
+    #[tokio::test]
+    async fn backslash_is_kept() {
+        let file = File::new("/path/a\\b.mp4", 0);
+        assert_eq!(file.path(), Path::new("/path/a\\b.mp4"));
+        assert_eq!(file.name_with_extension(), "a\\b.mp4");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn non_utf8_path_is_kept() {
+        use std::os::unix::ffi::OsStrExt;
+        let path = Path::new(std::ffi::OsStr::from_bytes(b"/path/caf\xe9.mp4"));
+        let file = File::new(path, 0);
+        assert_eq!(file.path(), path);
+        assert_eq!(file.name(), "caf\u{FFFD}");
+        assert_eq!(file.mime().unwrap(), "video/mp4");
+    }
+
     #[tokio::test]
     async fn size() {
         assert_eq!(
@@ -170,7 +185,7 @@ mod test {
         async fn normal_path() {
             assert_eq!(
                 File::new("/path/name.extension".to_owned(), 0).path_of_dir(),
-                String::from("/path/")
+                Path::new("/path")
             );
         }
 
@@ -178,13 +193,13 @@ mod test {
         async fn only_name() {
             assert_eq!(
                 File::new("name.extension".to_owned(), 0).path_of_dir(),
-                String::from("")
+                Path::new("")
             );
         }
 
         #[tokio::test]
         async fn empty_path() {
-            assert_eq!(File::new("".to_owned(), 0).path_of_dir(), String::from(""));
+            assert_eq!(File::new("".to_owned(), 0).path_of_dir(), Path::new(""));
         }
     }
 
@@ -216,10 +231,11 @@ mod test {
         }
 
         #[tokio::test]
-        async fn no_name_and_no_extension() {
+        async fn trailing_slash() {
+            // like std::path, a trailing "/" is ignored
             assert_eq!(
                 File::new("/path/".to_owned(), 0).name_with_extension(),
-                String::from("")
+                String::from("path")
             );
         }
 
@@ -268,10 +284,11 @@ mod test {
         }
 
         #[tokio::test]
-        async fn no_name_but_extension() {
+        async fn hidden_file() {
+            // like std::path, a leading "." does not start an extension
             assert_eq!(
                 File::new("/path/.extension".to_owned(), 0).name(),
-                String::from("")
+                String::from(".extension")
             );
         }
 
@@ -284,8 +301,11 @@ mod test {
         }
 
         #[tokio::test]
-        async fn no_name_and_no_extension() {
-            assert_eq!(File::new("/path/".to_owned(), 0).name(), String::from(""));
+        async fn trailing_slash() {
+            assert_eq!(
+                File::new("/path/".to_owned(), 0).name(),
+                String::from("path")
+            );
         }
 
         #[tokio::test]
@@ -1,4 +1,7 @@
+#[allow(clippy::module_inception)]
 mod file;
 mod range;
-pub use file::File;
+mod sendfile;
+pub use file::{File, FileStream};
 pub use range::Range;
+pub use sendfile::{Connection, Sendfile};
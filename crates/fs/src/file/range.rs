@@ -19,11 +19,14 @@ impl Range {
         self.offset
     }
 
-    pub fn apply_filesize(&self, file_size: u64) -> Self {
-        let start = min(file_size - 1, self.start);
-        Self {
-            start,
-            offset: min(file_size - start, self.offset),
+    /// the part of the range inside of a file, `None` if the range starts at or after its end
+    pub fn apply_filesize(&self, file_size: u64) -> Option<Self> {
+        if self.start >= file_size {
+            return None;
         }
+        Some(Self {
+            start: self.start,
+            offset: min(file_size - self.start, self.offset),
+        })
     }
 }
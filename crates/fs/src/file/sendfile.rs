@@ -0,0 +1,316 @@
+//! zero-copy fast path: the kernel copies file ranges into a socket without passing them through
+//! userspace. The http server keeps writing the response, the body only holds placeholders that
+//! the `Connection` replaces with the file range they stand for.
+
+#![allow(clippy::needless_return)]
+
+use super::{file::File, file::FileStream, range::Range};
+use futures::StreamExt;
+use std::cmp::min;
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind, IoSlice, Result};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+
+/// size of the chunks of a placeholder stream
+const PLACEHOLDER_LEN: usize = 1 << 16;
+
+/// the content of the placeholders does not matter, they are recognized by their address
+static PLACEHOLDER: [u8; PLACEHOLDER_LEN] = [0; PLACEHOLDER_LEN];
+
+fn is_placeholder(buf: &[u8]) -> bool {
+    !buf.is_empty() && PLACEHOLDER.as_ptr_range().contains(&buf.as_ptr())
+}
+
+/// a file range whose placeholders are not sent yet
+struct Pending {
+    file: std::fs::File,
+    offset: u64,
+    remaining: u64,
+}
+
+/// the file ranges of the placeholders written to a `Connection`, in the order they are written
+#[derive(Clone, Default)]
+pub struct Sendfile(Arc<Mutex<VecDeque<Pending>>>);
+
+impl Sendfile {
+    fn push(&self, pending: Pending) {
+        self.0.lock().expect("sendfile queue").push_back(pending);
+    }
+}
+
+/// a tcp connection that sends placeholders of `File::sendfile` with sendfile(2)
+pub struct Connection {
+    stream: TcpStream,
+    sendfile: Sendfile,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            sendfile: Sendfile::default(),
+        }
+    }
+
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    /// `None` if the platform has no sendfile, the ranges have to be streamed then
+    pub fn sendfile(&self) -> Option<Sendfile> {
+        return match cfg!(target_os = "linux") {
+            true => Some(self.sendfile.clone()),
+            false => None,
+        };
+    }
+
+    /// sends at most `len` bytes of the first pending range
+    fn poll_sendfile(&mut self, cx: &mut Context<'_>, len: usize) -> Poll<Result<usize>> {
+        let mut queue = self.sendfile.0.lock().expect("sendfile queue");
+        let pending = match queue.front_mut() {
+            Some(p) => p,
+            None => {
+                let message = "placeholder without a file range";
+                return Poll::Ready(Err(Error::new(ErrorKind::InvalidData, message)));
+            }
+        };
+        let count = min(len as u64, pending.remaining) as usize;
+
+        let sent = futures::ready!(poll_send(&self.stream, cx, pending, count))?;
+        pending.remaining -= sent as u64;
+        if pending.remaining == 0 {
+            queue.pop_front();
+        }
+        return Poll::Ready(Ok(sent));
+    }
+}
+
+impl AsyncRead for Connection {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Connection {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize>> {
+        self.poll_write_vectored(cx, &[IoSlice::new(buf)])
+    }
+
+    /// the buffers before the first placeholder are written as they are, a placeholder at the
+    /// start is replaced by its file range
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize>> {
+        let this = self.get_mut();
+        let plain = bufs.iter().take_while(|b| !is_placeholder(b)).count();
+        return match bufs.get(plain) {
+            Some(placeholder) if plain == 0 => this.poll_sendfile(cx, placeholder.len()),
+            _ => Pin::new(&mut this.stream).poll_write_vectored(cx, &bufs[..plain]),
+        };
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+impl File {
+    /// placeholders for the range, a `Connection` with the same `Sendfile` sends the file instead
+    /// of them. The file is opened right away and the range ends with it like in `stream`
+    pub async fn sendfile(&self, range: &Range, sendfile: &Sendfile) -> Result<FileStream> {
+        let file = tokio::fs::File::open(self.path()).await?.into_std().await;
+        let meta = file.metadata()?;
+        if !meta.is_file() {
+            let message = format!("{} is not a regular file", self.path().display());
+            return Err(Error::new(ErrorKind::InvalidInput, message));
+        }
+        let remaining = min(range.offset(), meta.len().saturating_sub(range.start()));
+        let pending = Pending {
+            file,
+            offset: range.start(),
+            remaining,
+        };
+
+        // the range is queued when the body is first read, an unread body never reaches the socket
+        let state = (Some(pending), remaining, sendfile.clone());
+        let stream = futures::stream::unfold(state, |(pending, remaining, sendfile)| async move {
+            if remaining == 0 {
+                return None;
+            }
+            if let Some(pending) = pending {
+                sendfile.push(pending);
+            }
+            let len = min(remaining, PLACEHOLDER_LEN as u64) as usize;
+            let placeholder = bytes::Bytes::from_static(&PLACEHOLDER[..len]);
+            Some((Ok(placeholder), (None, remaining - len as u64, sendfile)))
+        });
+        return Ok(stream.boxed());
+    }
+
+    /// writes the range of the file to the socket with sendfile(2)
+    /// returns the number of bytes sent, which is less than range.offset() if the file is shorter
+    #[cfg(target_os = "linux")]
+    pub async fn send_to(&self, range: &Range, socket: &TcpStream) -> Result<u64> {
+        let file = tokio::fs::File::open(self.path()).await?.into_std().await;
+        let mut pending = Pending {
+            file,
+            offset: range.start(),
+            remaining: range.offset(),
+        };
+
+        while pending.remaining > 0 {
+            let count = min(pending.remaining, MAX_CHUNK) as usize;
+            let sent = futures::future::poll_fn(|cx| poll_send(socket, cx, &mut pending, count));
+            match sent.await? {
+                0 => break, // end of file
+                sent => pending.remaining -= sent as u64,
+            }
+        }
+        return Ok(pending.offset - range.start());
+    }
+}
+
+/// limit of a single sendfile call in `send_to`, so other tasks can use the runtime in between
+#[cfg(target_os = "linux")]
+const MAX_CHUNK: u64 = 1 << 21;
+
+/// sends `count` bytes of the pending range once the socket is writable, 0 at the end of the file
+#[cfg(target_os = "linux")]
+fn poll_send(
+    socket: &TcpStream,
+    cx: &mut Context<'_>,
+    pending: &mut Pending,
+    count: usize,
+) -> Poll<Result<usize>> {
+    use std::os::unix::io::AsRawFd;
+    use tokio::io::Interest;
+
+    loop {
+        futures::ready!(socket.poll_write_ready(cx))?;
+        let result = socket.try_io(Interest::WRITABLE, || {
+            sendfile(
+                socket.as_raw_fd(),
+                pending.file.as_raw_fd(),
+                &mut pending.offset,
+                count,
+            )
+        });
+        match result {
+            Err(e) if e.kind() == ErrorKind::WouldBlock => continue,
+            result => return Poll::Ready(result),
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn poll_send(
+    _socket: &TcpStream,
+    _cx: &mut Context<'_>,
+    _pending: &mut Pending,
+    _count: usize,
+) -> Poll<Result<usize>> {
+    let message = "sendfile is only supported on linux";
+    Poll::Ready(Err(Error::new(ErrorKind::Unsupported, message)))
+}
+
+/// advances `offset` by the number of bytes sent
+#[cfg(target_os = "linux")]
+fn sendfile(socket: i32, file: i32, offset: &mut u64, count: usize) -> Result<usize> {
+    let mut raw_offset = *offset as libc::off_t;
+    // SAFETY: both descriptors are open for the duration of the call and raw_offset is a valid pointer
+    let sent = unsafe { libc::sendfile(socket, file, &mut raw_offset, count) };
+    if sent < 0 {
+        return Err(Error::last_os_error());
+    }
+    *offset = raw_offset as u64;
+    return Ok(sent as usize);
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    async fn connect() -> (Connection, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let sender = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (receiver, _) = listener.accept().await.unwrap();
+        (Connection::new(sender), receiver)
+    }
+
+    #[tokio::test]
+    async fn placeholders_are_replaced_by_the_file() {
+        let (mut connection, mut receiver) = connect().await;
+        let file = File::new("./tests/data/text.txt", 0);
+        let expected = file.chunk(&Range::new(10, 100)).await.unwrap();
+
+        let sendfile = connection.sendfile().unwrap();
+        let mut placeholders = file
+            .sendfile(&Range::new(10, 100), &sendfile)
+            .await
+            .unwrap();
+        let placeholder = placeholders.next().await.unwrap().unwrap();
+        assert!(placeholders.next().await.is_none());
+
+        let head = IoSlice::new(b"head");
+        let written = connection
+            .write_vectored(&[head, IoSlice::new(&placeholder)])
+            .await
+            .unwrap();
+        assert_eq!(
+            written, 4,
+            "only the buffers before the placeholder are written"
+        );
+        connection.write_all(&placeholder).await.unwrap();
+        drop(connection);
+
+        let mut received = Vec::new();
+        receiver.read_to_end(&mut received).await.unwrap();
+        assert_eq!(received, [&b"head"[..], &expected].concat());
+    }
+
+    #[tokio::test]
+    async fn placeholders_end_with_the_file() {
+        let (connection, _receiver) = connect().await;
+        let file = File::new("./tests/data/text.txt", 0);
+        let len = std::fs::metadata(file.path()).unwrap().len();
+
+        let sendfile = connection.sendfile().unwrap();
+        let placeholders = file.sendfile(&Range::new(0, len + 100), &sendfile).await;
+        let sent: usize = placeholders
+            .unwrap()
+            .map(|p| p.unwrap().len())
+            .fold(0, |sum, len| async move { sum + len })
+            .await;
+        assert_eq!(sent as u64, len);
+    }
+
+    #[tokio::test]
+    async fn placeholder_without_range_is_error() {
+        let (mut connection, _receiver) = connect().await;
+        let placeholder = &PLACEHOLDER[..10];
+        assert!(connection.write(placeholder).await.is_err());
+    }
+}
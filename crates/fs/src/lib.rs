@@ -2,9 +2,17 @@ mod dir;
 mod error;
 mod file;
 mod path;
+mod vfs;
 
 pub use dir::Directory;
 pub use dir::Entry;
+pub use dir::WalkEntry;
+pub use dir::WalkError;
+pub use dir::WalkOptions;
+pub use dir::Walker;
 pub use file::File;
+pub use file::FileStream;
 pub use file::Range;
-pub use path::Path;
+pub use file::{Connection, Sendfile};
+pub use path::{decode_path, encode_path};
+pub use vfs::{Archive, Archives, Local, Memory, Metadata, Vfs, ARCHIVE_EXTENSIONS};
@@ -1,30 +1,89 @@
-use std::path::PathBuf;
+//! paths are saved as text, the encoding keeps every byte so a decoded path opens the same file
 
-use tokio::fs::DirEntry;
+#![allow(clippy::needless_return)]
 
-pub struct Path(String);
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
 
-impl From<String> for Path {
-    fn from(f: String) -> Self {
-        Self(f)
+/// valid utf-8 is kept except "%", which is written as "%25" like every byte that is not part of
+/// valid utf-8
+pub fn encode_path(path: &Path) -> String {
+    let bytes = path.as_os_str().as_encoded_bytes();
+    let mut encoded = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        encoded.push_str(&chunk.valid().replace('%', "%25"));
+        for byte in chunk.invalid() {
+            let _ = write!(encoded, "%{:02X}", byte);
+        }
     }
+    return encoded;
 }
 
-impl From<&PathBuf> for Path {
-    fn from(f: &PathBuf) -> Self {
-        let path = f.as_os_str().to_owned().to_string_lossy().to_string();
-        Self(path)
+/// the reverse of `encode_path`, a "%" that is not followed by two hex digits is kept
+pub fn decode_path(encoded: &str) -> PathBuf {
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut rest = encoded.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = tail
+            .get(..2)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(escaped) if byte == b'%' => {
+                bytes.push(escaped);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
     }
+    return from_bytes(bytes);
 }
 
-impl From<DirEntry> for Path {
-    fn from(f: DirEntry) -> Self {
-        Path::from(&f.path())
-    }
+#[cfg(unix)]
+fn from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(std::ffi::OsString::from_vec(bytes))
+}
+
+// paths of other platforms are not arbitrary bytes
+#[cfg(not(unix))]
+fn from_bytes(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
 }
 
-impl From<Path> for String {
-    fn from(f: Path) -> Self {
-        f.0
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn utf8_is_unchanged() {
+        let path = Path::new("/movies/Amélie (2001)/a\\b.mp4");
+        assert_eq!(encode_path(path), "/movies/Amélie (2001)/a\\b.mp4");
+        assert_eq!(decode_path(&encode_path(path)), path);
+    }
+
+    #[test]
+    fn percent_is_escaped() {
+        let path = Path::new("/music/100%25.mp3");
+        assert_eq!(encode_path(path), "/music/100%2525.mp3");
+        assert_eq!(decode_path(&encode_path(path)), path);
+    }
+
+    #[test]
+    fn single_percent_is_kept() {
+        assert_eq!(decode_path("/100%.mp3"), Path::new("/100%.mp3"));
+        assert_eq!(decode_path("/100%zz"), Path::new("/100%zz"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn invalid_utf8_is_escaped() {
+        use std::os::unix::ffi::OsStrExt;
+        let path = Path::new(std::ffi::OsStr::from_bytes(b"/movies/caf\xe9.mp4"));
+        assert_eq!(encode_path(path), "/movies/caf%E9.mp4");
+        assert_eq!(decode_path(&encode_path(path)), path);
     }
 }
@@ -0,0 +1,208 @@
+#![allow(clippy::needless_return)]
+
+use super::{children, inode_of, insert, normalize, not_a_directory, not_found, Metadata, Vfs};
+use crate::{File, FileStream, Range};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::{stream, StreamExt};
+use std::collections::BTreeMap;
+use std::io::{self, Error, Read, Result};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+use tokio::sync::mpsc;
+use zip::{CompressionMethod, ZipArchive};
+
+/// bytes that are decompressed at once
+const INFLATE_CHUNK: usize = 64 * 1024;
+
+/// the members of a zip or tar file, the archive is the directory "/"
+///
+/// stored members are read directly from the archive file, so a range costs no more than it
+/// does for a local file. deflated members are decompressed from their start
+#[derive(Clone, Debug)]
+pub struct Archive {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    /// `None` for directories
+    members: BTreeMap<PathBuf, Option<Member>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Member {
+    len: u64,
+    /// where the content starts in the archive file
+    start: u64,
+    /// the position in a zip file, only needed to decompress the member
+    index: Option<usize>,
+}
+
+impl Archive {
+    /// a ".tar" file is read as tar, every other file as zip
+    pub async fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let modified = tokio::fs::metadata(&path).await?.modified().ok();
+        let is_tar = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("tar"));
+        let archive = path.clone();
+        let members = tokio::task::spawn_blocking(move || match is_tar {
+            true => tar_members(&archive),
+            false => zip_members(&archive),
+        })
+        .await
+        .map_err(Error::other)??;
+
+        Ok(Self {
+            path,
+            modified,
+            members,
+        })
+    }
+
+    /// the path of the archive file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn get(&self, path: &Path) -> Result<&Option<Member>> {
+        self.members
+            .get(&normalize(path))
+            .ok_or_else(|| not_found(path))
+    }
+}
+
+#[async_trait]
+impl Vfs for Archive {
+    async fn list(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        match self.get(dir)? {
+            None => Ok(children(&self.members, &normalize(dir))),
+            Some(_) => Err(not_a_directory(dir)),
+        }
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        let member = self.get(path)?;
+        Ok(Metadata {
+            is_dir: member.is_none(),
+            len: member.map(|m| m.len).unwrap_or(0),
+            modified: self.modified,
+            inode: inode_of(&normalize(path)),
+            ..Default::default()
+        })
+    }
+
+    async fn open_range(&self, path: &Path, range: &Range) -> Result<FileStream> {
+        let member = self.get(path)?.ok_or_else(|| not_found(path))?;
+        let start = range.start().min(member.len);
+        let range = Range::new(start, range.offset().min(member.len - start));
+        return match member.index {
+            Some(index) => Ok(inflate(self.path.clone(), index, range)),
+            None => {
+                let range = Range::new(member.start + range.start(), range.offset());
+                File::new(&self.path, 0).stream(&range).await
+            }
+        };
+    }
+
+    async fn canonicalize(&self, path: &Path) -> Result<PathBuf> {
+        self.get(path)?;
+        return Ok(normalize(path));
+    }
+}
+
+fn zip_members(archive: &Path) -> Result<BTreeMap<PathBuf, Option<Member>>> {
+    let mut zip = ZipArchive::new(std::fs::File::open(archive)?)?;
+    let mut members = BTreeMap::from([(PathBuf::from("/"), None)]);
+    for index in 0..zip.len() {
+        // encrypted members can not be read and are left out
+        let file = match zip.by_index(index) {
+            Ok(file) => file,
+            Err(_) => continue,
+        };
+        let path = match file.enclosed_name() {
+            Some(name) => normalize(name),
+            None => continue,
+        };
+        let member = match (file.is_dir(), file.compression()) {
+            (true, _) => None,
+            (false, CompressionMethod::Stored) => Some(Member {
+                len: file.size(),
+                start: file.data_start(),
+                index: None,
+            }),
+            (false, CompressionMethod::Deflated) => Some(Member {
+                len: file.size(),
+                start: file.data_start(),
+                index: Some(index),
+            }),
+            (false, _) => continue,
+        };
+        insert(&mut members, path, member);
+    }
+    return Ok(members);
+}
+
+fn tar_members(archive: &Path) -> Result<BTreeMap<PathBuf, Option<Member>>> {
+    let mut tar = tar::Archive::new(std::fs::File::open(archive)?);
+    let mut members = BTreeMap::from([(PathBuf::from("/"), None)]);
+    for entry in tar.entries_with_seek()? {
+        let entry = entry?;
+        let path = match enclosed(&entry.path()?) {
+            Some(path) => path,
+            None => continue,
+        };
+        let kind = entry.header().entry_type();
+        let member = match kind {
+            kind if kind.is_dir() => None,
+            kind if kind.is_file() || kind.is_contiguous() => Some(Member {
+                len: entry.size(),
+                start: entry.raw_file_position(),
+                index: None,
+            }),
+            // links and sparse files are not supported
+            _ => continue,
+        };
+        insert(&mut members, path, member);
+    }
+    return Ok(members);
+}
+
+/// `None` for paths that leave the archive
+fn enclosed(path: &Path) -> Option<PathBuf> {
+    let leaves = path
+        .components()
+        .any(|c| matches!(c, Component::ParentDir | Component::Prefix(_)));
+    return (!leaves).then(|| normalize(path));
+}
+
+/// decompresses the member on a blocking thread while the stream is polled
+fn inflate(archive: PathBuf, index: usize, range: Range) -> FileStream {
+    let (sender, receiver) = mpsc::channel::<Result<Bytes>>(4);
+    tokio::task::spawn_blocking(move || {
+        let result = (|| -> Result<()> {
+            let mut zip = ZipArchive::new(std::fs::File::open(&archive)?)?;
+            let mut member = zip.by_index(index)?;
+            io::copy(&mut (&mut member).take(range.start()), &mut io::sink())?;
+            let mut content = member.take(range.offset());
+            loop {
+                let mut buffer = vec![0; INFLATE_CHUNK];
+                let read = content.read(&mut buffer)?;
+                if read == 0 {
+                    return Ok(());
+                }
+                buffer.truncate(read);
+                // the stream was dropped
+                if sender.blocking_send(Ok(Bytes::from(buffer))).is_err() {
+                    return Ok(());
+                }
+            }
+        })();
+        if let Err(e) = result {
+            let _ = sender.blocking_send(Err(e));
+        }
+    });
+    stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|item| (item, receiver))
+    })
+    .boxed()
+}
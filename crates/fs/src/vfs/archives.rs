@@ -0,0 +1,242 @@
+#![allow(clippy::needless_return)]
+
+use super::{inode_of, Archive, Metadata, Vfs};
+use crate::{FileStream, Range};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// files with these extensions are mounted
+pub const ARCHIVE_EXTENSIONS: &[&str] = &["zip", "cbz"];
+
+/// archives that are kept in memory, all of them are dropped when there are more
+const MAX_OPENED: usize = 64;
+
+/// a file system whose zip archives are directories of their members
+///
+/// the members of "/music/album.zip" are below "/music/album.zip!", listings show that directory
+/// instead of the archive. an archive is read once and again after it changed
+#[derive(Debug)]
+pub struct Archives {
+    inner: Arc<dyn Vfs>,
+    opened: Mutex<HashMap<PathBuf, Opened>>,
+}
+
+#[derive(Debug)]
+struct Opened {
+    len: u64,
+    modified: Option<SystemTime>,
+    archive: Arc<Archive>,
+}
+
+/// a path below a mounted archive
+struct Mounted {
+    /// "/music/album.zip"
+    archive: PathBuf,
+    /// "/music/album.zip!"
+    mount: PathBuf,
+    /// the path inside of the archive, "/" for the mount itself
+    member: PathBuf,
+}
+
+impl Archives {
+    pub fn new(inner: Arc<dyn Vfs>) -> Self {
+        Self {
+            inner,
+            opened: Default::default(),
+        }
+    }
+
+    /// the members of the archive at `path` and the metadata of its file
+    async fn open(&self, path: &Path) -> Result<(Arc<Archive>, Metadata)> {
+        let meta = self.inner.stat(path).await?;
+        if meta.is_dir {
+            let message = format!("{} is not an archive", path.display());
+            return Err(Error::new(ErrorKind::InvalidInput, message));
+        }
+        let cached = self
+            .lock()
+            .get(path)
+            .filter(|o| o.len == meta.len && o.modified == meta.modified)
+            .map(|o| o.archive.clone());
+        if let Some(archive) = cached {
+            return Ok((archive, meta));
+        }
+
+        let local = self.inner.local_path(path).ok_or_else(|| {
+            let message = format!("{} is not on the disk", path.display());
+            Error::new(ErrorKind::Unsupported, message)
+        })?;
+        let archive = Arc::new(Archive::open(local).await?);
+        let mut opened = self.lock();
+        if opened.len() >= MAX_OPENED {
+            opened.clear();
+        }
+        let entry = Opened {
+            len: meta.len,
+            modified: meta.modified,
+            archive: archive.clone(),
+        };
+        opened.insert(path.to_owned(), entry);
+        return Ok((archive, meta));
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<PathBuf, Opened>> {
+        self.opened.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl Vfs for Archives {
+    async fn list(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        if let Some(mounted) = Mounted::split(dir) {
+            let (archive, _) = self.open(&mounted.archive).await?;
+            let members = archive.list(&mounted.member).await?;
+            return Ok(members.iter().map(|m| mounted.join(m)).collect());
+        }
+
+        let mut paths = self.inner.list(dir).await?;
+        for path in paths.iter_mut() {
+            let is_archive = path.extension().is_some_and(is_archive_extension);
+            // a directory may be called like an archive
+            if is_archive && self.inner.stat(path).await.is_ok_and(|m| !m.is_dir) {
+                *path = mount(path);
+            }
+        }
+        return Ok(paths);
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        let mounted = match Mounted::split(path) {
+            Some(mounted) => mounted,
+            None => return self.inner.stat(path).await,
+        };
+        let (archive, file) = self.open(&mounted.archive).await?;
+        let member = archive.stat(&mounted.member).await?;
+        Ok(Metadata {
+            // the mount is a link if the archive is
+            is_symlink: file.is_symlink && mounted.member == Path::new("/"),
+            modified: file.modified,
+            device: file.device,
+            inode: inode_of(path),
+            ..member
+        })
+    }
+
+    async fn open_range(&self, path: &Path, range: &Range) -> Result<FileStream> {
+        return match Mounted::split(path) {
+            Some(mounted) => {
+                let (archive, _) = self.open(&mounted.archive).await?;
+                archive.open_range(&mounted.member, range).await
+            }
+            None => self.inner.open_range(path, range).await,
+        };
+    }
+
+    async fn canonicalize(&self, path: &Path) -> Result<PathBuf> {
+        let mounted = match Mounted::split(path) {
+            Some(mounted) => mounted,
+            None => return self.inner.canonicalize(path).await,
+        };
+        let (archive, _) = self.open(&mounted.archive).await?;
+        let member = archive.canonicalize(&mounted.member).await?;
+        let canonical = Mounted {
+            mount: mount(&self.inner.canonicalize(&mounted.archive).await?),
+            ..mounted
+        };
+        return Ok(canonical.join(&member));
+    }
+
+    fn local_path(&self, path: &Path) -> Option<PathBuf> {
+        return match Mounted::split(path) {
+            Some(_) => None,
+            None => self.inner.local_path(path),
+        };
+    }
+}
+
+impl Mounted {
+    /// `None` if the path is not below a mount
+    fn split(path: &Path) -> Option<Self> {
+        let mut archive = PathBuf::new();
+        let mut components = path.components();
+        while let Some(component) = components.next() {
+            let mounted = match component {
+                Component::Normal(name) => mounted_extension(name),
+                _ => None,
+            };
+            if let Some(extension) = mounted {
+                let mount = archive.join(component);
+                archive.push(Path::new(&mount).with_extension(extension).file_name()?);
+                let member = Path::new("/").join(components.as_path());
+                return Some(Self {
+                    archive,
+                    mount,
+                    member,
+                });
+            }
+            archive.push(component);
+        }
+        return None;
+    }
+
+    /// the path of a member of the archive
+    fn join(&self, member: &Path) -> PathBuf {
+        let relative = member.strip_prefix("/").unwrap_or(member);
+        return match relative.as_os_str().is_empty() {
+            true => self.mount.to_owned(),
+            false => self.mount.join(relative),
+        };
+    }
+}
+
+/// "/music/album.zip" => "/music/album.zip!"
+fn mount(archive: &Path) -> PathBuf {
+    let mut name = archive.file_name().unwrap_or_default().to_owned();
+    name.push("!");
+    return archive.with_file_name(name);
+}
+
+/// "zip" for "album.zip!"
+fn mounted_extension(name: &OsStr) -> Option<&str> {
+    let extension = Path::new(name).extension()?.to_str()?.strip_suffix('!')?;
+    return is_archive_extension(OsStr::new(extension)).then_some(extension);
+}
+
+fn is_archive_extension(extension: &OsStr) -> bool {
+    ARCHIVE_EXTENSIONS
+        .iter()
+        .any(|e| extension.eq_ignore_ascii_case(e))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn paths_below_mounts_are_split() {
+        let mounted = Mounted::split(Path::new("/music/Album.ZIP!/cd 1/01.flac")).unwrap();
+        assert_eq!(mounted.archive, Path::new("/music/Album.ZIP"));
+        assert_eq!(mounted.mount, Path::new("/music/Album.ZIP!"));
+        assert_eq!(mounted.member, Path::new("/cd 1/01.flac"));
+        assert_eq!(mounted.join(Path::new("/")), mounted.mount);
+
+        let root = Mounted::split(Path::new("/comics/a.cbz!")).unwrap();
+        assert_eq!(root.member, Path::new("/"));
+    }
+
+    #[test]
+    fn other_paths_are_not_split() {
+        for path in [
+            "/music/album.zip",
+            "/music/album!/a.flac",
+            "/music/a.tar!/b",
+        ] {
+            assert!(Mounted::split(Path::new(path)).is_none(), "{}", path);
+        }
+    }
+}
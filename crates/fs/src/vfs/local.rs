@@ -0,0 +1,65 @@
+use super::{Metadata, Vfs};
+use crate::{File, FileStream, Range};
+use async_trait::async_trait;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// the file system of the operating system
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Local;
+
+#[async_trait]
+impl Vfs for Local {
+    async fn list(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        let mut entries = fs::read_dir(dir).await?;
+        let mut paths = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            paths.push(entry.path());
+        }
+        return Ok(paths);
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        let is_symlink = fs::symlink_metadata(path).await?.file_type().is_symlink();
+        let meta = fs::metadata(path).await?;
+        if !meta.is_dir() && !meta.is_file() {
+            let message = format!("{} is neither file nor directory", path.display());
+            return Err(Error::new(ErrorKind::InvalidInput, message));
+        }
+        let (device, inode) = id(path, &meta).await?;
+        Ok(Metadata {
+            is_dir: meta.is_dir(),
+            is_symlink,
+            len: meta.len(),
+            modified: meta.modified().ok(),
+            device,
+            inode,
+        })
+    }
+
+    async fn open_range(&self, path: &Path, range: &Range) -> Result<FileStream> {
+        File::new(path, 0).stream(range).await
+    }
+
+    async fn canonicalize(&self, path: &Path) -> Result<PathBuf> {
+        fs::canonicalize(path).await
+    }
+
+    fn local_path(&self, path: &Path) -> Option<PathBuf> {
+        Some(path.to_owned())
+    }
+}
+
+#[cfg(unix)]
+async fn id(_path: &Path, meta: &std::fs::Metadata) -> Result<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Ok((meta.dev(), meta.ino()))
+}
+
+// without inodes the canonical path identifies a file
+#[cfg(not(unix))]
+async fn id(path: &Path, _meta: &std::fs::Metadata) -> Result<(u64, u64)> {
+    let canonical = fs::canonicalize(path).await?;
+    Ok((0, super::inode_of(&canonical)))
+}
@@ -0,0 +1,85 @@
+#![allow(clippy::needless_return)]
+
+use super::{children, inode_of, insert, normalize, not_a_directory, not_found, Metadata, Vfs};
+use crate::{FileStream, Range};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::{stream, StreamExt};
+use std::collections::BTreeMap;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+
+/// files that only exist in memory, the parent directories of a file are created with it
+#[derive(Clone, Debug)]
+pub struct Memory {
+    /// `None` for directories
+    entries: BTreeMap<PathBuf, Option<Bytes>>,
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self {
+            entries: BTreeMap::from([(PathBuf::from("/"), None)]),
+        }
+    }
+}
+
+impl Memory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_file(mut self, path: impl AsRef<Path>, content: impl Into<Bytes>) -> Self {
+        insert(
+            &mut self.entries,
+            normalize(path.as_ref()),
+            Some(content.into()),
+        );
+        return self;
+    }
+
+    pub fn with_dir(mut self, path: impl AsRef<Path>) -> Self {
+        insert(&mut self.entries, normalize(path.as_ref()), None);
+        return self;
+    }
+
+    fn get(&self, path: &Path) -> Result<&Option<Bytes>> {
+        self.entries
+            .get(&normalize(path))
+            .ok_or_else(|| not_found(path))
+    }
+}
+
+#[async_trait]
+impl Vfs for Memory {
+    async fn list(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        match self.get(dir)? {
+            None => Ok(children(&self.entries, &normalize(dir))),
+            Some(_) => Err(not_a_directory(dir)),
+        }
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        let content = self.get(path)?;
+        Ok(Metadata {
+            is_dir: content.is_none(),
+            len: content.as_ref().map(|c| c.len() as u64).unwrap_or(0),
+            inode: inode_of(&normalize(path)),
+            ..Default::default()
+        })
+    }
+
+    async fn open_range(&self, path: &Path, range: &Range) -> Result<FileStream> {
+        let content = self.get(path)?.as_ref().ok_or_else(|| not_found(path))?;
+        let len = content.len() as u64;
+        let start = range.start().min(len);
+        let end = start.saturating_add(range.offset()).min(len);
+        let bytes = content.slice(start as usize..end as usize);
+        return Ok(stream::once(async { Ok(bytes) }).boxed());
+    }
+
+    async fn canonicalize(&self, path: &Path) -> Result<PathBuf> {
+        self.get(path)?;
+        return Ok(normalize(path));
+    }
+}
@@ -0,0 +1,147 @@
+//! file systems a library can be read from, all of them are read-only
+
+#![allow(clippy::needless_return)]
+
+use crate::{FileStream, Range};
+use async_trait::async_trait;
+use futures::StreamExt;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+mod archive;
+mod archives;
+mod local;
+mod memory;
+
+pub use archive::Archive;
+pub use archives::{Archives, ARCHIVE_EXTENSIONS};
+pub use local::Local;
+pub use memory::Memory;
+
+/// a symlink is described by its target
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Metadata {
+    pub is_dir: bool,
+    pub is_symlink: bool,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+    /// with the inode it identifies a directory independently of the path it was reached by
+    pub device: u64,
+    pub inode: u64,
+}
+
+#[async_trait]
+pub trait Vfs: Send + Sync + std::fmt::Debug {
+    /// the paths of the entries of a directory
+    async fn list(&self, dir: &Path) -> Result<Vec<PathBuf>>;
+
+    /// follows symlinks, only files and directories exist
+    async fn stat(&self, path: &Path) -> Result<Metadata>;
+
+    /// the bytes of the range, fewer if the file ends before
+    async fn open_range(&self, path: &Path, range: &Range) -> Result<FileStream>;
+
+    /// the absolute path without symlinks, fails if it does not exist
+    async fn canonicalize(&self, path: &Path) -> Result<PathBuf>;
+
+    /// the path on the disk for programs that can only read files, like a transcoder
+    fn local_path(&self, _path: &Path) -> Option<PathBuf> {
+        None
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let len = self.stat(path).await?.len;
+        let mut content = Vec::with_capacity(len as usize);
+        let mut stream = self.open_range(path, &Range::new(0, len)).await?;
+        while let Some(bytes) = stream.next().await {
+            content.extend_from_slice(&bytes?);
+        }
+        return Ok(content);
+    }
+}
+
+/// "/a/./b/../c" => "/a/c", without looking at the file system
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => {}
+        }
+    }
+    return normalized;
+}
+
+/// adds the missing parent directories with the entry, `None` is a directory
+fn insert<T>(tree: &mut BTreeMap<PathBuf, Option<T>>, path: PathBuf, entry: Option<T>) {
+    for parent in path.ancestors().skip(1) {
+        tree.entry(parent.to_owned()).or_insert(None);
+    }
+    tree.insert(path, entry);
+}
+
+/// the direct children of `dir` in a tree of normalized paths
+fn children<T>(tree: &BTreeMap<PathBuf, T>, dir: &Path) -> Vec<PathBuf> {
+    tree.range(dir.to_owned()..)
+        .map(|(path, _)| path)
+        .skip_while(|path| path.as_path() == dir)
+        .take_while(|path| path.starts_with(dir))
+        .filter(|path| path.parent() == Some(dir))
+        .cloned()
+        .collect()
+}
+
+/// stands in for the inode of files that do not have one
+fn inode_of(path: &Path) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn not_found(path: &Path) -> Error {
+    Error::new(
+        ErrorKind::NotFound,
+        format!("{} does not exist", path.display()),
+    )
+}
+
+fn not_a_directory(path: &Path) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!("{} is not a directory", path.display()),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn paths_are_normalized() {
+        assert_eq!(normalize(Path::new("/a/./b/../c")), Path::new("/a/c"));
+        assert_eq!(normalize(Path::new("a/../../b")), Path::new("/b"));
+    }
+
+    #[test]
+    fn only_direct_children() {
+        let tree: BTreeMap<PathBuf, ()> = ["/", "/a", "/a/b", "/a/b/c", "/a/d", "/ab"]
+            .iter()
+            .map(|p| (PathBuf::from(p), ()))
+            .collect();
+        assert_eq!(
+            children(&tree, Path::new("/a")),
+            [Path::new("/a/b"), Path::new("/a/d")]
+        );
+        assert_eq!(
+            children(&tree, Path::new("/")),
+            [Path::new("/a"), Path::new("/ab")]
+        );
+    }
+}
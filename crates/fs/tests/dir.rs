@@ -13,7 +13,12 @@ async fn elements() -> Result<()> {
     }
 
     let path = "./tests/data".to_owned();
-    let dir = Directory::new(path).elements().await?;
+    let dir: Vec<Entry> = Directory::new(path)
+        .elements()
+        .await?
+        .into_iter()
+        .map(|e| e.unwrap())
+        .collect();
 
     let expected = join_all(vec![
         new_entry("./tests/data/text.txt"),
@@ -37,3 +42,17 @@ async fn invalid_path_is_error() {
 
     assert!(elements.is_err());
 }
+
+#[cfg(unix)]
+#[tokio::test]
+async fn unreadable_entry_is_error_with_path() {
+    let dir = testing::TempDir::new("elements-broken");
+    std::fs::write(dir.join("a.txt"), "a").unwrap();
+    std::os::unix::fs::symlink(dir.join("missing"), dir.join("broken")).unwrap();
+
+    let elements = Directory::new(dir.path()).elements().await.unwrap();
+    assert_eq!(elements.len(), 2);
+    let error = elements.iter().find_map(|e| e.as_ref().err()).unwrap();
+    assert_eq!(error.path(), dir.join("broken"));
+    assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+}
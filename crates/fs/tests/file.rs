@@ -1,4 +1,5 @@
 use fs::{File, Range};
+use futures::TryStreamExt;
 use tokio::fs::metadata;
 
 #[tokio::test]
@@ -21,3 +22,93 @@ async fn invalid_path_is_error() {
     let chunk = file.chunk(&range).await;
     assert!(chunk.is_err());
 }
+
+#[tokio::test]
+async fn stream_equals_chunk() {
+    let path = "./tests/data/text.txt".to_owned();
+    let meta = metadata(&path).await.unwrap();
+    let file = File::new(path, meta.len());
+    let range = Range::new(10, 100);
+
+    let chunk = file.chunk(&range).await.expect("chunk is error");
+    let stream = file.stream(&range).await.expect("stream is error");
+    let streamed: Vec<u8> = stream
+        .try_fold(Vec::new(), |mut acc, bytes| async move {
+            acc.extend_from_slice(&bytes);
+            Ok(acc)
+        })
+        .await
+        .expect("reading stream failed");
+
+    assert_eq!(streamed, chunk);
+}
+
+#[tokio::test]
+async fn stream_of_invalid_path_is_error() {
+    let path = "./tests/data/not_found.invalid".to_owned();
+    let file = File::new(path, 100);
+
+    assert!(file.stream(&Range::new(0, 100)).await.is_err());
+}
+
+#[test]
+fn range_is_cut_at_end_of_file() {
+    let range = Range::new(10, 100).apply_filesize(50).unwrap();
+    assert_eq!((range.start(), range.offset()), (10, 40));
+    assert!(Range::new(50, 10).apply_filesize(50).is_none());
+    assert!(Range::new(0, 10).apply_filesize(0).is_none());
+}
+
+#[cfg(target_os = "linux")]
+mod send_to {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::{TcpListener, TcpStream};
+
+    async fn send(file: &File, range: &Range) -> (std::io::Result<u64>, Vec<u8>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let sender = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (mut receiver, _) = listener.accept().await.unwrap();
+
+        let sent = file.send_to(range, &sender).await;
+        drop(sender);
+
+        let mut received = Vec::new();
+        receiver.read_to_end(&mut received).await.unwrap();
+        (sent, received)
+    }
+
+    #[tokio::test]
+    async fn sends_range() {
+        let path = "./tests/data/text.txt".to_owned();
+        let meta = metadata(&path).await.unwrap();
+        let file = File::new(path, meta.len());
+        let range = Range::new(10, 100);
+
+        let (sent, received) = send(&file, &range).await;
+
+        assert_eq!(sent.unwrap(), 100);
+        assert_eq!(received, file.chunk(&range).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn stops_at_end_of_file() {
+        let path = "./tests/data/text.txt".to_owned();
+        let meta = metadata(&path).await.unwrap();
+        let file = File::new(path, meta.len());
+
+        let (sent, received) = send(&file, &Range::new(0, meta.len() + 100)).await;
+
+        assert_eq!(sent.unwrap(), meta.len());
+        assert_eq!(received.len() as u64, meta.len());
+    }
+
+    #[tokio::test]
+    async fn invalid_path_is_error() {
+        let file = File::new("./tests/data/not_found.invalid".to_owned(), 100);
+        let (sent, _) = send(&file, &Range::new(0, 100)).await;
+        assert!(sent.is_err());
+    }
+}
@@ -0,0 +1,206 @@
+use fs::{Archive, Archives, Local, Memory, Range, Vfs, WalkOptions, Walker};
+use futures::StreamExt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use testing::TempDir;
+use zip::write::FileOptions;
+use zip::CompressionMethod;
+
+const TEXT: &[u8] = b"the quick brown fox jumps over the lazy dog";
+
+/// "stored.txt" is stored and "dir/deflated.txt" deflated
+fn write_zip(path: &Path) {
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("stored.txt", stored).unwrap();
+    zip.write_all(TEXT).unwrap();
+    let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file("dir/deflated.txt", deflated).unwrap();
+    zip.write_all(TEXT).unwrap();
+    zip.start_file("../outside.txt", stored).unwrap();
+    zip.write_all(TEXT).unwrap();
+    zip.finish().unwrap();
+}
+
+async fn read_range(vfs: &dyn Vfs, path: &str, range: Range) -> Vec<u8> {
+    let mut stream = vfs.open_range(Path::new(path), &range).await.unwrap();
+    let mut content = Vec::new();
+    while let Some(bytes) = stream.next().await {
+        content.extend_from_slice(&bytes.unwrap());
+    }
+    content
+}
+
+async fn files(vfs: Arc<dyn Vfs>, root: &str) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = Walker::new(vfs, root, WalkOptions::default())
+        .filter_map(|e| async move { e.ok().filter(|e| e.is_file()) })
+        .map(|e| e.path().to_owned())
+        .collect()
+        .await;
+    files.sort();
+    files
+}
+
+#[tokio::test]
+async fn memory_has_parents_of_files() {
+    let memory = Memory::new()
+        .with_file("/movies/a/a.mp4", TEXT)
+        .with_dir("/empty");
+
+    let meta = memory.stat(Path::new("/movies/a")).await.unwrap();
+    assert!(meta.is_dir);
+    let mut root = memory.list(Path::new("/")).await.unwrap();
+    root.sort();
+    assert_eq!(root, [Path::new("/empty"), Path::new("/movies")]);
+    assert_eq!(
+        memory.stat(Path::new("/movies/a/a.mp4")).await.unwrap().len,
+        43
+    );
+    assert!(memory.stat(Path::new("/movies/b")).await.is_err());
+}
+
+#[tokio::test]
+async fn memory_reads_ranges() {
+    let memory = Memory::new().with_file("/a.txt", TEXT);
+
+    assert_eq!(
+        read_range(&memory, "/a.txt", Range::new(4, 5)).await,
+        b"quick"
+    );
+    assert_eq!(
+        read_range(&memory, "/a.txt", Range::new(40, 10)).await,
+        b"dog"
+    );
+    assert_eq!(memory.read(Path::new("/./a.txt")).await.unwrap(), TEXT);
+}
+
+#[tokio::test]
+async fn walk_in_memory() {
+    let memory = Memory::new()
+        .with_file("/lib/a.mp4", TEXT)
+        .with_file("/lib/x/b.mp4", TEXT)
+        .with_file("/other/c.mp4", TEXT);
+
+    let files = files(Arc::new(memory), "/lib").await;
+    assert_eq!(files, [Path::new("/lib/a.mp4"), Path::new("/lib/x/b.mp4")]);
+}
+
+#[tokio::test]
+async fn local_reads_files() {
+    let local = Local;
+    let path = "./tests/data/text.txt";
+    let meta = local.stat(Path::new(path)).await.unwrap();
+    let content = std::fs::read(path).unwrap();
+
+    assert!(!meta.is_dir);
+    assert_eq!(meta.len, content.len() as u64);
+    assert_eq!(local.read(Path::new(path)).await.unwrap(), content);
+    assert_eq!(
+        read_range(&local, path, Range::new(10, 20)).await,
+        content[10..30]
+    );
+    assert_eq!(local.local_path(Path::new(path)), Some(PathBuf::from(path)));
+}
+
+#[tokio::test]
+async fn zip_members_are_read() {
+    let dir = TempDir::new("vfs-zip");
+    let path = dir.join("members.zip");
+    write_zip(&path);
+    let archive = Archive::open(&path).await.unwrap();
+
+    let files = files(Arc::new(archive.clone()), "/").await;
+    assert_eq!(
+        files,
+        [Path::new("/dir/deflated.txt"), Path::new("/stored.txt")]
+    );
+    for path in ["/stored.txt", "/dir/deflated.txt"] {
+        assert_eq!(archive.stat(Path::new(path)).await.unwrap().len, 43);
+        assert_eq!(archive.read(Path::new(path)).await.unwrap(), TEXT);
+        assert_eq!(read_range(&archive, path, Range::new(4, 5)).await, b"quick");
+        assert_eq!(read_range(&archive, path, Range::new(40, 10)).await, b"dog");
+    }
+    assert!(archive.local_path(Path::new("/stored.txt")).is_none());
+}
+
+#[tokio::test]
+async fn tar_members_are_read() {
+    let dir = TempDir::new("vfs-tar");
+    let path = dir.join("members.tar");
+    let mut tar = tar::Builder::new(std::fs::File::create(&path).unwrap());
+    let mut header = tar::Header::new_gnu();
+    header.set_size(TEXT.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    tar.append_data(&mut header, "music/a.flac", TEXT).unwrap();
+    tar.into_inner().unwrap().flush().unwrap();
+
+    let archive = Archive::open(&path).await.unwrap();
+    assert!(archive.stat(Path::new("/music")).await.unwrap().is_dir);
+    assert_eq!(
+        read_range(&archive, "/music/a.flac", Range::new(10, 5)).await,
+        b"brown"
+    );
+}
+
+#[tokio::test]
+async fn broken_archive_is_error() {
+    let dir = TempDir::new("vfs-broken");
+    let path = dir.join("broken.zip");
+    std::fs::write(&path, TEXT).unwrap();
+    assert!(Archive::open(&path).await.is_err());
+}
+
+#[tokio::test]
+async fn archives_are_mounted() {
+    let dir = TempDir::new("vfs-mounted");
+    std::fs::create_dir_all(dir.join("folder.zip")).unwrap();
+    std::fs::write(dir.join("a.txt"), TEXT).unwrap();
+    write_zip(&dir.join("album.zip"));
+    let root = dir.to_string_lossy().to_string();
+
+    let archives = Arc::new(Archives::new(Arc::new(Local)));
+    let files = files(archives.clone(), &root).await;
+    assert_eq!(
+        files,
+        [
+            dir.join("a.txt"),
+            dir.join("album.zip!/dir/deflated.txt"),
+            dir.join("album.zip!/stored.txt"),
+        ]
+    );
+
+    let stored = format!("{}/album.zip!/stored.txt", root);
+    assert!(archives.stat(&dir.join("album.zip!")).await.unwrap().is_dir);
+    assert_eq!(
+        read_range(archives.as_ref(), &stored, Range::new(4, 5)).await,
+        b"quick"
+    );
+    assert_eq!(
+        archives.canonicalize(Path::new(&stored)).await.unwrap(),
+        std::fs::canonicalize(&dir)
+            .unwrap()
+            .join("album.zip!/stored.txt")
+    );
+    assert!(archives.local_path(Path::new(&stored)).is_none());
+    assert!(archives
+        .stat(&dir.join("album.zip!/missing"))
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn changed_archives_are_read_again() {
+    let dir = TempDir::new("vfs-changed");
+    let archive = dir.join("a.cbz");
+    write_zip(&archive);
+    let archives = Archives::new(Arc::new(Local));
+    assert!(archives.stat(&dir.join("a.cbz!/stored.txt")).await.is_ok());
+
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(&archive).unwrap());
+    zip.start_file("page.jpg", FileOptions::default()).unwrap();
+    zip.finish().unwrap();
+    assert!(archives.stat(&dir.join("a.cbz!/stored.txt")).await.is_err());
+    assert!(archives.stat(&dir.join("a.cbz!/page.jpg")).await.is_ok());
+}
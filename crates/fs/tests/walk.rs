@@ -0,0 +1,225 @@
+#![cfg(unix)]
+
+use fs::{Directory, WalkOptions};
+use std::os::unix::fs::symlink;
+use std::path::Path;
+use testing::TempDir;
+
+/// "root/a.txt", "root/x/x.txt", "root/x/deep/deep.txt" and "root/y/y.txt", "root/x/up" links to
+/// root, "root/x/to_y" to "root/y" and "root/y/to_x" back to "root/x"
+fn cyclic_tree(name: &str) -> TempDir {
+    let dir = TempDir::new(&format!("walk-{}", name));
+    std::fs::create_dir_all(dir.join("x/deep")).unwrap();
+    std::fs::create_dir_all(dir.join("y")).unwrap();
+    for file in ["a.txt", "x/x.txt", "x/deep/deep.txt", "y/y.txt"] {
+        std::fs::write(dir.join(file), file).unwrap();
+    }
+    symlink(&dir, dir.join("x/up")).unwrap();
+    symlink(dir.join("y"), dir.join("x/to_y")).unwrap();
+    symlink(dir.join("x"), dir.join("y/to_x")).unwrap();
+    dir
+}
+
+fn directory(path: &Path) -> Directory {
+    Directory::new(path)
+}
+
+async fn names(dir: &Path, options: WalkOptions) -> Vec<String> {
+    let mut names: Vec<String> = directory(dir)
+        .files_recursively_with(options)
+        .await
+        .into_iter()
+        .map(|f| f.unwrap().name_with_extension())
+        .collect();
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn cycles_end() {
+    let dir = cyclic_tree("cycles");
+    let names = names(&dir, WalkOptions::default()).await;
+    assert_eq!(names, ["a.txt", "deep.txt", "x.txt", "y.txt"]);
+}
+
+#[tokio::test]
+async fn symlinks_are_not_followed() {
+    let dir = cyclic_tree("no-follow");
+    std::fs::remove_dir_all(dir.join("y")).unwrap();
+    std::fs::create_dir(dir.join("y")).unwrap();
+    let outside = cyclic_tree("no-follow-outside");
+    symlink(&outside, dir.join("y/outside")).unwrap();
+
+    let options = WalkOptions {
+        follow_symlinks: false,
+        ..Default::default()
+    };
+    assert_eq!(names(&dir, options).await, ["a.txt", "deep.txt", "x.txt"]);
+    assert_eq!(names(&dir, WalkOptions::default()).await.len(), 7);
+}
+
+#[tokio::test]
+async fn max_depth_limits_levels() {
+    let dir = cyclic_tree("depth");
+    let depth = |max_depth| WalkOptions {
+        max_depth: Some(max_depth),
+        ..Default::default()
+    };
+    assert_eq!(names(&dir, depth(0)).await, ["a.txt"]);
+    assert_eq!(names(&dir, depth(1)).await, ["a.txt", "x.txt", "y.txt"]);
+    assert_eq!(names(&dir, depth(2)).await.len(), 4);
+}
+
+#[tokio::test]
+async fn same_filesystem_enters_local_directories() {
+    let dir = cyclic_tree("same-fs");
+    let options = WalkOptions {
+        same_filesystem: true,
+        ..Default::default()
+    };
+    assert_eq!(names(&dir, options).await.len(), 4);
+}
+
+mod walker {
+    use super::*;
+    use futures::StreamExt;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::PathBuf;
+
+    /// the paths of every entry relative to `dir`
+    async fn paths(dir: &Path, options: WalkOptions) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = directory(dir)
+            .walk(options)
+            .map(|e| e.unwrap().path().strip_prefix(dir).unwrap().to_owned())
+            .collect()
+            .await;
+        paths.sort();
+        paths
+    }
+
+    #[tokio::test]
+    async fn directory_is_entered_once() {
+        let dir = cyclic_tree("once");
+        let paths = paths(&dir, WalkOptions::default()).await;
+
+        // the links are listed, but their targets were entered by another path
+        for link in ["x/up", "x/to_y", "y/to_x"] {
+            assert!(paths.contains(&PathBuf::from(link)), "{}", link);
+            assert!(!paths
+                .iter()
+                .any(|p| p.starts_with(link) && p != Path::new(link)));
+        }
+        assert_eq!(paths.len(), 10, "{:?}", paths);
+    }
+
+    #[tokio::test]
+    async fn depth_is_limited() {
+        let dir = cyclic_tree("depth-limit");
+        let options = WalkOptions {
+            max_depth: Some(1),
+            ..Default::default()
+        };
+        let entries: Vec<_> = directory(&dir).walk(options).collect().await;
+        let depths = entries.iter().map(|e| e.as_ref().unwrap().depth());
+
+        // the entries of the subdirectories of the root have depth 2
+        assert_eq!(depths.max(), Some(2));
+        let paths = paths(&dir, options).await;
+        assert!(paths.contains(&PathBuf::from("x/deep")));
+        assert!(!paths.contains(&PathBuf::from("x/deep/deep.txt")));
+    }
+
+    #[tokio::test]
+    async fn entries_carry_metadata() {
+        let dir = cyclic_tree("metadata");
+        let entries: Vec<_> = directory(&dir).walk(WalkOptions::default()).collect().await;
+        let file = entries
+            .into_iter()
+            .map(|e| e.unwrap())
+            .find(|e| e.path().ends_with("x.txt"))
+            .unwrap();
+        let meta = std::fs::metadata(dir.join("x/x.txt")).unwrap();
+
+        assert!(file.is_file());
+        assert_eq!(file.depth(), 2);
+        assert_eq!(file.size(), "x/x.txt".len() as u64);
+        assert_eq!(file.inode(), meta.ino());
+        assert_eq!(file.modified(), meta.modified().ok());
+    }
+
+    #[tokio::test]
+    async fn errors_carry_the_path() {
+        let dir = cyclic_tree("errors");
+        symlink(dir.join("missing"), dir.join("x/broken")).unwrap();
+        let errors: Vec<_> = directory(&dir)
+            .walk(WalkOptions::default())
+            .filter_map(|e| async move { e.err() })
+            .collect()
+            .await;
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].path().ends_with("x/broken"));
+        assert_eq!(errors[0].kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[tokio::test]
+    async fn files_recursively_reports_errors() {
+        let dir = cyclic_tree("files-errors");
+        symlink(dir.join("missing"), dir.join("x/broken")).unwrap();
+        let files = directory(&dir).files_recursively().await;
+
+        assert_eq!(files.iter().filter(|f| f.is_ok()).count(), 4);
+        let error = files.iter().find_map(|f| f.as_ref().err()).unwrap();
+        assert!(error.path().ends_with("x/broken"));
+    }
+
+    #[tokio::test]
+    async fn missing_root_is_error() {
+        let temp_dir = TempDir::new("walk-not-found");
+        let dir = temp_dir.join("missing");
+        let entries: Vec<_> = directory(&dir).walk(WalkOptions::default()).collect().await;
+
+        assert_eq!(entries.len(), 1);
+        let error = entries.into_iter().next().unwrap().unwrap_err();
+        assert_eq!(error.path(), dir);
+    }
+
+    #[tokio::test]
+    async fn skipped_dir_is_not_entered() {
+        let dir = cyclic_tree("skip");
+        let mut walker = directory(&dir).walk(WalkOptions {
+            concurrency: 1,
+            ..Default::default()
+        });
+        let mut names = Vec::new();
+        while let Some(entry) = walker.next().await {
+            let entry = entry.unwrap();
+            // "y/to_x" leads to "x" as well
+            if entry.path().ends_with("x") || entry.path().ends_with("to_x") {
+                walker.skip_current_dir();
+            }
+            names.extend(entry.file().map(|f| f.name_with_extension()));
+        }
+        names.sort();
+        assert_eq!(names, ["a.txt", "y.txt"]);
+    }
+
+    #[tokio::test]
+    async fn non_utf8_names_are_kept() {
+        use std::os::unix::ffi::OsStrExt;
+        let dir = cyclic_tree("non-utf8");
+        let name = std::ffi::OsStr::from_bytes(b"caf\xe9.txt");
+        std::fs::write(dir.join(name), "café").unwrap();
+
+        let files = directory(&dir)
+            .files_recursively_with(WalkOptions::default())
+            .await;
+        let file = files
+            .iter()
+            .map(|f| f.as_ref().unwrap())
+            .find(|f| f.path().file_name() == Some(name))
+            .unwrap();
+        let content = file.chunk(&fs::Range::new(0, 10)).await.unwrap();
+        assert_eq!(content, "café".as_bytes());
+    }
+}
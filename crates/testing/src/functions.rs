@@ -1,3 +1,5 @@
+#![allow(clippy::needless_return)]
+
 use crate::error::{Error, Result};
 
 pub fn assert_vec_equal<T: std::cmp::PartialEq + std::fmt::Debug>(
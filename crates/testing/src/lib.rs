@@ -1,2 +1,4 @@
 pub mod functions;
+mod temp_dir;
 pub use anyhow as error;
+pub use temp_dir::TempDir;
@@ -0,0 +1,17 @@
+#!/bin/sh
+# runs the database tests of the app against a throwaway postgres container
+set -e
+
+CONTAINER=netflex-test-postgres
+PORT=${PORT:-55432}
+
+docker run --rm -d --name "$CONTAINER" -p "$PORT:5432" \
+    -e POSTGRES_HOST_AUTH_METHOD=trust postgres:15 >/dev/null
+trap 'docker stop "$CONTAINER" >/dev/null' EXIT
+
+until docker exec "$CONTAINER" pg_isready -U postgres >/dev/null 2>&1; do
+    sleep 1
+done
+
+NETFLEX_TEST_DATABASE_URL="postgres://postgres@127.0.0.1:$PORT/postgres" \
+    cargo test -p app --features postgres --lib "$@"